@@ -1,9 +1,110 @@
-use crate::ca_game::CaGame;
-use crate::factions::Wh2Factions;
-use crate::ymd_hms_dash_format;
-use crate::ymd_hms_dash_format::YMD_HMS_FORMAT;
-use chrono::Utc;
-use std::path::PathBuf;
+use crate::army_build::ArmyBuild;
+use crate::name_search::get_name_match_score;
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use eframe::egui;
+use eframe::egui::Ui;
+
+const MAX_SEARCH_FUNDS: u32 = 100000;
+
+//One check on a build, a search is every filter in it passing
+#[derive(Debug, Clone, PartialEq)]
+pub enum SearchFilter {
+    Name(String),   // fuzzy & ranked
+    Phrase(String), // exactly as typed, ignoring case
+    Faction(String), // registry faction name
+    VsFaction(String),
+    FundsRange { min: u32, max: u32 },
+    CreatedAfter(DateTime<Utc>),
+    CreatedBefore(DateTime<Utc>),
+    GameMod(String),
+    CreatedBy(String),
+    Notes(String),
+    MinWinPercent(f64),
+    MinGamesPlayed(u32),
+}
+
+fn contains_ignore_case(haystack: &str, needle: &str) -> bool {
+    haystack
+        .to_ascii_lowercase()
+        .contains(needle.to_ascii_lowercase().as_str())
+}
+
+pub fn is_filter_match(filter: &SearchFilter, build: &ArmyBuild) -> bool {
+    match filter {
+        SearchFilter::Name(name) => get_name_match_score(name, build.file_stem.as_str()).is_some(),
+        SearchFilter::Phrase(p) => contains_ignore_case(build.file_stem.as_str(), p),
+        SearchFilter::Faction(f) => build.faction_str.eq_ignore_ascii_case(f),
+        SearchFilter::VsFaction(f) => build.vs_faction_str.eq_ignore_ascii_case(f),
+        SearchFilter::FundsRange { min, max } => *min <= build.funds && build.funds <= *max,
+        SearchFilter::CreatedAfter(t) => build.created_on >= *t,
+        SearchFilter::CreatedBefore(t) => build.created_on < *t,
+        SearchFilter::GameMod(m) => contains_ignore_case(build.game_mod.as_str(), m),
+        SearchFilter::CreatedBy(c) => contains_ignore_case(build.created_by.as_str(), c),
+        SearchFilter::Notes(n) => contains_ignore_case(build.notes.as_str(), n),
+        SearchFilter::MinWinPercent(p) => {
+            build.get_games_played_count() > 0 && build.get_win_percent() >= *p
+        }
+        SearchFilter::MinGamesPlayed(n) => build.get_games_played_count() >= *n,
+    }
+}
+
+//None if the build is filtered out, otherwise how well it matched the name queries
+pub fn get_search_score(filters: &[SearchFilter], build: &ArmyBuild) -> Option<f64> {
+    let mut score = 0.0;
+    for f in filters {
+        match f {
+            SearchFilter::Name(name) => {
+                score += get_name_match_score(name, build.file_stem.as_str())?
+            }
+            _ if !is_filter_match(f, build) => return None,
+            _ => {}
+        }
+    }
+    Some(score)
+}
+
+pub fn has_name_query(filters: &[SearchFilter]) -> bool {
+    filters.iter().any(|f| matches!(f, SearchFilter::Name(_)))
+}
+
+//Takes 2021, 2021-01 or 2021-01-31, the start of that year/month/day & the start of the next one
+pub fn parse_search_date_range(date_str: &str) -> Result<(DateTime<Utc>, DateTime<Utc>), String> {
+    let err = || format!("\'{}\' ain't a date, use YYYY-MM-DD", date_str);
+    let parts: Vec<&str> = date_str.trim().split('-').collect();
+    let year = parts[0].parse::<i32>().map_err(|_| err())?;
+    let nums: Vec<u32> = parts[1..]
+        .iter()
+        .map(|p| p.parse::<u32>())
+        .collect::<Result<_, _>>()
+        .map_err(|_| err())?;
+    let next_year = || year.checked_add(1).ok_or_else(err);
+    let (start, end) = match nums.as_slice() {
+        [] => (
+            NaiveDate::from_ymd_opt(year, 1, 1),
+            NaiveDate::from_ymd_opt(next_year()?, 1, 1),
+        ),
+        [12] => (
+            NaiveDate::from_ymd_opt(year, 12, 1),
+            NaiveDate::from_ymd_opt(next_year()?, 1, 1),
+        ),
+        [m] => (
+            NaiveDate::from_ymd_opt(year, *m, 1),
+            NaiveDate::from_ymd_opt(year, m.checked_add(1).ok_or_else(err)?, 1),
+        ),
+        [m, d] => {
+            let start = NaiveDate::from_ymd_opt(year, *m, *d);
+            (start, start.and_then(|s| s.succ_opt()))
+        }
+        _ => return Err(err()),
+    };
+    let start = start.and_then(|d| d.and_hms_opt(0, 0, 0)).ok_or_else(err)?;
+    let end = end.and_then(|d| d.and_hms_opt(0, 0, 0)).ok_or_else(err)?;
+    Ok((Utc.from_utc_datetime(&start), Utc.from_utc_datetime(&end)))
+}
+
+pub fn parse_search_date(date_str: &str) -> Result<DateTime<Utc>, String> {
+    parse_search_date_range(date_str).map(|(start, _)| start)
+}
 
 #[cfg_attr(
     feature = "persistence",
@@ -11,41 +112,241 @@ use std::path::PathBuf;
 )]
 #[derive()]
 pub struct AdvancedSearch {
-    pub name: String, //also acts as display name
+    pub enabled: bool,
 
-    #[serde(with = "ymd_hms_dash_format")]
-    pub created_on_search: chrono::DateTime<Utc>,
-    pub check_after_created: bool,
+    pub min_funds: u32,
+    pub max_funds: u32,
 
-    pub faction: Wh2Factions,
-    pub vs_faction: Wh2Factions,
+    pub created_after: String, // YYYY-MM-DD, empty for any
+    pub created_before: String,
 
-    pub ca_game: CaGame,
     pub game_mod: String,
     pub created_by: String,
+    pub notes: String,
 
-    pub faction_str: String,    // for display, nonwarhammer2 games, & mods
-    pub vs_faction_str: String, // for display, nonwarhammer2 games, & mods
-
-    pub funds: u32,
+    pub min_win_percent: f64, // 0 for any
+    pub min_games_played: u32,
 }
 
 impl Default for AdvancedSearch {
     fn default() -> Self {
         Self {
-            name: String::new(),
-            faction: Wh2Factions::UNKNOWN,
-            vs_faction: Wh2Factions::UNKNOWN,
-            created_on_search: std::time::SystemTime::now().into(),
-            check_after_created: true,
-
-            ca_game: CaGame::Warhammer2,
+            enabled: false,
+            min_funds: 0,
+            max_funds: MAX_SEARCH_FUNDS,
+            created_after: String::new(),
+            created_before: String::new(),
             game_mod: String::new(),
             created_by: String::new(),
-            faction_str: String::new(),
-            vs_faction_str: String::new(),
+            notes: String::new(),
+            min_win_percent: 0.0,
+            min_games_played: 0,
+        }
+    }
+}
+
+impl AdvancedSearch {
+    //Filters for every field set, dates that don't parse are left out & shown by get_errors
+    pub fn get_filters(&self) -> Vec<SearchFilter> {
+        let mut filters = vec![];
+        if self.min_funds > 0 || self.max_funds < MAX_SEARCH_FUNDS {
+            filters.push(SearchFilter::FundsRange {
+                min: self.min_funds,
+                max: self.max_funds,
+            });
+        }
+        if let Ok(t) = parse_search_date(self.created_after.as_str()) {
+            filters.push(SearchFilter::CreatedAfter(t));
+        }
+        if let Ok(t) = parse_search_date(self.created_before.as_str()) {
+            filters.push(SearchFilter::CreatedBefore(t));
+        }
+        if !self.game_mod.is_empty() {
+            filters.push(SearchFilter::GameMod(self.game_mod.clone()));
+        }
+        if !self.created_by.is_empty() {
+            filters.push(SearchFilter::CreatedBy(self.created_by.clone()));
+        }
+        if !self.notes.is_empty() {
+            filters.push(SearchFilter::Notes(self.notes.clone()));
+        }
+        if self.min_win_percent > 0.0 {
+            filters.push(SearchFilter::MinWinPercent(self.min_win_percent));
+        }
+        if self.min_games_played > 0 {
+            filters.push(SearchFilter::MinGamesPlayed(self.min_games_played));
+        }
+        filters
+    }
+
+    pub fn get_errors(&self) -> Vec<String> {
+        [&self.created_after, &self.created_before]
+            .iter()
+            .filter(|d| !d.trim().is_empty())
+            .filter_map(|d| parse_search_date(d.as_str()).err())
+            .collect()
+    }
 
+    fn str_row(ui: &mut Ui, title: &str, edit_str: &mut String) -> bool {
+        ui.horizontal(|ui| {
+            ui.label(title);
+            ui.text_edit_singleline(edit_str).changed()
+        })
+        .inner
+    }
+
+    //returns true if anything changed so the caller can re-run the search
+    pub fn advanced_search_ui(&mut self, ui: &mut Ui) -> bool {
+        let mut changed = false;
+        changed |= ui
+            .checkbox(&mut self.enabled, "Use Advanced Search")
+            .changed();
+
+        ui.horizontal(|ui| {
+            ui.label("Funds");
+            changed |= ui
+                .add(egui::Slider::new(&mut self.min_funds, 0..=MAX_SEARCH_FUNDS).text("min"))
+                .changed();
+            changed |= ui
+                .add(egui::Slider::new(&mut self.max_funds, 0..=MAX_SEARCH_FUNDS).text("max"))
+                .changed();
+        });
+
+        changed |= AdvancedSearch::str_row(ui, "Created After", &mut self.created_after);
+        changed |= AdvancedSearch::str_row(ui, "Created Before", &mut self.created_before);
+        changed |= AdvancedSearch::str_row(ui, "Mod", &mut self.game_mod);
+        changed |= AdvancedSearch::str_row(ui, "Created By", &mut self.created_by);
+        changed |= AdvancedSearch::str_row(ui, "Notes", &mut self.notes);
+
+        ui.horizontal(|ui| {
+            changed |= ui
+                .add(egui::Slider::new(&mut self.min_win_percent, 0.0..=100.0).text("min % W"))
+                .changed();
+            changed |= ui
+                .add(egui::DragValue::new(&mut self.min_games_played).prefix("min played "))
+                .changed();
+        });
+
+        for e in self.get_errors() {
+            ui.colored_label(egui::Color32::RED, e);
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_build() -> ArmyBuild {
+        ArmyBuild {
+            file_stem: "GS vs HE Night Goblin Rush".to_string(),
+            faction_str: "Greenskins".to_string(),
+            vs_faction_str: "High Elves".to_string(),
             funds: 12400,
+            created_on: Utc.with_ymd_and_hms(2021, 3, 15, 20, 0, 0).unwrap(),
+            game_mod: "SFO Grimhammer".to_string(),
+            created_by: "Felkon".to_string(),
+            notes: "Rush the left flank".to_string(),
+            win_count: 3,
+            loss_count: 1,
+            draw_count: 0,
+            ..ArmyBuild::default()
+        }
+    }
+
+    fn get_date(date_str: &str) -> DateTime<Utc> {
+        parse_search_date(date_str).unwrap()
+    }
+
+    #[test]
+    fn each_filter_matches() {
+        let build = get_build();
+        let cases = vec![
+            (SearchFilter::Name("night gob".to_string()), true),
+            (SearchFilter::Name("dwarf gyrocopter".to_string()), false),
+            (SearchFilter::Phrase("goblin rush".to_string()), true),
+            (SearchFilter::Phrase("rush goblin".to_string()), false),
+            (SearchFilter::Faction("greenskins".to_string()), true),
+            (SearchFilter::Faction("Greenskin".to_string()), false),
+            (SearchFilter::VsFaction("High Elves".to_string()), true),
+            (SearchFilter::VsFaction("Greenskins".to_string()), false),
+            (
+                SearchFilter::FundsRange {
+                    min: 12400,
+                    max: 12400,
+                },
+                true,
+            ),
+            (SearchFilter::FundsRange { min: 0, max: 12399 }, false),
+            (SearchFilter::CreatedAfter(get_date("2021-03-15")), true),
+            (SearchFilter::CreatedAfter(get_date("2021-03-16")), false),
+            (SearchFilter::CreatedBefore(get_date("2021-03-16")), true),
+            (SearchFilter::CreatedBefore(get_date("2021-03-15")), false),
+            (SearchFilter::GameMod("sfo".to_string()), true),
+            (SearchFilter::GameMod("radious".to_string()), false),
+            (SearchFilter::CreatedBy("felk".to_string()), true),
+            (SearchFilter::CreatedBy("turin".to_string()), false),
+            (SearchFilter::Notes("LEFT FLANK".to_string()), true),
+            (SearchFilter::Notes("right flank".to_string()), false),
+            (SearchFilter::MinWinPercent(75.0), true), // 0 to 100, not 0 to 1
+            (SearchFilter::MinWinPercent(76.0), false),
+            (SearchFilter::MinGamesPlayed(4), true),
+            (SearchFilter::MinGamesPlayed(5), false),
+        ];
+        for (filter, is_match) in cases {
+            assert_eq!(is_filter_match(&filter, &build), is_match, "{:?}", filter);
         }
+
+        //never played has no win rate to pass with
+        let unplayed = ArmyBuild {
+            win_count: 0,
+            loss_count: 0,
+            ..get_build()
+        };
+        assert!(!is_filter_match(
+            &SearchFilter::MinWinPercent(0.0),
+            &unplayed
+        ));
+        assert!(is_filter_match(&SearchFilter::MinGamesPlayed(0), &unplayed));
+    }
+
+    #[test]
+    fn score_ranks_names_and_drops_misses() {
+        let build = get_build();
+        let faction = SearchFilter::Faction("Greenskins".to_string());
+        assert_eq!(get_search_score(std::slice::from_ref(&faction), &build), Some(0.0));
+        assert_eq!(get_search_score(&[], &build), Some(0.0));
+
+        let close = get_search_score(
+            &[
+                SearchFilter::Name("night goblin".to_string()),
+                faction.clone(),
+            ],
+            &build,
+        )
+        .unwrap();
+        let fuzzy = get_search_score(&[SearchFilter::Name("nigth goblin".to_string())], &build);
+        assert!(close > 0.0);
+        assert!(close > fuzzy.unwrap(), "{} {:?}", close, fuzzy);
+
+        //any filter failing drops it, names included
+        assert_eq!(
+            get_search_score(
+                &[
+                    SearchFilter::Name("night goblin".to_string()),
+                    SearchFilter::Faction("Dwarfs".to_string()),
+                ],
+                &build
+            ),
+            None
+        );
+        assert_eq!(
+            get_search_score(
+                &[SearchFilter::Name("gyrocopter".to_string()), faction],
+                &build
+            ),
+            None
+        );
     }
 }
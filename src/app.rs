@@ -1,7 +1,10 @@
 use crate::army_setups_manager::ArmySetupsManager;
-use crate::ca_game::{get_ca_game_title, GameSelector};
+use crate::build_watcher::BuildWatcher;
+use crate::ca_game::{get_ca_game_title, CaGame, GameSelector};
 use crate::central_panel_state::{AppState, CentralPanelState};
 use crate::resources_panel;
+use crate::stats_panel::StatsPanel;
+use crate::tier_list_panel::TierListPanel;
 use eframe::{egui, epi};
 
 /// We derive Deserialize/Serialize so we can persist app state on shutdown.
@@ -10,6 +13,14 @@ pub struct OwaaghApp {
     app_state: AppState,
     pub army_setups_manager: ArmySetupsManager,
     game_selector: GameSelector,
+    #[serde(default)]
+    stats_panel: StatsPanel,
+    #[serde(default)]
+    tier_list_panel: TierListPanel,
+    #[serde(skip)]
+    build_watcher: Option<BuildWatcher>,
+    #[serde(skip)]
+    watched_game: Option<CaGame>, // set even when the watcher failed so it isn't retried every frame
 }
 
 impl Default for OwaaghApp {
@@ -18,6 +29,31 @@ impl Default for OwaaghApp {
             army_setups_manager: Default::default(),
             app_state: Default::default(),
             game_selector: Default::default(),
+            stats_panel: Default::default(),
+            tier_list_panel: Default::default(),
+            build_watcher: None,
+            watched_game: None,
+        }
+    }
+}
+
+impl OwaaghApp {
+    //Follows the selected game, changes it saw since last frame go into the builds
+    fn update_build_watcher(&mut self, frame: &mut epi::Frame<'_>) {
+        if self.watched_game.as_ref() != Some(&self.app_state.ca_game) {
+            self.build_watcher = None;
+            self.build_watcher =
+                match BuildWatcher::new(&self.app_state.ca_game, frame.repaint_signal()) {
+                    Ok(w) => Some(w),
+                    Err(e) => {
+                        println!("{}", e);
+                        None
+                    }
+                };
+            self.watched_game = Some(self.app_state.ca_game.clone());
+        }
+        if let Some(watcher) = self.build_watcher.as_ref() {
+            self.army_setups_manager.apply_build_changes(watcher.poll());
         }
     }
 }
@@ -43,10 +79,15 @@ impl epi::App for OwaaghApp {
     /// Called each time the UI needs repainting, which may be many times per second.
     /// Put your widgets into a `SidePanel`, `TopPanel`, `CentralPanel`, `Window` or `Area`.
     fn update(&mut self, ctx: &egui::CtxRef, frame: &mut epi::Frame<'_>) {
+        self.update_build_watcher(frame);
+
         let OwaaghApp {
             army_setups_manager,
             app_state,
             game_selector,
+            stats_panel,
+            tier_list_panel,
+            ..
         } = self;
 
         egui::SidePanel::left("side_panel", 200.0).show(ctx, |ui| {
@@ -68,14 +109,11 @@ impl epi::App for OwaaghApp {
             }
             CentralPanelState::BuildManager => army_setups_manager.central_panel_ui(ui, ctx),
             CentralPanelState::TierList => {
-                ui.label("Greenskins da Best");
+                tier_list_panel.central_panel_ui(ui, &app_state.ca_game, army_setups_manager);
+            }
+            CentralPanelState::Stats => {
+                stats_panel.central_panel_ui(ui, &app_state.ca_game, army_setups_manager);
             }
-            // CentralPanelState::Replays => {
-            //     ui.horizontal(|ui| {
-            //         ui.label("To Do In OWAAGH");
-            //         ui.hyperlink("https://www.twitch.tv/gudgitz");
-            //     });
-            // }
             CentralPanelState::Resources => {
                 resources_panel::central_panel_ui(ui, ctx);
             }
@@ -6,6 +6,8 @@ use chrono::{DateTime, Utc};
 use eframe::egui;
 use eframe::egui::Ui;
 use enum_iterator::IntoEnumIterator;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
 use std::path::PathBuf;
 use std::cmp::Ordering;
@@ -33,39 +35,50 @@ pub struct ArmyBuild {
     pub faction_str: String,    // for display, nonwarhammer2 games, & mods
     pub vs_faction_str: String, // for display, nonwarhammer2 games, & mods
 
-    pub win_count: u32,
+    pub win_count: u32, // counters come from the match log
     pub loss_count: u32,
+    #[serde(default)]
+    pub draw_count: u32,
 
     pub funds: u32,
 
     pub image_files: Vec<PathBuf>,
     pub notes: String,
+
+    #[serde(default)]
+    pub unit_keys: Vec<String>, // filled from the .army_setup when it parses
+
+    #[serde(default)]
+    pub build_id: String, // sha256 of the .army_setup, keys the library index
 }
 
+//Sorted by name for display, id breaks ties so Ord agrees with Eq
 impl Ord for ArmyBuild {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.file_stem.cmp(&other.file_stem)
+        self.file_stem
+            .cmp(&other.file_stem)
+            .then_with(|| self.get_identity().cmp(&other.get_identity()))
     }
 }
 
-
 impl PartialOrd for ArmyBuild {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
     }
 }
 
+//Same content is the same build, whatever it's called
 impl PartialEq for ArmyBuild {
     fn eq(&self, other: &Self) -> bool {
-        self.file_stem == other.file_stem
+        self.get_identity() == other.get_identity()
     }
 }
 
 impl Eq for ArmyBuild {}
 
 impl Hash for ArmyBuild {
-    fn hash<H: Hasher>(&self, hasher: &mut H){
-        self.file_stem.hash(hasher)
+    fn hash<H: Hasher>(&self, hasher: &mut H) {
+        self.get_identity().hash(hasher)
     }
 }
 
@@ -108,10 +121,137 @@ pub fn get_army_build_display_column_title(display_col: &ArmyBuildDisplayColumns
     }
 }
 
-pub fn show_army_build_header_row(ui: &mut Ui) {
-    for display_col in ArmyBuildDisplayColumns::into_enum_iter() {
-        ui.label(get_army_build_display_column_title(&display_col));
+//Which columns the build table shows in what order & what it's sorted by, saved with the app
+#[cfg_attr(
+    feature = "persistence",
+    derive(serde::Deserialize, serde::Serialize, Clone)
+)]
+pub struct ArmyBuildTableLayout {
+    #[serde(deserialize_with = "deserialize_columns")]
+    pub columns: Vec<(ArmyBuildDisplayColumns, bool)>, // visible flag
+    pub sort_column: Option<ArmyBuildDisplayColumns>,
+    pub sort_descending: bool,
+}
+
+impl Default for ArmyBuildTableLayout {
+    fn default() -> Self {
+        Self {
+            columns: ArmyBuildDisplayColumns::into_enum_iter()
+                .map(|c| (c, true))
+                .collect(),
+            sort_column: None,
+            sort_descending: false,
+        }
+    }
+}
+
+//columns added since the layout was saved go on the end, so they can still be hidden & moved
+fn deserialize_columns<'de, D>(
+    deserializer: D,
+) -> Result<Vec<(ArmyBuildDisplayColumns, bool)>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let mut columns: Vec<(ArmyBuildDisplayColumns, bool)> =
+        serde::Deserialize::deserialize(deserializer)?;
+    for c in ArmyBuildDisplayColumns::into_enum_iter() {
+        if !columns.iter().any(|(saved, _)| *saved == c) {
+            columns.push((c, true));
+        }
+    }
+    Ok(columns)
+}
+
+impl ArmyBuildTableLayout {
+    pub fn get_visible_columns(&self) -> Vec<ArmyBuildDisplayColumns> {
+        self.columns
+            .iter()
+            .filter(|(_, visible)| *visible)
+            .map(|(c, _)| c.clone())
+            .collect()
+    }
+
+    //Ascending, then descending, then back to the default order
+    pub fn cycle_sort(&mut self, display_col: &ArmyBuildDisplayColumns) {
+        if self.sort_column.as_ref() != Some(display_col) {
+            self.sort_column = Some(display_col.clone());
+            self.sort_descending = false;
+        } else if !self.sort_descending {
+            self.sort_descending = true;
+        } else {
+            self.sort_column = None;
+            self.sort_descending = false;
+        }
+    }
+
+    //Stable, ties keep the order they came in
+    pub fn sort_army_builds(&self, builds: &mut [ArmyBuild]) {
+        if let Some(display_col) = &self.sort_column {
+            builds.sort_by(|a, b| {
+                let ordering = a.cmp_by_column(b, display_col);
+                if self.sort_descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            });
+        }
+    }
+
+    //returns true if anything changed
+    pub fn columns_ui(&mut self, ui: &mut Ui) -> bool {
+        let mut changed = false;
+        let mut move_up = None;
+        for (i, (display_col, visible)) in self.columns.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                let title = get_army_build_display_column_title(display_col);
+                //always need something to click on to pick a build
+                if *display_col == ArmyBuildDisplayColumns::Name {
+                    ui.label(title);
+                } else {
+                    changed |= ui.checkbox(visible, title).changed();
+                }
+                if ui.small_button("⬆").clicked() && i > 0 {
+                    move_up = Some(i);
+                }
+                if ui.small_button("⬇").clicked() {
+                    move_up = Some(i + 1);
+                }
+            });
+        }
+        if let Some(i) = move_up {
+            if i > 0 && i < self.columns.len() {
+                self.columns.swap(i - 1, i);
+                changed = true;
+            }
+        }
+        if ui.button("Reset Columns").clicked() {
+            *self = ArmyBuildTableLayout::default();
+            changed = true;
+        }
+        changed
+    }
+}
+
+//returns true if a header got clicked & the sort changed
+pub fn show_army_build_header_row(ui: &mut Ui, layout: &mut ArmyBuildTableLayout) -> bool {
+    let mut sort_changed = false;
+    for display_col in layout.get_visible_columns() {
+        let mut title = get_army_build_display_column_title(&display_col);
+        let sorted = layout.sort_column.as_ref() == Some(&display_col);
+        if sorted {
+            title += if layout.sort_descending { " ⏷" } else { " ⏶" };
+        }
+        if ui
+            .selectable_label(sorted, title)
+            .on_hover_text("Sort")
+            .clicked()
+        {
+            layout.cycle_sort(&display_col);
+            sort_changed = true;
+        }
     }
+    sort_changed
 }
 
 impl Default for ArmyBuild {
@@ -130,19 +270,54 @@ impl Default for ArmyBuild {
             vs_faction_str: String::new(),
             win_count: 0,
             loss_count: 0,
+            draw_count: 0,
             funds: 12400,
             image_files: vec![],
             notes: String::new(),
+            unit_keys: vec![],
+            build_id: String::new(),
         }
     }
 }
 
+//Same bytes same id, no matter the file name or folder
+pub fn get_army_build_id(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+//Build ids loaded more than once under different names, each with every copy found
+pub fn find_duplicate_builds(builds: &[ArmyBuild]) -> HashMap<String, Vec<ArmyBuild>> {
+    let mut by_id: HashMap<String, Vec<ArmyBuild>> = HashMap::new();
+    for b in builds.iter().filter(|b| !b.build_id.is_empty()) {
+        let copies = by_id.entry(b.build_id.clone()).or_default();
+        if !copies.iter().any(|c| c.file_stem == b.file_stem) {
+            copies.push(b.clone());
+        }
+    }
+    by_id.retain(|_, copies| copies.len() > 1);
+    by_id
+}
+
 impl ArmyBuild {
+    //Content hash, or the file for builds persisted before ids existed
+    pub fn get_identity(&self) -> String {
+        if self.build_id.is_empty() {
+            self.file.to_string_lossy().to_string()
+        } else {
+            self.build_id.clone()
+        }
+    }
+
+    //0 to 100 of all games played, draws count as played but not won. NaN when unplayed
     pub fn get_win_percent(&self) -> f64 {
-        self.win_count as f64 / self.loss_count as f64
+        let games = self.get_games_played_count();
+        if games == 0 {
+            return f64::NAN;
+        }
+        100.0 * self.win_count as f64 / games as f64
     }
     pub fn get_games_played_count(&self) -> u32 {
-        self.win_count + self.loss_count
+        self.win_count + self.loss_count + self.draw_count
     }
 
     pub fn get_display_string(&self, display_col: &ArmyBuildDisplayColumns) -> String {
@@ -162,7 +337,7 @@ impl ArmyBuild {
                 }
             }
             ArmyBuildDisplayColumns::NumGamesPlayed => {
-                format!("{}", self.win_count + self.loss_count)
+                format!("{}", self.get_games_played_count())
             }
             ArmyBuildDisplayColumns::CreatedOn => {
                 format!("{}", self.created_on.format(YMD_HMS_FORMAT))
@@ -171,10 +346,48 @@ impl ArmyBuild {
         }
     }
 
+    //Compares on just the one column, case insensitive for text & unplayed builds below any win %
+    pub fn cmp_by_column(&self, other: &Self, display_col: &ArmyBuildDisplayColumns) -> Ordering {
+        let cmp_str = |a: &str, b: &str| a.to_lowercase().cmp(&b.to_lowercase());
+        match display_col {
+            ArmyBuildDisplayColumns::Name => cmp_str(&self.file_stem, &other.file_stem),
+            ArmyBuildDisplayColumns::Faction => cmp_str(&self.faction_str, &other.faction_str),
+            ArmyBuildDisplayColumns::VsFaction => {
+                cmp_str(&self.vs_faction_str, &other.vs_faction_str)
+            }
+            ArmyBuildDisplayColumns::Funds => self.funds.cmp(&other.funds),
+            ArmyBuildDisplayColumns::CreatedBy => cmp_str(&self.created_by, &other.created_by),
+            ArmyBuildDisplayColumns::GameMod => cmp_str(&self.game_mod, &other.game_mod),
+            ArmyBuildDisplayColumns::WinPercent => {
+                let win_percent = |b: &ArmyBuild| {
+                    let p = b.get_win_percent();
+                    if p.is_finite() {
+                        p
+                    } else {
+                        -1.0
+                    }
+                };
+                win_percent(self)
+                    .partial_cmp(&win_percent(other))
+                    .unwrap_or(Ordering::Equal)
+            }
+            ArmyBuildDisplayColumns::NumGamesPlayed => self
+                .get_games_played_count()
+                .cmp(&other.get_games_played_count()),
+            ArmyBuildDisplayColumns::CreatedOn => self.created_on.cmp(&other.created_on),
+            ArmyBuildDisplayColumns::Notes => cmp_str(&self.notes, &other.notes),
+        }
+    }
+
     //returns if user clicked
-    pub fn show_selectable_army_build_row(&mut self, ui: &mut Ui, selected_row: bool) -> bool {
+    pub fn show_selectable_army_build_row(
+        &mut self,
+        ui: &mut Ui,
+        selected_row: bool,
+        display_cols: &[ArmyBuildDisplayColumns],
+    ) -> bool {
         let mut clicked = false;
-        for display_col in ArmyBuildDisplayColumns::into_enum_iter() {
+        for display_col in display_cols.iter().cloned() {
             match display_col {
                 ArmyBuildDisplayColumns::Notes => {
                     if ui.button(self.get_display_string(&display_col)).clicked() {
@@ -219,6 +432,27 @@ pub fn get_funds_amount(funds_level: FundsLevel, ca_game: &CaGame) -> u32 {
     }
 }
 
+//Funds level as stored in the .army_setup header
+pub fn get_army_setup_funds_level(funds_level: u32) -> FundsLevel {
+    match funds_level {
+        0 => FundsLevel::Small,
+        1 => FundsLevel::Medium,
+        2 => FundsLevel::Large,
+        3 => FundsLevel::Ultra,
+        _ => FundsLevel::Custom,
+    }
+}
+
+pub fn get_army_setup_funds_level_index(funds_level: &FundsLevel) -> Option<u32> {
+    match funds_level {
+        FundsLevel::Small => Some(0),
+        FundsLevel::Medium => Some(1),
+        FundsLevel::Large => Some(2),
+        FundsLevel::Ultra => Some(3),
+        FundsLevel::Custom => None,
+    }
+}
+
 pub fn get_funds_level(funds: u32, ca_game: &CaGame) -> FundsLevel {
     match ca_game {
         _ => match funds {
@@ -252,3 +486,117 @@ pub fn funds_slider_ui(army_build: &mut ArmyBuild, ui: &mut Ui) {
         }
     });
 }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn get_build(name: &str, wins: u32, losses: u32) -> ArmyBuild {
+        ArmyBuild {
+            file: PathBuf::from(format!("{}.army_setup", name)),
+            file_stem: name.to_string(),
+            build_id: name.to_string(),
+            win_count: wins,
+            loss_count: losses,
+            ..ArmyBuild::default()
+        }
+    }
+
+    fn get_names(builds: &[ArmyBuild]) -> Vec<&str> {
+        builds.iter().map(|b| b.file_stem.as_str()).collect()
+    }
+
+    #[test]
+    fn compares_one_column() {
+        let mut a = get_build("gs vs he", 1, 1);
+        let mut b = get_build("GS vs WE", 3, 0);
+        a.funds = 12400;
+        b.funds = 9000;
+        a.created_on = Utc.with_ymd_and_hms(2021, 5, 1, 0, 0, 0).unwrap();
+        b.created_on = Utc.with_ymd_and_hms(2021, 4, 1, 0, 0, 0).unwrap();
+        a.notes = "Zerg".to_string();
+        b.notes = "artillery".to_string();
+        let cases = vec![
+            (ArmyBuildDisplayColumns::Name, Ordering::Less), // ignoring case
+            (ArmyBuildDisplayColumns::Funds, Ordering::Greater),
+            (ArmyBuildDisplayColumns::WinPercent, Ordering::Less),
+            (ArmyBuildDisplayColumns::NumGamesPlayed, Ordering::Less),
+            (ArmyBuildDisplayColumns::CreatedOn, Ordering::Greater),
+            (ArmyBuildDisplayColumns::Notes, Ordering::Greater),
+            (ArmyBuildDisplayColumns::Faction, Ordering::Equal),
+        ];
+        for (col, ordering) in cases {
+            assert_eq!(a.cmp_by_column(&b, &col), ordering, "{:?}", col);
+            assert_eq!(b.cmp_by_column(&a, &col), ordering.reverse(), "{:?}", col);
+        }
+    }
+
+    #[test]
+    fn unplayed_sorts_below_any_win_percent() {
+        let unplayed = get_build("unplayed", 0, 0);
+        let never_won = get_build("never won", 0, 4);
+        assert!(unplayed.get_win_percent().is_nan());
+        let col = ArmyBuildDisplayColumns::WinPercent;
+        assert_eq!(unplayed.cmp_by_column(&never_won, &col), Ordering::Less);
+        assert_eq!(
+            unplayed.cmp_by_column(&get_build("also unplayed", 0, 0), &col),
+            Ordering::Equal
+        );
+
+        let mut layout = ArmyBuildTableLayout::default();
+        layout.cycle_sort(&col);
+        let mut builds = vec![
+            get_build("half", 1, 1),
+            unplayed.clone(),
+            get_build("all", 2, 0),
+            get_build("also unplayed", 0, 0),
+            never_won,
+        ];
+        layout.sort_army_builds(&mut builds);
+        assert_eq!(
+            get_names(&builds),
+            vec!["unplayed", "also unplayed", "never won", "half", "all"]
+        );
+        layout.cycle_sort(&col);
+        layout.sort_army_builds(&mut builds);
+        assert_eq!(
+            get_names(&builds),
+            vec!["all", "half", "never won", "unplayed", "also unplayed"]
+        );
+        layout.cycle_sort(&col);
+        assert!(layout.sort_column.is_none());
+    }
+
+    #[test]
+    fn saved_layout_gets_new_columns_on_the_end() {
+        let mut layout = ArmyBuildTableLayout::default();
+        layout.cycle_sort(&ArmyBuildDisplayColumns::Funds);
+        layout.columns = vec![
+            (ArmyBuildDisplayColumns::Funds, true),
+            (ArmyBuildDisplayColumns::Name, true),
+            (ArmyBuildDisplayColumns::Notes, false),
+        ];
+        let saved = serde_json::to_string(&layout).unwrap();
+        let loaded: ArmyBuildTableLayout = serde_json::from_str(saved.as_str()).unwrap();
+        assert_eq!(loaded.sort_column, Some(ArmyBuildDisplayColumns::Funds));
+        assert_eq!(
+            loaded.columns.len(),
+            ArmyBuildDisplayColumns::into_enum_iter().count()
+        );
+        assert_eq!(loaded.columns[3], (ArmyBuildDisplayColumns::Faction, true));
+        let visible = loaded.get_visible_columns();
+        assert_eq!(
+            visible[..3],
+            [
+                ArmyBuildDisplayColumns::Funds,
+                ArmyBuildDisplayColumns::Name,
+                ArmyBuildDisplayColumns::Faction
+            ]
+        );
+        assert!(!visible.contains(&ArmyBuildDisplayColumns::Notes));
+        assert_eq!(
+            visible.len(),
+            ArmyBuildDisplayColumns::into_enum_iter().count() - 1
+        );
+    }
+}
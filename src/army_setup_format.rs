@@ -0,0 +1,440 @@
+use std::fmt;
+use std::path::Path;
+
+// Layout worked out from the warhammer 2 files in felkon's pack, little endian throughout
+//
+// u32 version, u32 unknown, u32 funds level
+// u16 len + ascii faction key
+// u8 unknown
+// u16 len + utf16 author
+// u32 unknown, u8 unknown
+// u32 unit count, then per unit
+//     u16 len + ascii unit key
+//     u32 experience rank, u8 is general, u16 unknown
+//     u32 slot count, then per slot u8 slot kind + u16 len + ascii key
+// trailer, 16 zero bytes in every known file
+pub const SUPPORTED_ARMY_SETUP_VERSIONS: [u32; 1] = [15];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArmySetupFormatError {
+    Io(String),
+    UnexpectedEof {
+        offset: usize,
+        reading: &'static str,
+    },
+    UnsupportedVersion(u32),
+    InvalidString {
+        offset: usize,
+        reading: &'static str,
+    },
+    StringTooLong(String),
+    NoSuchUnit(usize),
+}
+
+impl fmt::Display for ArmySetupFormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArmySetupFormatError::Io(e) => write!(f, "Couldn't read army setup {}", e),
+            ArmySetupFormatError::UnexpectedEof { offset, reading } => write!(
+                f,
+                "Army setup ends early at byte {} reading {}",
+                offset, reading
+            ),
+            ArmySetupFormatError::UnsupportedVersion(v) => write!(
+                f,
+                "Army setup version {} not supported, know {:?}",
+                v, SUPPORTED_ARMY_SETUP_VERSIONS
+            ),
+            ArmySetupFormatError::InvalidString { offset, reading } => write!(
+                f,
+                "Army setup has a bad string at byte {} reading {}",
+                offset, reading
+            ),
+            ArmySetupFormatError::StringTooLong(s) => {
+                write!(f, "\'{}\' is too long to fit in an army setup", s)
+            }
+            ArmySetupFormatError::NoSuchUnit(i) => write!(f, "Army setup has no unit {}", i),
+        }
+    }
+}
+
+impl std::error::Error for ArmySetupFormatError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum UnitSlotKind {
+    Skill,
+    Item,
+    Other(u8),
+}
+
+pub fn get_unit_slot_kind(kind: u8) -> UnitSlotKind {
+    match kind {
+        0 => UnitSlotKind::Skill,
+        2 => UnitSlotKind::Item,
+        k => UnitSlotKind::Other(k),
+    }
+}
+
+pub fn get_unit_slot_kind_byte(kind: &UnitSlotKind) -> u8 {
+    match kind {
+        UnitSlotKind::Skill => 0,
+        UnitSlotKind::Item => 2,
+        UnitSlotKind::Other(k) => *k,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnitSlot {
+    pub kind: UnitSlotKind,
+    pub key: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArmySetupUnit {
+    pub unit_key: String,
+    pub experience_rank: u32,
+    pub is_general: bool,
+    pub unknown_unit: u16,
+    pub slots: Vec<UnitSlot>,
+}
+
+impl ArmySetupUnit {
+    pub fn get_items(&self) -> Vec<&str> {
+        self.slots
+            .iter()
+            .filter(|s| s.kind == UnitSlotKind::Item)
+            .map(|s| s.key.as_str())
+            .collect()
+    }
+
+    pub fn get_skills(&self) -> Vec<&str> {
+        self.slots
+            .iter()
+            .filter(|s| s.kind == UnitSlotKind::Skill)
+            .map(|s| s.key.as_str())
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArmySetup {
+    pub version: u32,
+    pub unknown_header: u32,
+    pub funds_level: u32,
+    pub faction_key: String,
+    pub unknown_faction: u8,
+    pub author: String,
+    pub unknown_author: u32,
+    pub unknown_author_flag: u8,
+    pub units: Vec<ArmySetupUnit>,
+    pub trailer: Vec<u8>,
+}
+
+impl ArmySetup {
+    pub fn get_general(&self) -> Option<&ArmySetupUnit> {
+        self.units.iter().find(|u| u.is_general)
+    }
+
+    pub fn get_unit_keys(&self) -> Vec<String> {
+        self.units.iter().map(|u| u.unit_key.clone()).collect()
+    }
+
+    pub fn set_funds_level(&mut self, funds_level: u32) {
+        self.funds_level = funds_level;
+    }
+
+    pub fn set_faction_key(&mut self, faction_key: &str) {
+        self.faction_key = faction_key.to_string();
+    }
+
+    //Swaps the unit for another, skills & items belong to the old unit so they go with it
+    pub fn swap_unit(
+        &mut self,
+        unit_indx: usize,
+        unit_key: &str,
+    ) -> Result<(), ArmySetupFormatError> {
+        match self.units.get_mut(unit_indx) {
+            Some(unit) => {
+                if unit.unit_key != unit_key {
+                    unit.unit_key = unit_key.to_string();
+                    unit.slots.clear();
+                }
+                Ok(())
+            }
+            None => Err(ArmySetupFormatError::NoSuchUnit(unit_indx)),
+        }
+    }
+}
+
+struct ArmySetupReader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> ArmySetupReader<'a> {
+    fn take(&mut self, n: usize, reading: &'static str) -> Result<&'a [u8], ArmySetupFormatError> {
+        if self.bytes.len() - self.offset < n {
+            return Err(ArmySetupFormatError::UnexpectedEof {
+                offset: self.offset,
+                reading,
+            });
+        }
+        let s = &self.bytes[self.offset..self.offset + n];
+        self.offset += n;
+        Ok(s)
+    }
+
+    fn read_u8(&mut self, reading: &'static str) -> Result<u8, ArmySetupFormatError> {
+        Ok(self.take(1, reading)?[0])
+    }
+
+    fn read_u16(&mut self, reading: &'static str) -> Result<u16, ArmySetupFormatError> {
+        let b = self.take(2, reading)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn read_u32(&mut self, reading: &'static str) -> Result<u32, ArmySetupFormatError> {
+        let b = self.take(4, reading)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn read_string(&mut self, reading: &'static str) -> Result<String, ArmySetupFormatError> {
+        let len = self.read_u16(reading)? as usize;
+        let start = self.offset;
+        let b = self.take(len, reading)?;
+        match std::str::from_utf8(b) {
+            Ok(s) => Ok(s.to_string()),
+            Err(_) => Err(ArmySetupFormatError::InvalidString {
+                offset: start,
+                reading,
+            }),
+        }
+    }
+
+    fn read_utf16_string(&mut self, reading: &'static str) -> Result<String, ArmySetupFormatError> {
+        let len = self.read_u16(reading)? as usize;
+        let start = self.offset;
+        let b = self.take(len * 2, reading)?;
+        let wide: Vec<u16> = b
+            .chunks(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .collect();
+        String::from_utf16(&wide).map_err(|_| ArmySetupFormatError::InvalidString {
+            offset: start,
+            reading,
+        })
+    }
+
+    fn read_unit(&mut self) -> Result<ArmySetupUnit, ArmySetupFormatError> {
+        let unit_key = self.read_string("unit key")?;
+        let experience_rank = self.read_u32("experience rank")?;
+        let is_general = self.read_u8("is general")? != 0;
+        let unknown_unit = self.read_u16("unit unknown")?;
+        let n_slots = self.read_u32("slot count")?;
+        let mut slots = vec![];
+        for _ in 0..n_slots {
+            let kind = get_unit_slot_kind(self.read_u8("slot kind")?);
+            let key = self.read_string("slot key")?;
+            slots.push(UnitSlot { kind, key });
+        }
+        Ok(ArmySetupUnit {
+            unit_key,
+            experience_rank,
+            is_general,
+            unknown_unit,
+            slots,
+        })
+    }
+}
+
+pub fn parse_army_setup(bytes: &[u8]) -> Result<ArmySetup, ArmySetupFormatError> {
+    let mut r = ArmySetupReader { bytes, offset: 0 };
+
+    let version = r.read_u32("version")?;
+    if !SUPPORTED_ARMY_SETUP_VERSIONS.contains(&version) {
+        return Err(ArmySetupFormatError::UnsupportedVersion(version));
+    }
+    let unknown_header = r.read_u32("header")?;
+    let funds_level = r.read_u32("funds level")?;
+    let faction_key = r.read_string("faction key")?;
+    let unknown_faction = r.read_u8("faction unknown")?;
+    let author = r.read_utf16_string("author")?;
+    let unknown_author = r.read_u32("author unknown")?;
+    let unknown_author_flag = r.read_u8("author flag")?;
+
+    let n_units = r.read_u32("unit count")?;
+    let mut units = vec![];
+    for _ in 0..n_units {
+        units.push(r.read_unit()?);
+    }
+    let trailer = bytes[r.offset..].to_vec();
+
+    Ok(ArmySetup {
+        version,
+        unknown_header,
+        funds_level,
+        faction_key,
+        unknown_faction,
+        author,
+        unknown_author,
+        unknown_author_flag,
+        units,
+        trailer,
+    })
+}
+
+pub fn read_army_setup_file(file: &Path) -> Result<ArmySetup, ArmySetupFormatError> {
+    match std::fs::read(file) {
+        Ok(bytes) => parse_army_setup(bytes.as_slice()),
+        Err(e) => Err(ArmySetupFormatError::Io(format!("{}", e))),
+    }
+}
+
+struct ArmySetupWriter {
+    bytes: Vec<u8>,
+}
+
+impl ArmySetupWriter {
+    fn write_u8(&mut self, v: u8) {
+        self.bytes.push(v);
+    }
+
+    fn write_u16(&mut self, v: u16) {
+        self.bytes.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, v: u32) {
+        self.bytes.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn write_len(&mut self, len: usize, s: &str) -> Result<(), ArmySetupFormatError> {
+        if len > u16::MAX as usize {
+            return Err(ArmySetupFormatError::StringTooLong(s.to_string()));
+        }
+        self.write_u16(len as u16);
+        Ok(())
+    }
+
+    fn write_string(&mut self, s: &str) -> Result<(), ArmySetupFormatError> {
+        self.write_len(s.len(), s)?;
+        self.bytes.extend_from_slice(s.as_bytes());
+        Ok(())
+    }
+
+    fn write_utf16_string(&mut self, s: &str) -> Result<(), ArmySetupFormatError> {
+        let wide: Vec<u16> = s.encode_utf16().collect();
+        self.write_len(wide.len(), s)?;
+        for w in wide {
+            self.write_u16(w);
+        }
+        Ok(())
+    }
+
+    fn write_unit(&mut self, unit: &ArmySetupUnit) -> Result<(), ArmySetupFormatError> {
+        self.write_string(unit.unit_key.as_str())?;
+        self.write_u32(unit.experience_rank);
+        self.write_u8(unit.is_general as u8);
+        self.write_u16(unit.unknown_unit);
+        self.write_u32(unit.slots.len() as u32);
+        for slot in unit.slots.iter() {
+            self.write_u8(get_unit_slot_kind_byte(&slot.kind));
+            self.write_string(slot.key.as_str())?;
+        }
+        Ok(())
+    }
+}
+
+//Mirror of parse_army_setup, an unmodified setup writes back byte for byte
+pub fn write_army_setup(setup: &ArmySetup) -> Result<Vec<u8>, ArmySetupFormatError> {
+    if !SUPPORTED_ARMY_SETUP_VERSIONS.contains(&setup.version) {
+        return Err(ArmySetupFormatError::UnsupportedVersion(setup.version));
+    }
+    let mut w = ArmySetupWriter { bytes: vec![] };
+
+    w.write_u32(setup.version);
+    w.write_u32(setup.unknown_header);
+    w.write_u32(setup.funds_level);
+    w.write_string(setup.faction_key.as_str())?;
+    w.write_u8(setup.unknown_faction);
+    w.write_utf16_string(setup.author.as_str())?;
+    w.write_u32(setup.unknown_author);
+    w.write_u8(setup.unknown_author_flag);
+
+    w.write_u32(setup.units.len() as u32);
+    for unit in setup.units.iter() {
+        w.write_unit(unit)?;
+    }
+    w.bytes.extend_from_slice(setup.trailer.as_slice());
+
+    Ok(w.bytes)
+}
+
+pub fn write_army_setup_file(file: &Path, setup: &ArmySetup) -> Result<(), ArmySetupFormatError> {
+    let bytes = write_army_setup(setup)?;
+    std::fs::write(file, bytes).map_err(|e| ArmySetupFormatError::Io(format!("{}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn fixtures_dir() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("assets")
+            .join("army_setups")
+    }
+
+    fn fixture_bytes() -> Vec<(PathBuf, Vec<u8>)> {
+        let mut fixtures = vec![];
+        for entry in std::fs::read_dir(fixtures_dir()).unwrap() {
+            let path = entry.unwrap().path();
+            let bytes = std::fs::read(&path).unwrap();
+            fixtures.push((path, bytes));
+        }
+        assert!(!fixtures.is_empty());
+        fixtures
+    }
+
+    #[test]
+    fn unmodified_fixtures_round_trip_byte_identical() {
+        for (path, bytes) in fixture_bytes() {
+            let setup = parse_army_setup(bytes.as_slice()).unwrap();
+            assert_eq!(
+                write_army_setup(&setup).unwrap(),
+                bytes,
+                "{}",
+                path.to_string_lossy()
+            );
+        }
+    }
+
+    #[test]
+    fn edited_setup_reads_back_edits() {
+        let (_, bytes) = fixture_bytes().remove(0);
+        let mut setup = parse_army_setup(bytes.as_slice()).unwrap();
+        setup.set_funds_level(3);
+        setup.set_faction_key("wh2_main_hef_high_elves");
+        let general = setup.units.iter().position(|u| u.is_general).unwrap();
+        let swap = if general == 0 { 1 } else { 0 };
+        setup
+            .swap_unit(swap, "wh2_main_hef_inf_spearmen_0")
+            .unwrap();
+
+        let edited = parse_army_setup(write_army_setup(&setup).unwrap().as_slice()).unwrap();
+        assert_eq!(edited, setup);
+        assert_eq!(edited.funds_level, 3);
+        assert_eq!(edited.units[swap].unit_key, "wh2_main_hef_inf_spearmen_0");
+        assert!(edited.units[swap].slots.is_empty());
+    }
+
+    #[test]
+    fn unknown_version_is_an_error() {
+        let (_, mut bytes) = fixture_bytes().remove(0);
+        bytes[0] = 99;
+        assert_eq!(
+            parse_army_setup(bytes.as_slice()),
+            Err(ArmySetupFormatError::UnsupportedVersion(99))
+        );
+    }
+}
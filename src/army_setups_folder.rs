@@ -1,10 +1,21 @@
-use crate::army_build::ArmyBuild;
+use crate::army_build::{
+    get_army_build_id, get_army_setup_funds_level, get_funds_amount, ArmyBuild, FundsLevel,
+};
+use crate::army_setup_format::parse_army_setup;
+use crate::build_archive::is_archive_file;
 use crate::ca_game::CaGame::Warhammer2;
 use crate::ca_game::{
-    get_ca_game_army_setups_folder, get_ca_game_from_folder_name, get_ca_game_subfolder, CaGame,
+    get_ca_game_army_setup_ext, get_ca_game_army_setups_folder, get_ca_game_from_folder_name,
+    get_ca_game_subfolder, CaGame,
+};
+use crate::ca_game_discovery::{get_ca_game_army_setups_subdir, is_ca_game_army_setups_path};
+use crate::faction_registry::{get_faction_registry, UNKNOWN_FACTION};
+use crate::factions::{get_wh2_faction_from_name, Wh2Factions};
+use crate::filename_template::{
+    compile_filename_templates, load_filename_templates, parse_filename, FilenameTemplate,
 };
-use crate::factions::{get_faction_names, Wh2Factions};
-use crate::factions::{parse_faction, parse_vs_faction};
+use crate::library_store::{get_library_index_file, load_library_index};
+use crate::match_log::{get_match_log_file, load_match_log};
 use crate::ymd_hms_dash_format::YMD_HMS_FORMAT;
 use chrono::{DateTime, Utc};
 use dirs;
@@ -12,7 +23,7 @@ use std::ffi::OsStr;
 use std::fmt::Debug;
 use std::fs;
 use std::io::Error;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 #[cfg_attr(
     feature = "persistence",
@@ -28,7 +39,7 @@ pub struct ArmySetupsFolder {
 impl ArmySetupsFolder {
     pub fn new(folder: &str) -> Self {
         let folder_string = folder.to_string();
-        let folder_error = match validate_load_folder(folder_string.as_str()) {
+        let folder_error = match validate_load_path(folder_string.as_str()) {
             Ok(_) => String::new(),
             Err(e) => e,
         };
@@ -46,46 +57,41 @@ impl ArmySetupsFolder {
     }
 
     pub fn is_owaagh_appdata(&self) -> bool {
-        validate_insert_folder(
-            self.folder_string.as_str(),
-            &["AppData\\Roaming\\WarbossWaaghit", "army_setups"],
-        )
-        .is_ok()
+        match get_owaagh_appdata_dir() {
+            Ok(p) => PathBuf::from(self.folder_string.as_str()).starts_with(p),
+            Err(_) => false,
+        }
     }
 
     pub fn is_load_folder(&self) -> bool {
-        validate_load_folder(self.folder_string.as_str()).is_ok()
+        validate_load_path(self.folder_string.as_str()).is_ok()
     }
 
     pub fn is_ca_game_folder(&self) -> bool {
-        let res_sub_dir = get_ca_game_army_setups_folder(self.ca_game.clone());
-        if res_sub_dir.is_err() {
-            return false;
-        }
-        let subdir_str = res_sub_dir.unwrap().to_string_lossy().to_string();
-        validate_insert_folder(
-            self.folder_string.as_str(),
-            &[
-                "AppData\\Roaming\\The Creative Assembly",
-                subdir_str.as_str(),
-            ],
-        )
-        .is_ok()
+        let path = PathBuf::from(self.folder_string.as_str());
+        path.is_dir() && is_ca_game_army_setups_path(path.as_path(), &self.ca_game)
     }
 
     pub fn set_load_folder_error(&mut self) {
-        self.folder_error = match validate_load_folder(self.folder_string.as_str()) {
+        self.folder_error = match validate_load_path(self.folder_string.as_str()) {
             Ok(_) => String::new(),
             Err(e) => e,
         };
     }
 
     pub fn set_insert_folder_error(&mut self) {
-        self.folder_error = match validate_insert_folder(
-            self.folder_string.as_str(),
-            &["AppData\\Roaming\\The Creative Assembly", "army_setups"],
-        ) {
-            Ok(_) => String::new(),
+        self.folder_error = match validate_insert_folder(self.folder_string.as_str(), &[]) {
+            Ok(_) => {
+                if self.is_ca_game_folder() {
+                    String::new()
+                } else {
+                    format!(
+                        "Path don't end with \'The Creative Assembly/{}/{}\'",
+                        get_ca_game_subfolder(&self.ca_game),
+                        get_ca_game_army_setups_subdir(&self.ca_game)
+                    )
+                }
+            }
             Err(e) => e,
         };
     }
@@ -133,6 +139,14 @@ pub fn validate_load_folder(folder_path: &str) -> Result<(), String> {
     Err("The folder got no \'.army_setup\' files".to_string())
 }
 
+//A folder of setups or a .zip/.7z/.rar build pack
+pub fn validate_load_path(load_path: &str) -> Result<(), String> {
+    if is_archive_file(std::path::Path::new(load_path)) {
+        return Ok(());
+    }
+    validate_load_folder(load_path)
+}
+
 pub fn load_army_builds(folder_path: &str, ca_game: &CaGame) -> Vec<ArmyBuild> {
     let mut builds = vec![];
     match validate_load_folder(folder_path) {
@@ -141,6 +155,7 @@ pub fn load_army_builds(folder_path: &str, ca_game: &CaGame) -> Vec<ArmyBuild> {
     }
 
     let path = std::path::Path::new(folder_path);
+    let (templates, _) = compile_filename_templates(&load_filename_templates());
 
     //use expect because of valid checks check
     //make sure there are .army_setup files in the directory
@@ -151,58 +166,12 @@ pub fn load_army_builds(folder_path: &str, ca_game: &CaGame) -> Vec<ArmyBuild> {
                     //skip subfolder
                     continue;
                 } else {
-                    if is_army_setup_file(&entry) {
-                        let file_string = entry.path().to_string_lossy().to_string();
-                        let file_stem = entry
-                            .path()
-                            .file_stem()
-                            .expect("load_army_builds file stem fail")
-                            .to_str()
-                            .expect("load_army_builds to_str fail")
-                            .to_string();
-
-                        let mut created_on: DateTime<Utc>;
-                        match std::fs::metadata(entry.path()) {
-                            Ok(m) => {
-                                let t = m.created().unwrap_or(std::time::SystemTime::now());
-                                created_on = t.into();
-                            }
-                            Err(e) => {
-                                println!("Getting metadata err {}", e);
-                                continue;
-                            }
+                    if is_army_setup_file(&entry) && is_ca_game_setup_file(&entry, ca_game) {
+                        if let Some(build) =
+                            read_army_build_file(entry.path().as_path(), ca_game, &templates)
+                        {
+                            builds.push(build);
                         }
-
-                        let mut faction = Wh2Factions::ALL;
-                        let mut vs_faction = Wh2Factions::ALL;
-                        let mut faction_str = String::new();
-                        let mut vs_faction_str = String::new();
-                        if *ca_game == CaGame::Warhammer2 {
-                            faction = parse_faction(&file_stem);
-                            vs_faction = parse_vs_faction(&file_stem);
-                            faction_str = get_faction_names(&faction).to_string();
-                            vs_faction_str = get_faction_names(&vs_faction).to_string();
-                        }
-
-                        builds.push(ArmyBuild {
-                            file: entry.path(),
-                            file_stem: file_stem.clone(),
-                            faction: parse_faction(&file_stem),
-                            funds: 12400,
-                            vs_faction: parse_vs_faction(&file_stem),
-                            created_on,
-                            original_file: entry.path(),
-                            ca_game: get_ca_game_from_folder_name(file_string.as_str()),
-                            created_by: String::new(),
-                            game_mod: String::new(),
-                            faction_str,
-                            vs_faction_str,
-                            win_count: 0,
-                            loss_count: 0,
-                            image_files: vec![],
-                            notes: String::new(),
-                        });
-                        //println!("{:?} {:?} {:?}", builds.last().unwrap().file_name, builds.last().unwrap().faction, builds.last().unwrap().vs_faction);
                     }
                 }
             }
@@ -211,9 +180,165 @@ pub fn load_army_builds(folder_path: &str, ca_game: &CaGame) -> Vec<ArmyBuild> {
             }
         }
     }
+
+    apply_library_metadata(&mut builds, ca_game);
     builds
 }
 
+//A single setup file, for picking up changes without reloading the folder
+pub fn load_army_build(file: &Path, ca_game: &CaGame) -> Option<ArmyBuild> {
+    if !file.is_file() || !is_ca_game_setup_path(file, ca_game) {
+        return None;
+    }
+    let (templates, _) = compile_filename_templates(&load_filename_templates());
+    let mut builds = vec![read_army_build_file(file, ca_game, &templates)?];
+    apply_library_metadata(&mut builds, ca_game);
+    builds.pop()
+}
+
+fn read_army_build_file(
+    file: &Path,
+    ca_game: &CaGame,
+    templates: &[FilenameTemplate],
+) -> Option<ArmyBuild> {
+    let file_string = file.to_string_lossy().to_string();
+    let file_stem = file
+        .file_stem()
+        .expect("load_army_builds file stem fail")
+        .to_str()
+        .expect("load_army_builds to_str fail")
+        .to_string();
+
+    let mut created_on: DateTime<Utc>;
+    match std::fs::metadata(file) {
+        Ok(m) => {
+            let t = m.created().unwrap_or(std::time::SystemTime::now());
+            created_on = t.into();
+        }
+        Err(e) => {
+            println!("Getting metadata err {}", e);
+            return None;
+        }
+    }
+
+    let bytes = match std::fs::read(file) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            println!("Reading army setup err {}", e);
+            return None;
+        }
+    };
+    let build_id = get_army_build_id(bytes.as_slice());
+    let setup = match parse_army_setup(bytes.as_slice()) {
+        Ok(setup) => Some(setup),
+        Err(e) => {
+            println!("{} for file_stem {}", e, file_stem);
+            None
+        }
+    };
+
+    //file contents win over file name guesses
+    let factions = get_faction_registry();
+    let parsed = parse_filename(ca_game, &file_stem, templates);
+    let mut faction_str = parsed.faction;
+    let vs_faction_str = parsed
+        .vs_faction
+        .unwrap_or_else(|| UNKNOWN_FACTION.to_string());
+    let mut funds = parsed.funds.unwrap_or(12400);
+    let mut created_by = parsed.author.unwrap_or_default();
+    let mut unit_keys = vec![];
+    if let Some(setup) = setup.as_ref() {
+        if let Some(f) = factions.find_faction_by_key(ca_game, setup.faction_key.as_str()) {
+            faction_str = Some(f.name.clone());
+        }
+        let funds_level = get_army_setup_funds_level(setup.funds_level);
+        if funds_level != FundsLevel::Custom {
+            funds = get_funds_amount(funds_level, ca_game);
+        }
+        if !setup.author.is_empty() {
+            created_by = setup.author.clone();
+        }
+        unit_keys = setup.get_unit_keys();
+    }
+    let faction_str = faction_str.unwrap_or_else(|| UNKNOWN_FACTION.to_string());
+
+    //only wh2 builds carry the faction enum
+    let (faction, vs_faction) = if *ca_game == CaGame::Warhammer2 {
+        (
+            get_wh2_faction_from_name(faction_str.as_str()),
+            get_wh2_faction_from_name(vs_faction_str.as_str()),
+        )
+    } else {
+        (Wh2Factions::UNKNOWN, Wh2Factions::UNKNOWN)
+    };
+
+    Some(ArmyBuild {
+        file: file.to_path_buf(),
+        file_stem: file_stem.clone(),
+        faction,
+        funds,
+        vs_faction,
+        created_on,
+        original_file: file.to_path_buf(),
+        ca_game: get_ca_game_from_folder_name(file_string.as_str()),
+        created_by,
+        game_mod: String::new(),
+        faction_str,
+        vs_faction_str,
+        win_count: 0,
+        loss_count: 0,
+        draw_count: 0,
+        image_files: vec![],
+        notes: String::new(),
+        unit_keys,
+        build_id,
+    })
+}
+
+pub fn apply_library_metadata(builds: &mut [ArmyBuild], ca_game: &CaGame) {
+    //user metadata lives in the library index, not the setup files
+    match get_library_index_file(ca_game).and_then(|f| load_library_index(f.as_path())) {
+        Ok(index) => {
+            for build in builds.iter_mut() {
+                index.merge_into(build);
+            }
+        }
+        Err(e) => println!("load_army_builds library index {}", e),
+    }
+    match get_match_log_file(ca_game).and_then(|f| load_match_log(f.as_path())) {
+        Ok(log) => {
+            let counts = log.get_all_build_counts();
+            for build in builds.iter_mut() {
+                if let Some(c) = counts.get(&build.build_id) {
+                    c.apply_to(build);
+                }
+            }
+        }
+        Err(e) => println!("load_army_builds match log {}", e),
+    }
+}
+
+//Empire keeps its builds in .battle_preferences, everything else .army_setup
+fn is_ca_game_setup_file(file: &fs::DirEntry, ca_game: &CaGame) -> bool {
+    match file.path().extension() {
+        Some(ext) => ext.to_string_lossy() == get_ca_game_army_setup_ext(ca_game.clone()),
+        None => false,
+    }
+}
+
+//For paths that may be gone already, like ones a watcher reports
+pub fn is_ca_game_setup_path(file: &Path, ca_game: &CaGame) -> bool {
+    let stem_ok = match file.file_stem().and_then(OsStr::to_str) {
+        Some(stem) => !stem.is_empty() && !stem.starts_with('.'),
+        None => false,
+    };
+    stem_ok
+        && match file.extension().and_then(OsStr::to_str) {
+            Some(ext) => ext == get_ca_game_army_setup_ext(ca_game.clone()),
+            None => false,
+        }
+}
+
 fn is_army_setup_file(file: &fs::DirEntry) -> bool {
     match file.file_type() {
         Ok(ft) => {
@@ -233,7 +358,7 @@ fn is_army_setup_file(file: &fs::DirEntry) -> bool {
                         return false;
                     }
                     Some(ext) => {
-                        if ext == "army_setup" {
+                        if ext == "army_setup" || ext == "battle_preferences" {
                             return true;
                         }
                     }
@@ -270,29 +395,57 @@ pub fn validate_insert_folder(
     Ok(())
 }
 
-//this code block is failing
+//Root of the WarbossWaaghit library, AppData\Roaming on windows & the xdg data dir elsewhere
+pub fn get_owaagh_appdata_dir() -> Result<PathBuf, String> {
+    let root = if cfg!(windows) {
+        dirs::home_dir().map(|p| p.join("AppData").join("Roaming"))
+    } else {
+        dirs::data_dir()
+    };
+    match root {
+        Some(p) => Ok(p.join("WarbossWaaghit")),
+        None => Err("dirs::home_dir() None".to_string()),
+    }
+}
+
 //folder guarenteed to exist if return ok
 pub fn get_owaagh_army_setups_dir(game: &CaGame) -> Result<PathBuf, String> {
-    let mut game_subdir = get_ca_game_subfolder(game);
-
-    if let Some(mut p) = dirs::home_dir() {
-        p = p.join("AppData\\Roaming\\WarbossWaaghit");
-        p = p.join(game_subdir.as_str());
-        p = p.join("army_setups");
-        if !p.exists() {
-            match std::fs::create_dir(p.clone()) {
-                Ok(_) => {}
-                Err(e) => {
-                    return Err(format!("{}", e));
-                }
+    let game_subdir = get_ca_game_subfolder(game);
+
+    let mut p = get_owaagh_appdata_dir()?;
+    p = p.join(game_subdir.as_str());
+    p = p.join("army_setups");
+    if !p.exists() {
+        match std::fs::create_dir_all(p.clone()) {
+            Ok(_) => {}
+            Err(e) => {
+                return Err(format!("{}", e));
             }
         }
-        return Ok(p);
     }
-    let err = format!("dirs::home_dir() None",);
-    println!("get_owaagh_army_setups_dir err2 {}", err);
+    Ok(p)
+}
 
-    Err(err)
+//Library snapshots, inside the appdata dir but never part of one
+pub const OWAAGH_BACKUPS_SUBDIR: &str = "backups";
+
+pub fn get_owaagh_backups_dir() -> Result<PathBuf, String> {
+    let p = get_owaagh_appdata_dir()?.join(OWAAGH_BACKUPS_SUBDIR);
+    match std::fs::create_dir_all(p.as_path()) {
+        Ok(_) => Ok(p),
+        Err(e) => Err(format!("{}", e)),
+    }
+}
+
+//Deleted builds wait here until the trash is emptied, never part of a snapshot either
+pub const OWAAGH_TRASH_SUBDIR: &str = "trash";
+
+pub fn get_owaagh_trash_dir() -> Result<PathBuf, String> {
+    let p = get_owaagh_appdata_dir()?.join(OWAAGH_TRASH_SUBDIR);
+    match std::fs::create_dir_all(p.as_path()) {
+        Ok(_) => Ok(p),
+        Err(e) => Err(format!("{}", e)),
+    }
 }
 
 //this code block is failing
@@ -1,27 +1,93 @@
+use crate::advanced_search_container::{
+    get_search_score, has_name_query, AdvancedSearch, SearchFilter,
+};
 use crate::army_build::{
-    funds_slider_ui, get_army_build_display_column_title, show_army_build_header_row, ArmyBuild,
-    ArmyBuildDisplayColumns,
+    find_duplicate_builds, funds_slider_ui, get_army_build_display_column_title,
+    get_army_setup_funds_level_index, get_funds_level, show_army_build_header_row, ArmyBuild,
+    ArmyBuildDisplayColumns, ArmyBuildTableLayout,
 };
+use crate::army_setup_format::{read_army_setup_file, write_army_setup_file, ArmySetup};
 use crate::army_setups_folder::{
-    get_owaagh_army_setups_dir, get_tmp_default_army_setups_dir, load_army_builds,
-    validate_load_folder, ArmySetupsFolder,
+    get_owaagh_army_setups_dir, get_tmp_default_army_setups_dir, load_army_build,
+    load_army_builds, validate_load_folder, ArmySetupsFolder,
+};
+use crate::build_archive::{
+    is_archive_file, stage_archive_setups, ArchiveEntryReport, StagedArchive,
+};
+use crate::build_code::{decode_build_code, encode_build_code};
+use crate::build_pack::export_build_pack;
+use crate::build_renamer::{
+    apply_rename_plan, sanitize_file_stem, get_default_canonical_template, plan_canonical_renames, RenamePlan,
+};
+use crate::build_sync::{
+    get_sync_conflict_policy_title, run_sync, SyncAction, SyncPolicy, SyncReportEntry,
+    SYNC_CONFLICT_POLICIES,
 };
+use crate::build_trash::{empty_trash, list_trash, restore_from_trash, trash_build, TrashEntry};
+use crate::build_undo::{UndoAction, UndoStack};
+use crate::build_watcher::BuildUpdate;
 use crate::ca_game::{
-    get_ca_game_army_setup_ext, get_ca_game_army_setups_folder, get_ca_game_title, CaGame,
+    get_ca_game_army_setup_ext, get_ca_game_army_setups_folder, get_ca_game_subfolder,
+    get_ca_game_title, CaGame,
+};
+use crate::ca_game_discovery::get_ca_game_army_setups_candidates;
+use crate::faction_registry::{get_faction_registry, ALL_FACTIONS};
+use crate::factions::{faction_dropdown_button, get_wh2_faction_from_name};
+use crate::filename_template::{
+    compile_filename_templates, load_filename_templates, parse_filename, save_filename_templates,
+    DEFAULT_FILENAME_TEMPLATES,
+};
+use crate::library_backup::{
+    create_snapshot, list_snapshots, restore_snapshot, restore_snapshot_build, Snapshot,
+    DEFAULT_BACKUP_RETENTION,
+};
+use crate::library_store::{
+    get_library_index_file, load_library_index, update_library_index,
 };
-use crate::factions::{faction_dropdown_button, Wh2Factions};
+use crate::match_log::{
+    get_match_log_file, get_match_result_title, load_match_log, record_match, MatchRecord,
+    MatchResult,
+};
+use crate::misc_folders::{get_user_dir, pick_folder_dialog};
+use crate::replays::ReplayLibrary;
+use crate::search_query::{parse_search_query, SearchQueryError, SEARCH_QUERY_HELP};
 use crate::ymd_hms_dash_format::YMD_HMS_FORMAT;
 use chrono::offset::Utc;
 use chrono::DateTime;
 use eframe::egui;
 use eframe::egui::{Align, Color32, ScrollArea, Ui};
 use enum_iterator::IntoEnumIterator;
+use std::cmp::Ordering;
 use std::collections::{HashMap, HashSet};
 use std::ffi::OsString;
 use std::iter::FromIterator;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::SystemTime;
-use wfd::{DialogParams, FOS_PICKFOLDERS};
+
+fn get_default_pack_export_file() -> String {
+    match get_user_dir("Downloads", false) {
+        Ok(p) => p.join("WarbossWaaghit Builds.zip").to_string_lossy().to_string(),
+        Err(_) => String::new(),
+    }
+}
+
+fn get_default_backup_retention() -> usize {
+    DEFAULT_BACKUP_RETENTION
+}
+
+//Things that can't be done without a yes first
+#[cfg_attr(feature = "persistence", derive(Clone))]
+enum PendingConfirm {
+    Delete(Box<ArmyBuild>),
+    EmptyTrash,
+}
+
+//What loading did with each build it found
+pub enum LoadOutcome {
+    Added(ArmyBuild),              // as it is in the library now
+    AlreadyHad(ArmyBuild, String), // & the library name it's under
+    CopyFailed(ArmyBuild, String),
+}
 
 #[cfg_attr(
     feature = "persistence",
@@ -31,16 +97,87 @@ pub struct ArmySetupsManager {
     selected_game: CaGame,
 
     load_folder: ArmySetupsFolder,
+    #[serde(skip)]
+    load_report: Vec<ArchiveEntryReport>, // per file, from the last build pack loaded
+    #[serde(default = "get_default_pack_export_file")]
+    pack_export_file: String,
+    #[serde(skip)]
+    pack_export_message: String,
+    #[serde(skip)]
+    build_code: Option<(String, String)>, // build identity & its code
+    #[serde(skip)]
+    import_code: String,
+    #[serde(skip)]
+    import_code_message: String,
+    #[serde(default)]
+    sync_policy: SyncPolicy,
+    #[serde(skip)]
+    sync_report: Vec<SyncReportEntry>, // from the last sync or dry run
+    #[serde(skip)]
+    sync_message: String,
+    #[serde(default = "get_default_backup_retention")]
+    backup_retention: usize, // snapshots kept, oldest go first
+    #[serde(skip)]
+    snapshots: Option<Vec<Snapshot>>, // newest first, listed when the backups are opened
+    #[serde(skip)]
+    backup_message: String,
+    #[serde(skip)]
+    undo_stack: UndoStack,
+    #[serde(skip)]
+    pending_confirm: Option<PendingConfirm>, // waiting on a yes in the confirm window
+    #[serde(skip)]
+    trash: Option<Vec<TrashEntry>>, // newest first, listed when the trash is opened
+    #[serde(skip)]
+    delete_message: String,
+    #[serde(skip)]
+    edit_message: String,
     pub(crate) army_builds: HashMap<CaGame, HashSet<ArmyBuild>>,
 
     display_builds: Vec<ArmyBuild>,
     search_string: String,
+    #[serde(skip)]
+    search_error: String,
     search_mod: String,
-    search_faction: Wh2Factions,
-    search_vs_faction: Wh2Factions,
+    #[serde(default)]
+    search_faction_name: String, // registry faction name, empty or All for any
+    #[serde(default)]
+    search_vs_faction_name: String,
     pub(crate) selected_army_build: ArmyBuild,
     edit_build: ArmyBuild,
 
+    #[serde(default)]
+    advanced_search: AdvancedSearch,
+    #[serde(default)]
+    table_layout: ArmyBuildTableLayout,
+    #[serde(skip, default = "load_filename_templates")]
+    filename_templates: Vec<String>, // lives in its own file so loading can get at it
+    #[serde(skip)]
+    template_test_name: String,
+    #[serde(default = "get_default_canonical_template")]
+    canonical_template: String,
+    #[serde(default)]
+    rename_tag: String,
+    #[serde(skip)]
+    rename_plan: Option<RenamePlan>,
+    #[serde(skip)]
+    rename_result: String,
+    #[serde(default)]
+    match_opponent_faction: String,
+    #[serde(default)]
+    match_opponent_name: String,
+    #[serde(default)]
+    match_map: String,
+    #[serde(skip)]
+    match_error: String,
+    #[serde(skip)]
+    selected_matches: Option<(String, Vec<MatchRecord>)>, // build_id & its games, newest first
+    #[serde(skip)]
+    replay_library: Option<ReplayLibrary>,
+    #[serde(skip)]
+    replay_error: String,
+    #[serde(default)]
+    duplicate_builds: HashMap<String, Vec<ArmyBuild>>, // by build_id, every name it was loaded as
+
     selected_display_build_indx: usize,
     tack_item_align: Align,
     offset: f32,
@@ -51,30 +188,33 @@ pub struct ArmySetupsManager {
 
 impl Default for ArmySetupsManager {
     fn default() -> Self {
+        let mut army_builds = ArmySetupsManager::get_ca_army_builds();
+
+        if ArmySetupsFolder::get_tmp_defaults_folder().exists() {
+            ArmySetupsManager::append_default_army_builds(&mut army_builds);
+            std::fs::remove_dir_all(ArmySetupsFolder::get_tmp_defaults_folder());
+        }
+
+        ArmySetupsManager::new(CaGame::Warhammer2, army_builds)
+    }
+}
 
-        let default_load_path = get_ca_game_army_setups_folder(CaGame::Warhammer2)
+impl ArmySetupsManager {
+    fn new(selected_game: CaGame, army_builds: HashMap<CaGame, HashSet<ArmyBuild>>) -> Self {
+        let default_load_path = get_ca_game_army_setups_folder(selected_game.clone())
             .unwrap_or(
                 PathBuf::from("C:\\Users\\DaBiggestBoss\\AppData\\Roaming\\The Creative Assembly\\Warhammer2\\army_setups")
             );
         let load_folder =
             ArmySetupsFolder::new(default_load_path.to_string_lossy().to_string().as_str());
 
-        let default_insert_path = get_ca_game_army_setups_folder(CaGame::Warhammer2)
+        let default_insert_path = get_ca_game_army_setups_folder(selected_game.clone())
             .unwrap_or(
                 PathBuf::from("C:\\Users\\DaBiggestBoss\\AppData\\Roaming\\The Creative Assembly\\Warhammer2\\army_setups")
             );
         let insert_folder =
             ArmySetupsFolder::new(default_insert_path.to_string_lossy().to_string().as_str());
 
-
-        let mut army_builds = ArmySetupsManager::get_ca_army_builds();
-
-        if ArmySetupsFolder::get_tmp_defaults_folder().exists() {
-            ArmySetupsManager::append_default_army_builds(&mut army_builds);
-            std::fs::remove_dir_all(ArmySetupsFolder::get_tmp_defaults_folder());
-        }
-
-        let selected_game = CaGame::Warhammer2;
         let display_builds = match army_builds.get(&selected_game) {
             Some(army_set) => army_set.iter().cloned().collect(),
             None => vec![],
@@ -84,15 +224,49 @@ impl Default for ArmySetupsManager {
             selected_game,
 
             load_folder,
+            load_report: vec![],
+            pack_export_file: get_default_pack_export_file(),
+            pack_export_message: "".to_owned(),
+            build_code: None,
+            import_code: "".to_owned(),
+            import_code_message: "".to_owned(),
+            sync_policy: SyncPolicy::default(),
+            sync_report: vec![],
+            sync_message: "".to_owned(),
+            backup_retention: DEFAULT_BACKUP_RETENTION,
+            snapshots: None,
+            backup_message: "".to_owned(),
+            undo_stack: UndoStack::default(),
+            pending_confirm: None,
+            trash: None,
+            delete_message: "".to_owned(),
+            edit_message: "".to_owned(),
             army_builds,
 
             display_builds,
             search_string: "".to_owned(),
-            search_faction: Wh2Factions::ALL,
-            search_vs_faction: Wh2Factions::ALL,
+            search_error: "".to_owned(),
+            search_faction_name: ALL_FACTIONS.to_string(),
+            search_vs_faction_name: ALL_FACTIONS.to_string(),
             search_mod: "".to_owned(),
             selected_army_build: ArmyBuild::default(),
             edit_build: ArmyBuild::default(),
+            advanced_search: AdvancedSearch::default(),
+            table_layout: ArmyBuildTableLayout::default(),
+            filename_templates: load_filename_templates(),
+            template_test_name: "GS vs HE 12400".to_owned(),
+            canonical_template: get_default_canonical_template(),
+            rename_tag: "".to_owned(),
+            rename_plan: None,
+            rename_result: "".to_owned(),
+            match_opponent_faction: ALL_FACTIONS.to_string(),
+            match_opponent_name: "".to_owned(),
+            match_map: "".to_owned(),
+            match_error: "".to_owned(),
+            selected_matches: None,
+            replay_library: None,
+            replay_error: "".to_owned(),
+            duplicate_builds: HashMap::new(),
 
             selected_display_build_indx: usize::MAX,
             tack_item_align: Align::Center,
@@ -102,9 +276,18 @@ impl Default for ArmySetupsManager {
             insert_folder,
         }
     }
-}
 
-impl ArmySetupsManager {
+    //Only the game's library, for running without the app's saved state
+    pub fn from_library(ca_game: CaGame) -> Result<Self, String> {
+        let folder = get_owaagh_army_setups_dir(&ca_game)?;
+        let builds = load_army_builds(folder.to_string_lossy().as_ref(), &ca_game);
+        let mut army_builds = HashMap::new();
+        army_builds.insert(ca_game.clone(), HashSet::from_iter(builds));
+        let mut manager = ArmySetupsManager::new(ca_game, army_builds);
+        manager.update_display_builds();
+        Ok(manager)
+    }
+
     fn get_ca_army_builds() -> HashMap<CaGame, HashSet<ArmyBuild>> {
         let mut army_builds: HashMap<CaGame, HashSet<ArmyBuild>> = HashMap::new();
         for ca_game in CaGame::into_enum_iter() {
@@ -235,6 +418,10 @@ impl ArmySetupsManager {
     }
 
     pub fn load_folder_to_owaagh_appdata(&mut self) -> Result<String, String> {
+        let load_path = PathBuf::from(self.load_folder.folder_string.as_str());
+        if is_archive_file(load_path.as_path()) {
+            return self.load_archive_to_owaagh_appdata(load_path.as_path());
+        }
         let res = validate_load_folder(&self.load_folder.folder_string);
         if res.is_err() {
             return Err(format!(
@@ -242,145 +429,1426 @@ impl ArmySetupsManager {
                 res.err().unwrap()
             ));
         }
-        let res1 = get_owaagh_army_setups_dir(&self.selected_game);
+
+        //Prepping army builds folder
+        let armies = load_army_builds(
+            self.load_folder.folder_string.as_str(),
+            &self.selected_game,
+        );
+        //If loading from CA game folder, have that be dominant file naming system, will always match what you have there.
+        let from_ca_game_folder = self.load_folder.is_ca_game_folder();
+
+        let (outcomes, n_duplicates) = self.copy_builds_to_owaagh_appdata(
+            &self.selected_game.clone(),
+            armies,
+            from_ca_game_folder,
+        )?;
+        let n_added = outcomes
+            .iter()
+            .filter(|o| matches!(o, LoadOutcome::Added(_)))
+            .count();
+
+        Ok(format!(
+            "{} Builds Added, {} Already Had Under Another Name",
+            n_added, n_duplicates
+        ))
+    }
+
+    //Copies loaded builds into the game's library, also gives back how many builds turned up under another name
+    fn copy_builds_to_owaagh_appdata(
+        &mut self,
+        ca_game: &CaGame,
+        armies: Vec<ArmyBuild>,
+        from_ca_game_folder: bool,
+    ) -> Result<(Vec<LoadOutcome>, usize), String> {
+        let res1 = get_owaagh_army_setups_dir(ca_game);
         if res1.is_err() {
             return Err(format!(
                 "load_folder_to_owaagh_appdata {}",
                 res1.err().unwrap()
             ));
         }
-        let owaagh_appdata_path = res1.unwrap().clone();
+        let owaagh_appdata_path = res1.unwrap().clone();
+        let game_extension = format!(".{}", get_ca_game_army_setup_ext(ca_game.clone()));
+
+        let mut game_army_builds = self.get_game_army_builds(ca_game.clone());
+        let mut outcomes = vec![];
+        let mut added = vec![];
+        let mut seen = vec![];
+        for mut a in armies.into_iter() {
+            //Same content already in the library or earlier in this folder, only note the other name
+            if let Some(existing) = game_army_builds.get(&a) {
+                seen.push(existing.clone());
+                outcomes.push(LoadOutcome::AlreadyHad(a.clone(), existing.file_stem.clone()));
+                seen.push(a);
+                continue;
+            }
+
+            let mut new_file_path =
+                owaagh_appdata_path.join(format!("{}{}", a.file_stem, game_extension));
+            if new_file_path.exists() {
+                if from_ca_game_folder {
+                    //game's version of the name wins, drop whatever build had it
+                    game_army_builds.retain(|b| b.file != new_file_path);
+                } else {
+                    match std::fs::metadata(a.file.as_path()) {
+                        Ok(m) => {
+                            let t = m.created().unwrap_or(std::time::SystemTime::now());
+                            let datetime: DateTime<Utc> = t.into();
+                            a.file_stem =
+                                format!("{} {}", a.file_stem, datetime.format(YMD_HMS_FORMAT));
+                        }
+                        Err(e) => {
+                            return Err(format!("Getting metadata err {}", e));
+                        }
+                    }
+                    new_file_path =
+                        owaagh_appdata_path.join(format!("{}{}", a.file_stem, game_extension));
+                }
+            }
+
+            //copy and add in new army builds
+            match std::fs::copy(a.file.clone(), new_file_path.clone()) {
+                Ok(_) => a.file = new_file_path,
+                Err(e) => {
+                    let err = format!(
+                        "load_folder_to_owaagh_appdata Couldn't copy from {} to {} err {}",
+                        a.file.to_string_lossy(),
+                        new_file_path.to_string_lossy(),
+                        e
+                    );
+                    println!("{}", err);
+                    outcomes.push(LoadOutcome::CopyFailed(a, err));
+                    continue;
+                }
+            }
+            game_army_builds.insert(a.clone());
+            outcomes.push(LoadOutcome::Added(a.clone()));
+            added.push(a);
+        }
+
+        ArmySetupsManager::save_game_library_metadata(ca_game, added.as_slice());
+        self.army_builds.insert(ca_game.clone(), game_army_builds);
+
+        let duplicates = find_duplicate_builds(seen.as_slice());
+        let n_duplicates = duplicates.len();
+        for (build_id, copies) in duplicates.into_iter() {
+            let known = self.duplicate_builds.entry(build_id).or_default();
+            for c in copies {
+                if !known.iter().any(|k| k.file_stem == c.file_stem) {
+                    known.push(c);
+                }
+            }
+        }
+
+        Ok((outcomes, n_duplicates))
+    }
+
+    //Unpacks a build pack's setups by game, loads each game's like a folder & reports on every file
+    fn load_archive_to_owaagh_appdata(&mut self, archive: &Path) -> Result<String, String> {
+        let staging = std::env::temp_dir().join(format!("owaagh_staging_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(staging.as_path());
+        let res = stage_archive_setups(archive, &self.selected_game, staging.as_path())
+            .and_then(|staged| self.load_staged_archive(archive, staged));
+        let _ = std::fs::remove_dir_all(staging.as_path());
+        res
+    }
+
+    fn load_staged_archive(
+        &mut self,
+        archive: &Path,
+        staged: StagedArchive,
+    ) -> Result<String, String> {
+        let mut report = staged.skipped;
+        let mut n_added = 0;
+        let mut loaded_files = HashSet::new();
+        for (ca_game, folder) in staged.game_folders.iter() {
+            let mut armies = load_army_builds(folder.to_string_lossy().as_ref(), ca_game);
+            //keep where it came from as archive/member, the staging copy won't be around
+            let mut member_by_original = HashMap::new();
+            //packs we made bring back the names & metadata the files don't carry
+            let mut manifest_notes = HashMap::new();
+            for a in armies.iter_mut() {
+                if let Some(member) = staged.member_by_file.get(&a.file) {
+                    loaded_files.insert(a.file.clone());
+                    a.original_file = archive.join(member);
+                    member_by_original.insert(a.original_file.clone(), member.clone());
+                    let entry = staged
+                        .manifest
+                        .as_ref()
+                        .and_then(|m| m.get_entry(member.as_str()));
+                    if let Some(entry) = entry {
+                        if let Err(e) = entry.apply_to(a) {
+                            manifest_notes.insert(member.clone(), e);
+                        }
+                    }
+                }
+            }
+
+            let (outcomes, _) = self.copy_builds_to_owaagh_appdata(ca_game, armies, false)?;
+            for outcome in outcomes {
+                let (build, result) = match outcome {
+                    LoadOutcome::Added(b) => {
+                        n_added += 1;
+                        let added = format!("Added to {} as {}", get_ca_game_title(ca_game), b.file_stem);
+                        (b, Ok(added))
+                    }
+                    LoadOutcome::AlreadyHad(b, name) => {
+                        (b, Err(format!("Already in the library as {}", name)))
+                    }
+                    LoadOutcome::CopyFailed(b, e) => (b, Err(e)),
+                };
+                let member = member_by_original
+                    .get(&build.original_file)
+                    .cloned()
+                    .unwrap_or(build.file_stem);
+                let result = match (result, manifest_notes.get(&member)) {
+                    (Ok(s), Some(note)) => Ok(format!("{}, {}", s, note)),
+                    (result, _) => result,
+                };
+                report.push(ArchiveEntryReport { member, result });
+            }
+        }
+        for (file, member) in staged.member_by_file.iter() {
+            if !loaded_files.contains(file) {
+                report.push(ArchiveEntryReport {
+                    member: member.clone(),
+                    result: Err("Couldn't read the setup".to_string()),
+                });
+            }
+        }
+        report.sort_by(|a, b| a.member.cmp(&b.member));
+
+        let n_skipped = report.len() - n_added;
+        self.load_report = report;
+        Ok(format!(
+            "{} Builds Added, {} Files Skipped",
+            n_added, n_skipped
+        ))
+    }
+
+    pub fn get_selected_build_code(&self) -> Result<String, String> {
+        let build = &self.selected_army_build;
+        match std::fs::read(build.file.as_path()) {
+            Ok(bytes) => encode_build_code(build, bytes.as_slice()),
+            Err(e) => Err(format!("Couldn't read {} {}", build.file.to_string_lossy(), e)),
+        }
+    }
+
+    //Writes the code's setup out as a one file folder & loads that, with the code's metadata on top
+    pub fn import_build_code(&mut self, code: &str) -> Result<String, String> {
+        let (entry, setup_bytes) = match decode_build_code(code) {
+            Ok(decoded) => decoded,
+            Err(e) => return Err(format!("{}", e)),
+        };
+        let ca_game = entry.ca_game.clone();
+        let staging = std::env::temp_dir().join(format!("owaagh_code_{}", std::process::id()));
+        let folder = staging.join(get_ca_game_subfolder(&ca_game));
+        let mut file_stem = sanitize_file_stem(entry.name.as_str());
+        if file_stem.is_empty() || file_stem.starts_with('.') {
+            file_stem = "Build Code".to_string();
+        }
+        let file = folder.join(format!(
+            "{}.{}",
+            file_stem,
+            get_ca_game_army_setup_ext(ca_game.clone())
+        ));
+
+        let _ = std::fs::remove_dir_all(staging.as_path());
+        let written = std::fs::create_dir_all(folder.as_path())
+            .and_then(|_| std::fs::write(file.as_path(), setup_bytes.as_slice()));
+        let res = match written {
+            Ok(()) => {
+                let mut armies = load_army_builds(folder.to_string_lossy().as_ref(), &ca_game);
+                for a in armies.iter_mut() {
+                    a.original_file = PathBuf::new();
+                    let _ = entry.apply_to(a);
+                }
+                self.copy_builds_to_owaagh_appdata(&ca_game, armies, false)
+            }
+            Err(e) => Err(format!("Couldn't write {} {}", file.to_string_lossy(), e)),
+        };
+        let _ = std::fs::remove_dir_all(staging.as_path());
+        self.update_display_builds();
+
+        match res?.0.pop() {
+            Some(LoadOutcome::Added(b)) => Ok(format!(
+                "Added {} to {}",
+                b.file_stem,
+                get_ca_game_title(&ca_game)
+            )),
+            Some(LoadOutcome::AlreadyHad(_, name)) => Ok(format!("Already had it as {}", name)),
+            Some(LoadOutcome::CopyFailed(_, e)) => Err(e),
+            None => Err("Couldn't read the setup in the code".to_string()),
+        }
+    }
+
+    //Settles a same build different names group on one name, the library copy gets renamed to it
+    //Dry run unless apply, a real sync reloads the game's builds from both folders after
+    pub fn sync_with_game_folder(&mut self, apply: bool) -> Result<String, String> {
+        let ca_game = self.selected_game.clone();
+        let game = get_ca_game_army_setups_folder(ca_game.clone())?;
+        let library = get_owaagh_army_setups_dir(&ca_game)?;
+        if apply {
+            self.create_library_snapshot()?;
+        }
+        let (plan, report) = run_sync(
+            &ca_game,
+            game.as_path(),
+            library.as_path(),
+            &self.sync_policy,
+            apply,
+        )?;
+        let n_skipped = report.iter().filter(|r| r.action == SyncAction::Skip).count();
+        let n_failed = report.iter().filter(|r| r.result.is_err()).count();
+        self.sync_report = report;
+        self.trash = None; // deletes go in the trash
+        if !apply {
+            return Ok(format!(
+                "{} to sync, {} left alone, {} already in sync",
+                plan.items.len() - n_skipped,
+                n_skipped,
+                plan.in_sync
+            ));
+        }
+
+        self.army_builds.insert(
+            ca_game.clone(),
+            ArmySetupsManager::get_game_folders_builds(&ca_game),
+        );
+        self.update_display_builds();
+        Ok(format!(
+            "{} synced, {} left alone, {} failed",
+            plan.items.len() - n_skipped - n_failed,
+            n_skipped,
+            n_failed
+        ))
+    }
+
+    pub fn set_sync_policy(&mut self, policy: SyncPolicy) {
+        self.sync_policy = policy;
+    }
+
+    pub fn get_sync_report(&self) -> &[SyncReportEntry] {
+        self.sync_report.as_slice()
+    }
+
+    pub fn create_library_snapshot(&mut self) -> Result<String, String> {
+        let snapshot = create_snapshot(self.backup_retention)?;
+        self.snapshots = None;
+        Ok(format!("Snapshot {} made", snapshot.name))
+    }
+
+    pub fn get_library_snapshots(&mut self) -> Result<&[Snapshot], String> {
+        if self.snapshots.is_none() {
+            self.snapshots = Some(list_snapshots()?);
+        }
+        Ok(self.snapshots.as_deref().unwrap_or_default())
+    }
+
+    pub fn set_backup_retention(&mut self, retention: usize) {
+        self.backup_retention = retention.max(1);
+    }
+
+    //By name, or latest for the newest
+    fn find_library_snapshot(&mut self, name: &str) -> Result<Snapshot, String> {
+        let snapshots = self.get_library_snapshots()?;
+        let found = if name == "latest" {
+            snapshots.first()
+        } else {
+            snapshots.iter().find(|s| s.name == name)
+        };
+        found
+            .cloned()
+            .ok_or_else(|| format!("No snapshot called {}", name))
+    }
+
+    //Every game's builds come back from the restored library
+    fn reload_library_builds(&mut self) {
+        let games: Vec<CaGame> = self.army_builds.keys().cloned().collect();
+        for ca_game in games {
+            let builds = ArmySetupsManager::get_game_folders_builds(&ca_game);
+            self.army_builds.insert(ca_game, builds);
+        }
+        self.selected_army_build = ArmyBuild::default();
+        self.update_display_builds();
+    }
+
+    pub fn restore_library_snapshot(&mut self, name: &str) -> Result<String, String> {
+        let snapshot = self.find_library_snapshot(name)?;
+        let before = restore_snapshot(&snapshot, self.backup_retention)?;
+        self.snapshots = None;
+        self.reload_library_builds();
+        Ok(format!(
+            "Restored {}, what was there is in snapshot {}",
+            snapshot.name, before.name
+        ))
+    }
+
+    pub fn restore_library_snapshot_build(
+        &mut self,
+        name: &str,
+        file_name: &str,
+    ) -> Result<String, String> {
+        let snapshot = self.find_library_snapshot(name)?;
+        let file = restore_snapshot_build(&snapshot, &self.selected_game, file_name)?;
+        let ca_game = self.selected_game.clone();
+        let builds = ArmySetupsManager::get_game_folders_builds(&ca_game);
+        self.army_builds.insert(ca_game, builds);
+        self.update_display_builds();
+        Ok(format!(
+            "Restored {} from {}",
+            file.file_name().unwrap_or_default().to_string_lossy(),
+            snapshot.name
+        ))
+    }
+
+    //Into the trash with its metadata, the library index keeps its copy too
+    pub fn delete_army_build(&mut self, build: &ArmyBuild) -> Result<String, String> {
+        let metadata = get_library_index_file(&build.ca_game)
+            .and_then(|f| load_library_index(f.as_path()))
+            .ok()
+            .and_then(|mut index| index.remove(&build.build_id));
+        let entry = trash_build(build, metadata)?;
+        self.apply_game_build_changes(
+            &build.ca_game,
+            vec![BuildUpdate::Removed {
+                file: build.file.clone(),
+                same_content: None, // the watcher finds it if there is one
+            }],
+        );
+        if self.selected_army_build.file == build.file {
+            self.selected_army_build = ArmyBuild::default();
+            self.edit_build = ArmyBuild::default();
+        }
+        self.trash = None;
+        let message = format!("{} is in the trash", build.file_stem);
+        self.undo_stack.push(UndoAction::Delete(Box::new(entry)));
+        Ok(message)
+    }
+
+    pub fn get_trash(&mut self) -> Result<&[TrashEntry], String> {
+        if self.trash.is_none() {
+            self.trash = Some(list_trash()?);
+        }
+        Ok(self.trash.as_deref().unwrap_or_default())
+    }
+
+    pub fn restore_trashed_build(&mut self, trash_file: &str) -> Result<String, String> {
+        let (entry, file) = restore_from_trash(trash_file)?;
+        self.trash = None;
+        //restored from the trash view its delete has nothing left to undo
+        self.undo_stack
+            .retain(|a| !matches!(a, UndoAction::Delete(e) if e.trash_file == trash_file));
+        if let Some(metadata) = entry.metadata.clone() {
+            //edits made since the delete win
+            let res = update_library_index(&entry.ca_game, |index| {
+                index
+                    .builds
+                    .entry(entry.build_id.clone())
+                    .or_insert(metadata);
+            });
+            if let Err(e) = res {
+                println!("restore_trashed_build {}", e);
+            }
+        }
+        if let Some(build) = load_army_build(file.as_path(), &entry.ca_game) {
+            self.apply_game_build_changes(&entry.ca_game, vec![BuildUpdate::Upserted(build)]);
+        }
+        Ok(format!(
+            "Restored {}",
+            file.file_stem().unwrap_or_default().to_string_lossy()
+        ))
+    }
+
+    pub fn empty_build_trash(&mut self) -> Result<String, String> {
+        let n = empty_trash()?;
+        self.trash = None;
+        //deletes can't come back any more
+        self.undo_stack
+            .retain(|a| !matches!(a, UndoAction::Delete(_)));
+        Ok(format!("{} builds gone for good", n))
+    }
+
+    pub fn get_undo_title(&self) -> Option<String> {
+        self.undo_stack.peek().map(|a| a.get_title())
+    }
+
+    pub fn undo(&mut self) -> Result<String, String> {
+        let action = match self.undo_stack.pop() {
+            Some(action) => action,
+            None => return Err("Nothing to undo".to_string()),
+        };
+        let title = action.get_title();
+        let res = match action {
+            UndoAction::Delete(entry) => {
+                let res = self.restore_trashed_build(entry.trash_file.as_str());
+                //still in the trash, keep it to try again
+                let is_trashed = || {
+                    list_trash()
+                        .map(|t| t.iter().any(|e| e.trash_file == entry.trash_file))
+                        .unwrap_or(false)
+                };
+                if res.is_err() && is_trashed() {
+                    self.undo_stack.push(UndoAction::Delete(entry));
+                }
+                res.map(|_| ())
+            }
+            UndoAction::Rename(renames) => self.undo_renames(renames),
+            UndoAction::MetadataEdit(edit) => {
+                let (before, after) = *edit;
+                self.undo_metadata_edit(before, after);
+                Ok(())
+            }
+        };
+        match res {
+            Ok(()) => Ok(format!("Undid {}", title)),
+            Err(e) => Err(format!("Couldn't undo {} {}", title, e)),
+        }
+    }
+
+    //Through tmp names like the rename plan so swapped names come back too
+    fn undo_renames(&mut self, renames: Vec<(ArmyBuild, ArmyBuild)>) -> Result<(), String> {
+        let mut errors = vec![];
+        let mut staged = vec![];
+        for (i, (before, after)) in renames.into_iter().enumerate() {
+            let tmp = after.file.with_file_name(format!(".owaagh_undo_{}.tmp", i));
+            match std::fs::rename(after.file.as_path(), tmp.as_path()) {
+                Ok(_) => staged.push((before, after, tmp)),
+                Err(e) => errors.push(format!("{} {}", after.file_stem, e)),
+            }
+        }
+        let mut undone = vec![];
+        for (before, after, tmp) in staged {
+            let to = if before.file.exists() {
+                errors.push(format!("{} is taken", before.file_stem));
+                after.file.clone()
+            } else {
+                before.file.clone()
+            };
+            if let Err(e) = std::fs::rename(tmp.as_path(), to.as_path()) {
+                errors.push(format!("{} {}", before.file_stem, e));
+                continue;
+            }
+            if to == before.file {
+                if let Some(army_set) = self.army_builds.get_mut(&before.ca_game) {
+                    army_set.replace(before.clone());
+                }
+                if self.selected_army_build == before {
+                    self.selected_army_build = before.clone();
+                    self.edit_build = before.clone();
+                }
+                undone.push(before);
+            }
+        }
+        self.save_library_metadata(undone.as_slice());
+        self.update_display_builds();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join(", "))
+        }
+    }
+
+    fn undo_metadata_edit(&mut self, before: ArmyBuild, after: ArmyBuild) {
+        if let Some(army_set) = self.army_builds.get_mut(&before.ca_game) {
+            army_set.remove(&after);
+            army_set.insert(before.clone());
+        }
+        self.save_library_metadata(std::slice::from_ref(&before));
+        if self.selected_army_build == before {
+            self.selected_army_build = before.clone();
+            self.edit_build = before;
+        }
+        self.update_display_builds();
+    }
+
+    pub fn merge_duplicate_builds(&mut self, build_id: &str, keep_name: &str) -> Result<(), String> {
+        let mut build = match self
+            .get_game_army_builds(self.selected_game.clone())
+            .into_iter()
+            .find(|b| b.build_id == build_id)
+        {
+            Some(b) => b,
+            None => return Err("Dat build ain't in da library".to_string()),
+        };
+
+        if build.file_stem != keep_name {
+            let mut new_file = build.file.clone();
+            new_file.set_file_name(format!(
+                "{}.{}",
+                keep_name,
+                get_ca_game_army_setup_ext(self.selected_game.clone())
+            ));
+            if new_file.exists() {
+                return Err(format!("Already got a build named {}", keep_name));
+            }
+            if let Err(e) = std::fs::rename(build.file.as_path(), new_file.as_path()) {
+                return Err(format!("Couldn't rename {} {}", build.file_stem, e));
+            }
+            let before = build.clone();
+            build.file = new_file;
+            build.file_stem = keep_name.to_string();
+            self.undo_stack
+                .push(UndoAction::Rename(vec![(before, build.clone())]));
+        }
+
+        if let Some(army_set) = self.army_builds.get_mut(&self.selected_game) {
+            army_set.replace(build.clone());
+        }
+        self.save_library_metadata(std::slice::from_ref(&build));
+        self.duplicate_builds.remove(build_id);
+        if self.selected_army_build == build {
+            self.selected_army_build = build.clone();
+            self.edit_build = build;
+        }
+        self.update_display_builds();
+        Ok(())
+    }
+
+    //Only WarbossWaaghit's own copies get renamed, the game's folder is left alone
+    fn get_library_builds(&mut self) -> Vec<ArmyBuild> {
+        let library_dir = match get_owaagh_army_setups_dir(&self.selected_game) {
+            Ok(dir) => dir,
+            Err(_) => return vec![],
+        };
+        self.get_game_army_builds(self.selected_game.clone())
+            .into_iter()
+            .filter(|b| b.file.parent() == Some(library_dir.as_path()))
+            .collect()
+    }
+
+    pub fn plan_normalize_names(&mut self) -> RenamePlan {
+        let builds = self.get_library_builds();
+        plan_canonical_renames(
+            builds.as_slice(),
+            self.canonical_template.as_str(),
+            self.rename_tag.trim(),
+            &self.selected_game,
+        )
+    }
+
+    //Renames the library files to the plan, original_file still points where each build came from
+    pub fn apply_normalize_names(&mut self, plan: &RenamePlan) -> Result<usize, String> {
+        let library_dir = get_owaagh_army_setups_dir(&self.selected_game)?;
+        let (done, errors) = apply_rename_plan(plan, library_dir.as_path(), &self.selected_game);
+
+        let mut renamed = vec![];
+        let mut undo = vec![];
+        if let Some(army_set) = self.army_builds.get_mut(&self.selected_game) {
+            for step in done.iter() {
+                let old = match army_set.iter().find(|b| b.get_identity() == step.identity) {
+                    Some(b) => b.clone(),
+                    None => continue,
+                };
+                let mut build = old.clone();
+                build.file.set_file_name(format!(
+                    "{}.{}",
+                    step.to_stem,
+                    get_ca_game_army_setup_ext(self.selected_game.clone())
+                ));
+                build.file_stem = step.to_stem.clone();
+                army_set.remove(&old);
+                army_set.insert(build.clone());
+                if self.selected_army_build == old {
+                    self.selected_army_build = build.clone();
+                    self.edit_build = build.clone();
+                }
+                undo.push((old, build.clone()));
+                renamed.push(build);
+            }
+        }
+        if !undo.is_empty() {
+            self.undo_stack.push(UndoAction::Rename(undo));
+        }
+        self.save_library_metadata(renamed.as_slice());
+        self.update_display_builds();
+
+        if errors.is_empty() {
+            Ok(renamed.len())
+        } else {
+            Err(format!("Renamed {}, {}", renamed.len(), errors.join(", ")))
+        }
+    }
+
+    fn normalize_names_ui(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Template");
+            ui.text_edit_singleline(&mut self.canonical_template)
+                .on_hover_text("{faction} {vs_faction} {funds} {author} {tag} {id}");
+            if ui.small_button("Default").clicked() {
+                self.canonical_template = get_default_canonical_template();
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("Tag");
+            ui.text_edit_singleline(&mut self.rename_tag);
+        });
+
+        if ui.button("Preview").clicked() {
+            self.rename_plan = Some(self.plan_normalize_names());
+            self.rename_result = "".to_owned();
+        }
+
+        let mut apply = false;
+        if let Some(plan) = self.rename_plan.as_ref() {
+            for e in plan.errors.iter() {
+                ui.colored_label(Color32::RED, e);
+            }
+            ui.label(format!(
+                "{} to rename, {} already named right",
+                plan.steps.len(),
+                plan.unchanged
+            ));
+            ScrollArea::from_max_height(200.0).show(ui, |ui| {
+                egui::Grid::new("rename_plan_grid").striped(true).show(ui, |ui| {
+                    for step in plan.steps.iter() {
+                        ui.label(step.from_stem.as_str());
+                        ui.label("→");
+                        if step.collided {
+                            ui.colored_label(Color32::YELLOW, step.to_stem.as_str())
+                                .on_hover_text("Name was taken, got a number");
+                        } else {
+                            ui.label(step.to_stem.as_str());
+                        }
+                        ui.end_row();
+                    }
+                });
+            });
+            apply = !plan.steps.is_empty() && ui.button("Rename Them").clicked();
+        }
+
+        if apply {
+            if let Some(plan) = self.rename_plan.take() {
+                self.rename_result = match self.apply_normalize_names(&plan) {
+                    Ok(n) => format!("Renamed {}", n),
+                    Err(e) => e,
+                };
+            }
+        }
+        if !self.rename_result.is_empty() {
+            ui.label(self.rename_result.as_str());
+        }
+    }
+
+    //Logs a game for the selected build, its counters get recounted from the log
+    pub fn record_match_result(&mut self, result: MatchResult) -> Result<(), String> {
+        let mut build = self.selected_army_build.clone();
+        if build.build_id.is_empty() {
+            return Err("Dis build needs reloading before it can track games".to_string());
+        }
+        let opponent_faction = if self.match_opponent_faction == ALL_FACTIONS {
+            String::new()
+        } else {
+            self.match_opponent_faction.clone()
+        };
+        let record = MatchRecord {
+            played_on: Utc::now(),
+            build_id: build.build_id.clone(),
+            opponent_faction,
+            opponent_name: self.match_opponent_name.trim().to_string(),
+            map: self.match_map.trim().to_string(),
+            result,
+        };
+        let counts = record_match(&self.selected_game, record)?;
+
+        counts.apply_to(&mut build);
+        if let Some(army_set) = self.army_builds.get_mut(&self.selected_game) {
+            army_set.replace(build.clone());
+        }
+        counts.apply_to(&mut self.edit_build);
+        self.selected_army_build = build.clone();
+        self.save_library_metadata(std::slice::from_ref(&build));
+        self.selected_matches = None;
+        self.update_display_builds();
+        Ok(())
+    }
+
+    fn refresh_selected_matches(&mut self) {
+        let build_id = self.selected_army_build.build_id.clone();
+        if let Some((id, _)) = self.selected_matches.as_ref() {
+            if *id == build_id {
+                return;
+            }
+        }
+        let records = match get_match_log_file(&self.selected_game)
+            .and_then(|f| load_match_log(f.as_path()))
+        {
+            Ok(log) => log
+                .get_build_records(build_id.as_str())
+                .into_iter()
+                .cloned()
+                .collect(),
+            Err(e) => {
+                self.match_error = e;
+                vec![]
+            }
+        };
+        self.selected_matches = Some((build_id, records));
+    }
+
+    fn match_log_ui(&mut self, ui: &mut Ui) {
+        let b = &self.selected_army_build;
+        ui.label(format!(
+            "{} W {} L {} D",
+            b.win_count, b.loss_count, b.draw_count
+        ));
+
+        if !get_faction_registry()
+            .get_game_factions(&self.selected_game)
+            .is_empty()
+        {
+            faction_dropdown_button(
+                ui,
+                &self.selected_game,
+                &mut self.match_opponent_faction,
+                "Opponent Faction",
+                true,
+            );
+        }
+        ui.horizontal(|ui| {
+            ui.label("Opponent");
+            ui.text_edit_singleline(&mut self.match_opponent_name);
+        });
+        ui.horizontal(|ui| {
+            ui.label("Map");
+            ui.text_edit_singleline(&mut self.match_map);
+        });
+
+        let mut result = None;
+        ui.horizontal(|ui| {
+            for r in [MatchResult::Win, MatchResult::Loss, MatchResult::Draw].iter() {
+                if ui
+                    .button(format!("Record {}", get_match_result_title(r)))
+                    .clicked()
+                {
+                    result = Some(*r);
+                }
+            }
+        });
+        if let Some(r) = result {
+            self.match_error = match self.record_match_result(r) {
+                Ok(_) => "".to_owned(),
+                Err(e) => e,
+            };
+        }
+        if !self.match_error.is_empty() {
+            ui.colored_label(Color32::RED, self.match_error.as_str());
+        }
+
+        self.refresh_selected_matches();
+        if let Some((_, records)) = self.selected_matches.as_ref() {
+            ScrollArea::from_max_height(150.0).show(ui, |ui| {
+                egui::Grid::new("match_log_grid").striped(true).show(ui, |ui| {
+                    for r in records.iter() {
+                        ui.label(format!("{}", r.played_on.format(YMD_HMS_FORMAT)));
+                        ui.label(get_match_result_title(&r.result));
+                        ui.label(r.opponent_faction.as_str());
+                        ui.label(r.opponent_name.as_str());
+                        ui.label(r.map.as_str());
+                        ui.end_row();
+                    }
+                });
+            });
+        }
+    }
+
+    fn get_replay_library(&mut self) -> Option<&mut ReplayLibrary> {
+        let is_stale = match self.replay_library.as_ref() {
+            Some(library) => library.ca_game != self.selected_game,
+            None => true,
+        };
+        if is_stale {
+            match ReplayLibrary::load(&self.selected_game) {
+                Ok(library) => self.replay_library = Some(library),
+                Err(e) => {
+                    self.replay_error = e;
+                    self.replay_library = None;
+                }
+            }
+        }
+        self.replay_library.as_mut()
+    }
+
+    //Replays linked to the selected build, plus every other replay to link it to
+    fn replays_ui(&mut self, ui: &mut Ui) {
+        let build_id = self.selected_army_build.build_id.clone();
+        let build_names: HashMap<String, String> = self
+            .get_game_army_builds(self.selected_game.clone())
+            .into_iter()
+            .map(|b| (b.build_id, b.file_stem))
+            .collect();
+        let mut link = None;
+        let library = match self.get_replay_library() {
+            Some(library) => library,
+            None => {
+                ui.colored_label(Color32::RED, self.replay_error.as_str());
+                return;
+            }
+        };
+        if build_id.is_empty() {
+            ui.label("Dis build needs reloading before replays can link to it");
+            return;
+        }
+
+        if ui.button("Rescan").clicked() {
+            library.rescan();
+        }
+        let linked = library.get_build_replays(build_id.as_str());
+        if linked.is_empty() {
+            ui.label("No replays linked");
+        }
+        egui::Grid::new("build_replays_grid").striped(true).show(ui, |ui| {
+            for r in linked {
+                ui.label(r.file_name.as_str());
+                ui.label(format!("{}", r.modified.format(YMD_HMS_FORMAT)));
+                ui.label(r.get_size_string());
+                if ui.small_button("Unlink").clicked() {
+                    link = Some((r.file_name.clone(), None));
+                }
+                ui.end_row();
+            }
+        });
+
+        egui::CollapsingHeader::new("Link a Replay")
+            .default_open(false)
+            .show(ui, |ui| {
+                if library.replays.is_empty() {
+                    ui.label("No replays found in the game's replays folder");
+                }
+                ScrollArea::from_max_height(200.0).show(ui, |ui| {
+                    egui::Grid::new("link_replays_grid").striped(true).show(ui, |ui| {
+                        for r in library.replays.iter() {
+                            let linked_to = library.links.get_replay_build_id(r.file_name.as_str());
+                            if linked_to == Some(build_id.as_str()) {
+                                continue;
+                            }
+                            ui.label(r.file_name.as_str());
+                            ui.label(format!("{}", r.modified.format(YMD_HMS_FORMAT)));
+                            ui.label(r.get_size_string());
+                            match linked_to.and_then(|id| build_names.get(id)) {
+                                Some(name) => ui.label(format!("with {}", name)),
+                                None => ui.label(""),
+                            };
+                            if ui.small_button("Link").clicked() {
+                                link = Some((r.file_name.clone(), Some(build_id.clone())));
+                            }
+                            ui.end_row();
+                        }
+                    });
+                });
+            });
+
+        if let Some((replay_file_name, build_id)) = link {
+            self.replay_error = match library.set_link(replay_file_name.as_str(), build_id.as_deref()) {
+                Ok(_) => "".to_owned(),
+                Err(e) => e,
+            };
+        }
+        if !self.replay_error.is_empty() {
+            ui.colored_label(Color32::RED, self.replay_error.as_str());
+        }
+    }
+
+    fn load_report_ui(&self, ui: &mut Ui) {
+        ScrollArea::from_max_height(200.0).show(ui, |ui| {
+            for r in self.load_report.iter() {
+                ui.horizontal(|ui| {
+                    ui.label(r.member.as_str());
+                    match &r.result {
+                        Ok(s) => ui.label(s.as_str()),
+                        Err(e) => ui.colored_label(Color32::YELLOW, e.as_str()),
+                    };
+                });
+            }
+        });
+    }
+
+    fn sync_ui(&mut self, ui: &mut Ui) {
+        ui.label("Matches the game's army_setups folder & the library by file name & content, Dry Run shows what Sync would do");
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_label("Changed Both Sides")
+                .selected_text(get_sync_conflict_policy_title(&self.sync_policy.on_conflict))
+                .show_ui(ui, |ui| {
+                    for p in SYNC_CONFLICT_POLICIES.iter() {
+                        ui.selectable_value(
+                            &mut self.sync_policy.on_conflict,
+                            *p,
+                            get_sync_conflict_policy_title(p),
+                        );
+                    }
+                });
+            ui.checkbox(&mut self.sync_policy.apply_deletes, "Apply Deletes");
+        });
+        ui.horizontal(|ui| {
+            for (title, apply) in [("Dry Run", false), ("Sync", true)].iter() {
+                if ui.button(*title).clicked() {
+                    self.sync_message = match self.sync_with_game_folder(*apply) {
+                        Ok(s) => s,
+                        Err(e) => e,
+                    };
+                }
+            }
+        });
+        if !self.sync_message.is_empty() {
+            ui.label(self.sync_message.as_str());
+        }
+        ScrollArea::from_max_height(200.0).show(ui, |ui| {
+            for r in self.sync_report.iter() {
+                let text = format!("{}", r);
+                if r.result.is_err() {
+                    ui.colored_label(Color32::YELLOW, text);
+                } else {
+                    ui.label(text);
+                }
+            }
+        });
+    }
+
+    fn backups_ui(&mut self, ui: &mut Ui) {
+        ui.label("Snapshots of the whole library, setups, metadata & match logs. One gets made before every sync & restore");
+        ui.horizontal(|ui| {
+            if ui.button("Snapshot Now").clicked() {
+                self.backup_message = match self.create_library_snapshot() {
+                    Ok(s) => s,
+                    Err(e) => e,
+                };
+            }
+            let mut retention = self.backup_retention;
+            ui.add(egui::DragValue::new(&mut retention).prefix("keep "));
+            self.set_backup_retention(retention);
+        });
+        if !self.backup_message.is_empty() {
+            ui.label(self.backup_message.as_str());
+        }
+
+        let snapshots = match self.get_library_snapshots() {
+            Ok(snapshots) => snapshots.to_vec(),
+            Err(e) => {
+                ui.colored_label(Color32::RED, e);
+                return;
+            }
+        };
+        let mut restore = None;
+        ScrollArea::from_max_height(300.0).show(ui, |ui| {
+            for snapshot in snapshots.iter() {
+                ui.horizontal(|ui| {
+                    if ui.small_button("Restore All").clicked() {
+                        restore = Some((snapshot.name.clone(), None));
+                    }
+                    egui::CollapsingHeader::new(snapshot.name.as_str())
+                        .default_open(false)
+                        .show(ui, |ui| {
+                            for f in snapshot.get_build_files(&self.selected_game) {
+                                ui.horizontal(|ui| {
+                                    if ui.small_button("Restore").clicked() {
+                                        restore = Some((snapshot.name.clone(), Some(f.clone())));
+                                    }
+                                    ui.label(f.as_str());
+                                });
+                            }
+                        });
+                });
+            }
+        });
+        if let Some((name, file)) = restore {
+            let res = match file {
+                Some(file) => self.restore_library_snapshot_build(name.as_str(), file.as_str()),
+                None => self.restore_library_snapshot(name.as_str()),
+            };
+            self.backup_message = match res {
+                Ok(s) => s,
+                Err(e) => e,
+            };
+        }
+    }
+
+    fn export_pack_ui(&mut self, ui: &mut Ui) {
+        ui.label(format!(
+            "Zips the {} builds the search is showing with their names, factions & notes, load it back in from Load Army Setups",
+            self.display_builds.len()
+        ));
+        ui.horizontal(|ui| {
+            ui.label("Pack File");
+            ui.text_edit_singleline(&mut self.pack_export_file);
+            if ui.button("Export").clicked() {
+                let file = PathBuf::from(self.pack_export_file.as_str());
+                self.pack_export_message = match self.export_display_builds_pack(file.as_path()) {
+                    Ok(n) => format!("Packed {} builds", n),
+                    Err(e) => e,
+                };
+            }
+        });
+        if !self.pack_export_message.is_empty() {
+            ui.label(self.pack_export_message.as_str());
+        }
+    }
+
+    fn share_code_ui(&mut self, ui: &mut Ui) {
+        let identity = self.selected_army_build.get_identity();
+        if ui.button("Make Code").clicked() {
+            self.build_code = match self.get_selected_build_code() {
+                Ok(code) => Some((identity.clone(), code)),
+                Err(e) => Some((identity.clone(), e)),
+            };
+        }
+        if let Some((code_identity, code)) = self.build_code.as_ref() {
+            if *code_identity == identity {
+                let mut shown = code.clone();
+                ui.text_edit_multiline(&mut shown);
+                if ui.button("Copy").clicked() {
+                    ui.output().copied_text = code.clone();
+                }
+            }
+        }
+    }
+
+    fn import_code_ui(&mut self, ui: &mut Ui) {
+        ui.label("Paste a build code from chat");
+        ui.text_edit_multiline(&mut self.import_code);
+        if ui.button("Import").clicked() {
+            let code = self.import_code.clone();
+            self.import_code_message = match self.import_build_code(code.as_str()) {
+                Ok(m) => {
+                    self.import_code.clear();
+                    m
+                }
+                Err(e) => e,
+            };
+        }
+        if !self.import_code_message.is_empty() {
+            ui.label(self.import_code_message.as_str());
+        }
+    }
+
+    fn duplicate_builds_ui(&mut self, ui: &mut Ui) {
+        let mut merge = None;
+        for (build_id, copies) in self.duplicate_builds.iter() {
+            ui.horizontal_wrapped(|ui| {
+                ui.label("Keep name");
+                for c in copies.iter() {
+                    if ui.button(c.file_stem.as_str()).clicked() {
+                        merge = Some((build_id.clone(), c.file_stem.clone()));
+                    }
+                }
+            });
+        }
+        if let Some((build_id, keep_name)) = merge {
+            if let Err(e) = self.merge_duplicate_builds(build_id.as_str(), keep_name.as_str()) {
+                println!("merge_duplicate_builds {}", e);
+            }
+        }
+    }
+
+    fn filename_templates_ui(&mut self, ui: &mut Ui) {
+        ui.label("How build names get read, used the next time builds load");
+        let mut remove = None;
+        for (i, template) in self.filename_templates.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(template);
+                if ui.small_button("🗙").clicked() {
+                    remove = Some(i);
+                }
+            });
+        }
+        if let Some(i) = remove {
+            self.filename_templates.remove(i);
+        }
+
+        let (templates, errors) = compile_filename_templates(&self.filename_templates);
+        for e in errors.iter() {
+            ui.colored_label(Color32::RED, e);
+        }
 
-        let mut added_or_merged_notification = String::new();
+        ui.horizontal(|ui| {
+            if ui.button("Add").clicked() {
+                self.filename_templates.push("{faction} vs {vs_faction}".to_string());
+            }
+            if ui.button("Defaults").clicked() {
+                self.filename_templates = DEFAULT_FILENAME_TEMPLATES
+                    .iter()
+                    .map(|t| t.to_string())
+                    .collect();
+            }
+            if ui.button("Save").clicked() && errors.is_empty() {
+                if let Err(e) = save_filename_templates(&self.filename_templates) {
+                    println!("save_filename_templates {}", e);
+                }
+            }
+        });
 
-        //Prepping army builds folder
-        let mut armies = load_army_builds(
-            self.insert_folder.folder_string.as_str(),
+        ui.horizontal(|ui| {
+            ui.label("Try a name");
+            ui.text_edit_singleline(&mut self.template_test_name);
+        });
+        let parsed = parse_filename(
             &self.selected_game,
+            self.template_test_name.as_str(),
+            &templates,
         );
-        let game_extension = format!(
-            ".{}",
-            get_ca_game_army_setup_ext(self.selected_game.clone())
-        );
-
-        //If loading from CA game folder, have that be dominant file naming system, will always match what you have there.
-        if self.load_folder.is_ca_game_folder() {
-            //copy over everything
-            for a in armies.iter_mut() {
-                let mut new_file_path: PathBuf = owaagh_appdata_path.clone();
+        ui.label(format!(
+            "Faction {}  vs {}  Funds {}  Author {}  ({:.0}% sure)",
+            parsed.faction.unwrap_or_default(),
+            parsed.vs_faction.unwrap_or_default(),
+            parsed.funds.map(|f| f.to_string()).unwrap_or_default(),
+            parsed.author.unwrap_or_default(),
+            parsed.confidence * 100.0
+        ));
+    }
 
-                new_file_path.push(format!("{}{}", a.file_stem, game_extension).as_str());
+    fn save_library_metadata(&self, builds: &[ArmyBuild]) {
+        ArmySetupsManager::save_game_library_metadata(&self.selected_game, builds);
+    }
 
-                match std::fs::copy(a.file.clone(), new_file_path.clone()) {
-                    Ok(_) => a.file = new_file_path,
-                    Err(e) => {
-                        let err = format!(
-                            "load_folder_to_owaagh_appdata Couldn't copy from {} to {} err {}",
-                            a.file.to_string_lossy().to_string(),
-                            new_file_path.to_string_lossy().to_string(),
-                            e
-                        );
-                        println!("{}", err);
-                    }
-                }
+    //Writes user metadata for the builds to the game's library index
+    fn save_game_library_metadata(ca_game: &CaGame, builds: &[ArmyBuild]) {
+        let res = update_library_index(ca_game, |index| {
+            for b in builds {
+                index.update_from(b);
             }
+        });
+        if let Err(e) = res {
+            println!("save_library_metadata {}", e);
+        }
+    }
 
-            let mut n_added = 0;
-            match self.army_builds.get_mut(&self.selected_game) {
-                None => {
-                    let army_set = HashSet::from_iter(armies.iter().cloned());
-                    n_added = army_set.len();
-                    self.army_builds
-                        .insert(self.selected_game.clone(), army_set);
+    //a new name moves the file, pushed as its own rename so undo puts it back like merge & normalize
+    fn rename_selected_build(&mut self, file_stem: &str) -> Result<ArmyBuild, String> {
+        let before = self.selected_army_build.clone();
+        let file_stem = sanitize_file_stem(file_stem);
+        if file_stem.is_empty() {
+            return Err("Dat build needs a name".to_string());
+        }
+        let mut renamed = before.clone();
+        let file_name = match before.file.extension() {
+            Some(ext) => format!("{}.{}", file_stem, ext.to_string_lossy()),
+            None => file_stem.clone(),
+        };
+        renamed.file.set_file_name(file_name);
+        renamed.file_stem = file_stem;
+        let same_file = renamed.file.to_string_lossy().to_lowercase() == before.file.to_string_lossy().to_lowercase();
+        if renamed.file.exists() && !same_file {
+            return Err(format!("Already got a build named {}", renamed.file_stem));
+        }
+        std::fs::rename(before.file.as_path(), renamed.file.as_path()).map_err(|e| e.to_string())?;
+        if let Some(army_set) = self.army_builds.get_mut(&self.selected_game) {
+            army_set.replace(renamed.clone());
+        }
+        self.undo_stack.push(UndoAction::Rename(vec![(before, renamed.clone())]));
+        self.selected_army_build = renamed.clone();
+        Ok(renamed)
+    }
+
+    fn apply_edits(&mut self) {
+        let mut edited = self.edit_build.clone();
+        self.edit_message = "".to_owned();
+        if edited.file_stem != self.selected_army_build.file_stem {
+            match self.rename_selected_build(edited.file_stem.as_str()) {
+                Ok(renamed) => {
+                    edited.file = renamed.file;
+                    edited.file_stem = renamed.file_stem;
                 }
-                Some(army_set) => {
-                    let n_before = army_set.len();
-                    for a in armies {
-                        army_set.insert(a); //hashset insert doesn't replace if exists
-                    }
-                    n_added = army_set.len() - n_before;
+                Err(e) => {
+                    self.edit_message = format!("Couldn't rename: {}", e);
+                    return;
                 }
             }
-            added_or_merged_notification = format!("{} Builds Added", n_added);
-            return Ok(added_or_merged_notification);
-        } else {
-            let game_army_builds = self.get_game_army_builds(self.selected_game.clone());
-            let n_before = game_army_builds.len();
+        }
+        self.undo_stack.push(UndoAction::MetadataEdit(Box::new((
+            self.selected_army_build.clone(),
+            edited.clone(),
+        ))));
+        if let Some(army_set) = self.army_builds.get_mut(&self.selected_game) {
+            army_set.remove(&self.selected_army_build);
+            army_set.insert(edited.clone());
+        }
+        self.save_library_metadata(std::slice::from_ref(&edited));
+        self.selected_army_build = edited;
+        self.update_display_builds();
+    }
 
-            //Copy & Rename loaded army builds
-            for a in armies.iter_mut() {
-                let mut new_file_path = owaagh_appdata_path.clone();
+    //What the build watcher read, only the files that changed & already parsed
+    pub fn apply_build_changes(&mut self, updates: Vec<BuildUpdate>) {
+        self.apply_game_build_changes(&self.selected_game.clone(), updates);
+    }
 
-                //Check unique & rename
-                if game_army_builds.contains(a) {
-                    match std::fs::metadata(a.file.as_path()) {
-                        Ok(m) => {
-                            let t = m.created().unwrap_or(std::time::SystemTime::now());
-                            let datetime: DateTime<Utc> = t.into();
-                            a.file_stem =
-                                format!("{} {}", a.file_stem, datetime.format(YMD_HMS_FORMAT));
-                        }
-                        Err(e) => {
-                            return Err(format!("Getting metadata err {}", e));
-                        }
+    fn apply_game_build_changes(&mut self, ca_game: &CaGame, updates: Vec<BuildUpdate>) {
+        if updates.is_empty() {
+            return;
+        }
+        let ca_game = ca_game.clone();
+        let mut game_army_builds = self.get_game_army_builds(ca_game.clone());
+        for update in updates {
+            match update {
+                //library first, the first of the same content stays
+                BuildUpdate::Reloaded(builds) => game_army_builds = builds.into_iter().collect(),
+                BuildUpdate::Upserted(build) => {
+                    game_army_builds.retain(|b| b.file != build.file);
+                    if self.selected_army_build.file == build.file {
+                        self.selected_army_build = build.clone();
                     }
-                    new_file_path.push(a.file_stem.as_str());
-                    new_file_path.push(game_extension.as_str());
+                    //same content under another file keeps the one already there
+                    game_army_builds.insert(build);
                 }
-
-                //copy and add in new army builds
-                match std::fs::copy(a.file.clone(), new_file_path.clone()) {
-                    Ok(_) => a.file = new_file_path,
-                    Err(e) => {
-                        let err = format!("{} for file_stem {}", e, a.file_stem);
-                        return Err(err);
+                BuildUpdate::Removed { file, same_content } => {
+                    game_army_builds.retain(|b| b.file != file);
+                    if let Some(b) = same_content {
+                        game_army_builds.insert(b);
                     }
                 }
             }
+        }
+        self.army_builds.insert(ca_game, game_army_builds);
+        self.update_display_builds();
+    }
+
+    //Library first so its copies win over the game's folder for the same content
+    fn get_game_folders_builds(ca_game: &CaGame) -> HashSet<ArmyBuild> {
+        let mut builds = HashSet::new();
+        let folders = vec![
+            get_owaagh_army_setups_dir(ca_game),
+            get_ca_game_army_setups_folder(ca_game.clone()),
+        ];
+        for folder in folders.into_iter().flatten() {
+            builds.extend(load_army_builds(folder.to_string_lossy().as_ref(), ca_game));
+        }
+        builds
+    }
 
-            let n_added = game_army_builds.len() - n_before;
-            added_or_merged_notification = format!("{} Builds Added", n_added);
-            self.army_builds
-                .insert(self.selected_game.clone(), game_army_builds);
-            //self.army_builds = armies;
-            return Ok(added_or_merged_notification);
+    //Search box query & faction dropdowns, plus the advanced panel when it's on
+    fn get_search_filters(&self) -> Result<Vec<SearchFilter>, SearchQueryError> {
+        let mut filters = vec![];
+        let is_any = |name: &String| name.is_empty() || name == ALL_FACTIONS;
+        if !is_any(&self.search_faction_name) {
+            filters.push(SearchFilter::Faction(self.search_faction_name.clone()));
         }
+        if !is_any(&self.search_vs_faction_name) {
+            filters.push(SearchFilter::VsFaction(self.search_vs_faction_name.clone()));
+        }
+        filters.extend(parse_search_query(self.search_string.as_str(), &self.selected_game)?);
+        if self.advanced_search.enabled {
+            filters.extend(self.advanced_search.get_filters());
+        }
+        Ok(filters)
     }
 
     pub fn update_display_builds(&mut self) {
-        let game_builds = self.get_game_army_builds(self.selected_game.clone());
-        //check factions first
-        let mut display_builds: Vec<ArmyBuild> = if (self.selected_game == CaGame::Warhammer2) {
-            //only implimented faction & vs faction enums for wh2
-            game_builds
-                .iter()
-                .filter(|ab| {
-                    (self.search_faction == Wh2Factions::ALL || ab.faction == self.search_faction)
-                        && (self.search_vs_faction == Wh2Factions::ALL
-                            || ab.vs_faction == self.search_vs_faction)
-                })
-                .cloned()
-                .collect()
-        } else {
-            game_builds.into_iter().collect()
+        //a bad query leaves the last results up
+        let filters = match self.get_search_filters() {
+            Ok(filters) => filters,
+            Err(e) => {
+                self.search_error = format!("{}", e);
+                return;
+            }
         };
-
-        //now do string manipulation, slower so on fewer
-        let lower_case_search = self.search_string.to_ascii_lowercase();
-        display_builds = display_builds
-            .iter()
-            .filter(|ab| {
-                ab.file_stem
-                    .to_ascii_lowercase()
-                    .contains(lower_case_search.as_str())
-            })
-            .cloned()
+        self.search_error.clear();
+        let mut scored_builds: Vec<(f64, ArmyBuild)> = self
+            .get_game_army_builds(self.selected_game.clone())
+            .into_iter()
+            .filter_map(|ab| get_search_score(&filters, &ab).map(|score| (score, ab)))
             .collect();
 
-        display_builds.sort();
+        scored_builds.sort_by(|a, b| a.1.cmp(&b.1));
+        //most relevant first when there's a name to rank by, stable so ties stay by name
+        if has_name_query(&filters) {
+            scored_builds.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
+        }
+        let mut display_builds: Vec<ArmyBuild> =
+            scored_builds.into_iter().map(|(_, ab)| ab).collect();
+        //a picked column wins over relevance
+        self.table_layout.sort_army_builds(&mut display_builds);
         self.display_builds = display_builds;
     }
 
+    //Same as typing in the search box, the results land in the display builds
+    pub fn set_search_query(&mut self, query: &str) -> Result<(), String> {
+        self.search_string = query.to_string();
+        self.update_display_builds();
+        if self.search_error.is_empty() {
+            Ok(())
+        } else {
+            Err(self.search_error.clone())
+        }
+    }
+
+    pub fn get_load_report(&self) -> &[ArchiveEntryReport] {
+        self.load_report.as_slice()
+    }
+
+    pub fn get_display_builds(&self) -> &[ArmyBuild] {
+        self.display_builds.as_slice()
+    }
+
+    pub fn get_selected_army_build(&self) -> &ArmyBuild {
+        &self.selected_army_build
+    }
+
+    //By display name ignoring case, or by the start of its build id
+    pub fn select_army_build(&mut self, name_or_id: &str) -> Result<(), String> {
+        let builds = self.get_game_army_builds(self.selected_game.clone());
+        let build = builds
+            .iter()
+            .find(|b| b.file_stem.eq_ignore_ascii_case(name_or_id))
+            .or_else(|| {
+                let mut by_id = builds
+                    .iter()
+                    .filter(|b| !name_or_id.is_empty() && b.build_id.starts_with(name_or_id));
+                match (by_id.next(), by_id.next()) {
+                    (Some(b), None) => Some(b),
+                    _ => None,
+                }
+            });
+        match build {
+            Some(b) => {
+                self.selected_army_build = b.clone();
+                self.edit_build = b.clone();
+                Ok(())
+            }
+            None => Err(format!("No build named or with id \'{}\'", name_or_id)),
+        }
+    }
+
+    pub fn set_insert_name(&mut self, insert_name: &str) {
+        self.insert_name = insert_name.to_string();
+    }
+
+    pub fn set_load_folder(&mut self, folder: &str) {
+        self.load_folder = ArmySetupsFolder::new(folder);
+    }
+
+    pub fn set_insert_folder(&mut self, folder: &str) {
+        self.insert_folder = ArmySetupsFolder::new(folder);
+    }
+
+    pub fn export_display_builds_pack(&self, file: &Path) -> Result<usize, String> {
+        export_build_pack(self.display_builds.as_slice(), file)
+    }
+
+    //Copies the displayed builds' setup files out as they're named in the library
+    pub fn export_display_builds(&self, folder: &Path) -> Result<Vec<PathBuf>, String> {
+        if let Err(e) = std::fs::create_dir_all(folder) {
+            return Err(format!("Couldn't make {} {}", folder.to_string_lossy(), e));
+        }
+        let mut exported = vec![];
+        for b in self.display_builds.iter() {
+            let file_name = match b.file.file_name() {
+                Some(n) => n,
+                None => continue,
+            };
+            let to = folder.join(file_name);
+            if let Err(e) = std::fs::copy(b.file.as_path(), to.as_path()) {
+                return Err(format!(
+                    "Couldn't copy {} to {} {}",
+                    b.file.to_string_lossy(),
+                    to.to_string_lossy(),
+                    e
+                ));
+            }
+            exported.push(to);
+        }
+        Ok(exported)
+    }
+
     pub fn update_load_folder(&mut self) {
         let f_string: String;
         match get_ca_game_army_setups_folder(self.selected_game.clone()) {
@@ -407,6 +1875,19 @@ impl ArmySetupsManager {
     }
 
     pub fn selected_game_update(&mut self) {
+        //faction picks don't carry over to a game that doesn't have them
+        let factions = get_faction_registry();
+        for faction_name in [
+            &mut self.search_faction_name,
+            &mut self.search_vs_faction_name,
+        ] {
+            if factions
+                .find_faction(&self.selected_game, faction_name.as_str())
+                .is_none()
+            {
+                *faction_name = ALL_FACTIONS.to_string();
+            }
+        }
         self.update_load_folder();
         match self.load_folder_to_owaagh_appdata() {
             Ok(_) => {}
@@ -425,6 +1906,7 @@ impl ArmySetupsManager {
         scroll_bottom: bool,
     ) {
         let scroll_area = ScrollArea::from_max_height(200.0);
+        let mut sort_changed = false;
 
         ui.separator();
         let (_current_scroll, _max_scroll) = scroll_area.show(ui, |ui| {
@@ -437,12 +1919,14 @@ impl ArmySetupsManager {
                 .min_col_width(1.0)
                 .max_col_width(200.0)
                 .show(ui, |ui| {
-                    show_army_build_header_row(ui);
+                    sort_changed = show_army_build_header_row(ui, &mut self.table_layout);
                     ui.end_row();
+                    let display_cols = self.table_layout.get_visible_columns();
                     for (row, display_build) in self.display_builds.iter_mut().enumerate() {
                         let selected = display_build.show_selectable_army_build_row(
                             ui,
                             self.selected_army_build == *display_build,
+                            &display_cols,
                         );
 
                         if selected {
@@ -464,9 +1948,27 @@ impl ArmySetupsManager {
             let max_scroll = ui.min_rect().height() - ui.clip_rect().height() + 2.0 * margin;
             (current_scroll, max_scroll)
         });
+        if sort_changed {
+            self.update_display_builds();
+        }
     }
 
-    fn army_selector_search_section_ui(&mut self, ui: &mut Ui, ctx: &egui::CtxRef) {}
+    fn army_selector_search_section_ui(&mut self, ui: &mut Ui) {
+        egui::CollapsingHeader::new("Advanced")
+            .default_open(false)
+            .show(ui, |ui| {
+                if self.advanced_search.advanced_search_ui(ui) {
+                    self.update_display_builds();
+                }
+            });
+        egui::CollapsingHeader::new("Columns")
+            .default_open(false)
+            .show(ui, |ui| {
+                if self.table_layout.columns_ui(ui) {
+                    self.update_display_builds();
+                }
+            });
+    }
 
     pub(crate) fn army_selector_scrolling_ui(&mut self, ui: &mut Ui, ctx: &egui::CtxRef) {
         if self.army_builds.is_empty() {
@@ -480,31 +1982,47 @@ impl ArmySetupsManager {
             }
             if ui
                 .text_edit_singleline(&mut self.search_string)
+                .on_hover_text(SEARCH_QUERY_HELP)
                 .lost_focus()
                 && ctx.input().key_pressed(egui::Key::Enter)
             {
                 self.update_display_builds();
             }
 
-            if self.selected_game == CaGame::Warhammer2 {
+            if !get_faction_registry()
+                .get_game_factions(&self.selected_game)
+                .is_empty()
+            {
                 //ui.add(doc_link_label("Combo box", "faction_search"));
-                let prior_selected_faction = self.search_faction.clone();
-                let faction_btn_response =
-                    faction_dropdown_button(ui, &mut self.search_faction, "Faction", false);
-                if faction_btn_response.clicked() && prior_selected_faction != self.search_faction {
+                let prior_selected_faction = self.search_faction_name.clone();
+                faction_dropdown_button(
+                    ui,
+                    &self.selected_game,
+                    &mut self.search_faction_name,
+                    "Faction",
+                    false,
+                );
+                if prior_selected_faction != self.search_faction_name {
                     self.update_display_builds();
                 }
 
-                let prior_selected_faction = self.search_vs_faction.clone();
-                let vs_faction_btn_response =
-                    faction_dropdown_button(ui, &mut self.search_vs_faction, "vs Faction", true);
-                if vs_faction_btn_response.clicked()
-                    && prior_selected_faction != self.search_vs_faction
-                {
+                let prior_selected_faction = self.search_vs_faction_name.clone();
+                faction_dropdown_button(
+                    ui,
+                    &self.selected_game,
+                    &mut self.search_vs_faction_name,
+                    "vs Faction",
+                    true,
+                );
+                if prior_selected_faction != self.search_vs_faction_name {
                     self.update_display_builds();
                 }
             }
         });
+        if !self.search_error.is_empty() {
+            ui.colored_label(Color32::RED, self.search_error.as_str());
+        }
+        self.army_selector_search_section_ui(ui);
 
         let mut scroll_top = false;
         let mut scroll_bottom = false;
@@ -514,18 +2032,122 @@ impl ArmySetupsManager {
         ui.horizontal(|ui| {
             scroll_top |= ui.button("⬆").clicked();
             scroll_bottom |= ui.button("⬇").clicked();
-            // if ui.button("🚫").clicked() {
-            //     println!("To do delete pop");
-            // }
-            // if ui.button("🗋").clicked() {
-            //     println!("To do delete pop");
-            // }
+            if ui
+                .add(
+                    egui::Button::new("🚫")
+                        .enabled(!self.selected_army_build.file.as_os_str().is_empty()),
+                )
+                .on_hover_text("Delete the selected build")
+                .clicked()
+            {
+                self.pending_confirm =
+                    Some(PendingConfirm::Delete(Box::new(self.selected_army_build.clone())));
+            }
+            if let Some(title) = self.get_undo_title() {
+                if ui.button(format!("Undo {}", title)).clicked() {
+                    self.delete_message = match self.undo() {
+                        Ok(s) => s,
+                        Err(e) => e,
+                    };
+                }
+            }
         });
 
+        if !self.delete_message.is_empty() {
+            ui.label(self.delete_message.as_str());
+        }
+
         self.army_selector_scrolling_table(ui, scroll_top, scroll_bottom);
         ui.separator();
     }
 
+    //Stays up over everything till it gets a yes or a no
+    fn confirm_window_ui(&mut self, ctx: &egui::CtxRef) {
+        let (title, question) = match self.pending_confirm.as_ref() {
+            Some(PendingConfirm::Delete(build)) => (
+                "Delete Build",
+                format!(
+                    "Put {} in the trash? You can get it back from Trash or Undo",
+                    build.file_stem
+                ),
+            ),
+            Some(PendingConfirm::EmptyTrash) => (
+                "Empty Trash",
+                "Delete everything in the trash for good? There's no getting it back".to_string(),
+            ),
+            None => return,
+        };
+        let mut answer = None;
+        egui::Window::new(title)
+            .collapsible(false)
+            .resizable(false)
+            .default_pos(ctx.input().screen_rect.center())
+            .show(ctx, |ui| {
+                ui.label(question);
+                ui.horizontal(|ui| {
+                    if ui.button("Yes").clicked() {
+                        answer = Some(true);
+                    }
+                    if ui.button("No").clicked() {
+                        answer = Some(false);
+                    }
+                });
+            });
+        let confirmed = match answer {
+            Some(confirmed) => confirmed,
+            None => return,
+        };
+        let res = match self.pending_confirm.take() {
+            Some(PendingConfirm::Delete(build)) if confirmed => self.delete_army_build(&build),
+            Some(PendingConfirm::EmptyTrash) if confirmed => self.empty_build_trash(),
+            _ => return,
+        };
+        self.delete_message = match res {
+            Ok(s) => s,
+            Err(e) => e,
+        };
+    }
+
+    fn trash_ui(&mut self, ui: &mut Ui) {
+        let trash = match self.get_trash() {
+            Ok(trash) => trash.to_vec(),
+            Err(e) => {
+                ui.colored_label(Color32::RED, e);
+                return;
+            }
+        };
+        if trash.is_empty() {
+            ui.label("Nothing in the trash");
+            return;
+        }
+        if ui.button("Empty Trash").clicked() {
+            self.pending_confirm = Some(PendingConfirm::EmptyTrash);
+        }
+        let mut restore = None;
+        ScrollArea::from_max_height(200.0).show(ui, |ui| {
+            egui::Grid::new("trash_grid").striped(true).show(ui, |ui| {
+                for entry in trash.iter() {
+                    if ui.small_button("Restore").clicked() {
+                        restore = Some(entry.trash_file.clone());
+                    }
+                    ui.label(entry.file_stem.as_str());
+                    ui.label(get_ca_game_title(&entry.ca_game));
+                    ui.label(format!("{}", entry.deleted_on.format(YMD_HMS_FORMAT)));
+                    ui.end_row();
+                }
+            });
+        });
+        if let Some(trash_file) = restore {
+            self.delete_message = match self.restore_trashed_build(trash_file.as_str()) {
+                Ok(s) => s,
+                Err(e) => e,
+            };
+        }
+        if !self.delete_message.is_empty() {
+            ui.label(self.delete_message.as_str());
+        }
+    }
+
     pub fn insert_army(&self) -> Result<(), String> {
         //Check If Inputs Valid
         if !self.insert_folder.is_ca_game_folder() {
@@ -545,11 +2167,43 @@ impl ArmySetupsManager {
 
         let insert_file =
             self.insert_folder.folder_string.clone() + "/" + insert_name.as_str() + ".army_setup";
-        match std::fs::copy(selected_file, insert_file.as_str()) {
-            Ok(_) => Ok(()),
+
+        //Write out the edited build, unreadable setups still go in as a plain copy
+        match read_army_setup_file(self.selected_army_build.file.as_path()) {
+            Ok(mut setup) => {
+                self.apply_build_edits_to_setup(&mut setup);
+                match write_army_setup_file(Path::new(insert_file.as_str()), &setup) {
+                    Ok(()) => Ok(()),
+                    Err(e) => Err(format!("{}", e)),
+                }
+            }
             Err(e) => {
-                println!("{}", e);
-                Err("Couldn't copy".to_string())
+                println!("{} inserting as copy", e);
+                match std::fs::copy(selected_file, insert_file.as_str()) {
+                    Ok(_) => Ok(()),
+                    Err(e) => {
+                        println!("{}", e);
+                        Err("Couldn't copy".to_string())
+                    }
+                }
+            }
+        }
+    }
+
+    fn apply_build_edits_to_setup(&self, setup: &mut ArmySetup) {
+        let build = &self.selected_army_build;
+        let funds_level = get_funds_level(build.funds, &build.ca_game);
+        if let Some(funds_level_indx) = get_army_setup_funds_level_index(&funds_level) {
+            setup.set_funds_level(funds_level_indx);
+        }
+        //only retarget when the build was moved to a different faction than the file has
+        let factions = get_faction_registry();
+        if let Some(f) = factions.find_faction(&build.ca_game, build.faction_str.as_str()) {
+            let current = factions.find_faction_by_key(&build.ca_game, setup.faction_key.as_str());
+            if current.map(|c| c.id != f.id).unwrap_or(true) {
+                if let Some(faction_key) = f.faction_key.as_ref() {
+                    setup.set_faction_key(faction_key);
+                }
             }
         }
     }
@@ -592,26 +2246,32 @@ impl ArmySetupsManager {
     fn army_card_image_file_select_ui_row(&mut self, ui: &mut Ui, ctx: &egui::CtxRef) {
         ui.horizontal(|ui| {
             if ui.button("Add Army Image File").clicked() {
-                let params = DialogParams {
-                    default_folder: self.insert_folder.folder_string.as_str(),
-                    options: FOS_PICKFOLDERS,
-                    ..Default::default()
-                };
-
-                match wfd::open_dialog(params) {
-                    Ok(res) => {
-                        self.insert_folder.folder_string =
-                            res.selected_file_path.to_string_lossy().to_string();
-                        self.insert_folder.set_insert_folder_error();
-                    }
-                    Err(e) => {
-                        println!("load folder dialog e {:?}", e);
-                    }
+                if let Some(folder) = pick_folder_dialog(self.insert_folder.folder_string.as_str()) {
+                    self.insert_folder.folder_string = folder;
+                    self.insert_folder.set_insert_folder_error();
                 }
             }
         });
     }
 
+    //Lists every army setups folder discovery came up with, returns the one the user picked
+    fn discovered_folders_ui(&self, ui: &mut Ui, title: &str) -> Option<String> {
+        let mut picked = None;
+        egui::CollapsingHeader::new(title)
+            .default_open(false)
+            .show(ui, |ui| {
+                for candidate in get_ca_game_army_setups_candidates(&self.selected_game) {
+                    if ui
+                        .selectable_label(false, candidate.get_display_string())
+                        .clicked()
+                    {
+                        picked = Some(candidate.folder.to_string_lossy().to_string());
+                    }
+                }
+            });
+        picked
+    }
+
     pub fn edit_section_ui(&mut self, ui: &mut Ui, ctx: &egui::CtxRef) {
         let mut has_errors = false;
 
@@ -626,7 +2286,11 @@ impl ArmySetupsManager {
                                 .army_builds
                                 .get(&self.selected_game)
                                 .expect("edit_section_ui didn't have builds for game")
-                                .contains(&self.edit_build)
+                                .iter()
+                                .any(|b| {
+                                    b.file_stem == self.edit_build.file_stem
+                                        && *b != self.edit_build
+                                })
                             {
                                 ui.label("That name already exists, pick another");
                                 has_errors = true;
@@ -634,19 +2298,34 @@ impl ArmySetupsManager {
                         }
                     });
                 }
-                ArmyBuildDisplayColumns::Faction => {
-                    ArmySetupsManager::edit_section_errorless_str_edit(
-                        ui,
-                        get_army_build_display_column_title(&display_col),
-                        &mut self.edit_build.faction_str,
-                    )
-                }
-                ArmyBuildDisplayColumns::VsFaction => {
-                    ArmySetupsManager::edit_section_errorless_str_edit(
-                        ui,
-                        get_army_build_display_column_title(&display_col),
-                        &mut self.edit_build.vs_faction_str,
-                    )
+                ArmyBuildDisplayColumns::Faction | ArmyBuildDisplayColumns::VsFaction => {
+                    let is_vs = display_col == ArmyBuildDisplayColumns::VsFaction;
+                    let title = get_army_build_display_column_title(&display_col);
+                    let faction_str = if is_vs {
+                        &mut self.edit_build.vs_faction_str
+                    } else {
+                        &mut self.edit_build.faction_str
+                    };
+                    if get_faction_registry()
+                        .get_game_factions(&self.edit_build.ca_game)
+                        .is_empty()
+                    {
+                        ArmySetupsManager::edit_section_errorless_str_edit(ui, title, faction_str)
+                    } else {
+                        faction_dropdown_button(
+                            ui,
+                            &self.edit_build.ca_game,
+                            faction_str,
+                            title.as_str(),
+                            is_vs,
+                        );
+                    }
+                    if self.edit_build.ca_game == CaGame::Warhammer2 {
+                        self.edit_build.faction =
+                            get_wh2_faction_from_name(self.edit_build.faction_str.as_str());
+                        self.edit_build.vs_faction =
+                            get_wh2_faction_from_name(self.edit_build.vs_faction_str.as_str());
+                    }
                 }
                 ArmyBuildDisplayColumns::Funds => {
                     funds_slider_ui(&mut self.edit_build, ui);
@@ -677,12 +2356,17 @@ impl ArmySetupsManager {
         //let mut file
         if !has_errors {
             if ui.button("Apply Edits").clicked() {
-                self.selected_army_build = self.edit_build.clone();
+                self.apply_edits();
             }
         }
+        if !self.edit_message.is_empty() {
+            ui.label(self.edit_message.as_str());
+        }
     }
 
     pub fn central_panel_ui(&mut self, ui: &mut Ui, ctx: &egui::CtxRef) {
+        self.confirm_window_ui(ctx);
+
         egui::CollapsingHeader::new("Load Army Setups")
             .default_open(self.load_folder.is_load_folder())
             .show(ui, |ui| {
@@ -709,26 +2393,28 @@ impl ArmySetupsManager {
                             }
                         }
                     }
-                    if ui.button("...").clicked() {
-                        let params = DialogParams {
-                            default_folder: self.load_folder.folder_string.as_str(),
-                            options: FOS_PICKFOLDERS,
-                            ..Default::default()
-                        };
-
-                        match wfd::open_dialog(params) {
-                            Ok(res) => {
-                                self.load_folder.folder_string =
-                                    res.selected_file_path.to_string_lossy().to_string();
-                                self.load_folder.set_load_folder_error();
-                            }
-                            Err(e) => {
-                                println!("load folder dialog e {:?}", e);
-                            }
+                    if cfg!(windows) && ui.button("...").clicked() {
+                        if let Some(folder) =
+                            pick_folder_dialog(self.load_folder.folder_string.as_str())
+                        {
+                            self.load_folder.folder_string = folder;
+                            self.load_folder.set_load_folder_error();
                         }
                     }
                 });
 
+                if let Some(folder) = self.discovered_folders_ui(ui, "Found Game Folders") {
+                    self.load_folder = ArmySetupsFolder::new(folder.as_str());
+                    self.load_folder.ca_game = self.selected_game.clone();
+                    self.load_folder.set_load_folder_error();
+                }
+
+                egui::CollapsingHeader::new("File Name Templates")
+                    .default_open(false)
+                    .show(ui, |ui| {
+                        self.filename_templates_ui(ui);
+                    });
+
                 if !self.load_folder.folder_error.is_empty() {
                     ui.label(self.load_folder.folder_error.clone());
 
@@ -737,23 +2423,95 @@ impl ArmySetupsManager {
                         .show(ui, |ui| {
                             ui.label("This can be any folder with a \'.army_setup\' file ex:");
                             ui.label("C:\\Users\\DaBiggestBoss\\Downloads\\ArmySetups");
+                            ui.label("Or a .zip, .7z or .rar build pack ex:");
+                            ui.label("C:\\Users\\DaBiggestBoss\\Downloads\\Felkon.rar");
                         });
                 }
+
+                egui::CollapsingHeader::new("Import from Code")
+                    .default_open(false)
+                    .show(ui, |ui| {
+                        self.import_code_ui(ui);
+                    });
+
+                if !self.load_report.is_empty() {
+                    egui::CollapsingHeader::new("Last Build Pack")
+                        .default_open(false)
+                        .show(ui, |ui| {
+                            self.load_report_ui(ui);
+                        });
+                }
+            });
+
+        egui::CollapsingHeader::new("Sync With Game")
+            .default_open(false)
+            .show(ui, |ui| {
+                self.sync_ui(ui);
+            });
+
+        egui::CollapsingHeader::new("Backups")
+            .default_open(false)
+            .show(ui, |ui| {
+                self.backups_ui(ui);
+            });
+
+        egui::CollapsingHeader::new("Trash")
+            .default_open(false)
+            .show(ui, |ui| {
+                self.trash_ui(ui);
+            });
+
+        egui::CollapsingHeader::new("Normalize Names")
+            .default_open(false)
+            .show(ui, |ui| {
+                self.normalize_names_ui(ui);
             });
 
+        if !self.duplicate_builds.is_empty() {
+            egui::CollapsingHeader::new("Same Build, Different Names")
+                .default_open(false)
+                .show(ui, |ui| {
+                    self.duplicate_builds_ui(ui);
+                });
+        }
+
         egui::CollapsingHeader::new("Select Army Setup")
             .default_open(false)
             .show(ui, |ui| {
                 self.army_selector_scrolling_ui(ui, ctx);
             });
 
+        egui::CollapsingHeader::new("Export Pack")
+            .default_open(false)
+            .show(ui, |ui| {
+                self.export_pack_ui(ui);
+            });
+
         if self.selected_army_build.file_stem.len() > 0 {
             //file stem is required so
-            // egui::CollapsingHeader::new(format!("Edit {}", self.selected_army_build.file_stem))
-            //     .default_open(false)
-            //     .show(ui, |ui| {
-            //         self.edit_section_ui(ui, ctx);
-            //     });
+            egui::CollapsingHeader::new(format!("Edit {}", self.selected_army_build.file_stem))
+                .default_open(false)
+                .show(ui, |ui| {
+                    self.edit_section_ui(ui, ctx);
+                });
+
+            egui::CollapsingHeader::new(format!("Games {}", self.selected_army_build.file_stem))
+                .default_open(false)
+                .show(ui, |ui| {
+                    self.match_log_ui(ui);
+                });
+
+            egui::CollapsingHeader::new(format!("Replays {}", self.selected_army_build.file_stem))
+                .default_open(false)
+                .show(ui, |ui| {
+                    self.replays_ui(ui);
+                });
+
+            egui::CollapsingHeader::new(format!("Share {}", self.selected_army_build.file_stem))
+                .default_open(false)
+                .show(ui, |ui| {
+                    self.share_code_ui(ui);
+                });
 
             egui::CollapsingHeader::new(format!("Insert {}", self.selected_army_build.file_stem))
                 .default_open(self.insert_folder.is_ca_game_folder())
@@ -771,26 +2529,22 @@ impl ArmySetupsManager {
                             self.insert_folder.set_insert_folder_error();
                         }
 
-                        if ui.button("...").clicked() {
-                            let params = DialogParams {
-                                default_folder: self.insert_folder.folder_string.as_str(),
-                                options: FOS_PICKFOLDERS,
-                                ..Default::default()
-                            };
-
-                            match wfd::open_dialog(params) {
-                                Ok(res) => {
-                                    self.insert_folder.folder_string =
-                                        res.selected_file_path.to_string_lossy().to_string();
-                                    self.insert_folder.set_insert_folder_error();
-                                }
-                                Err(e) => {
-                                    println!("load folder dialog e {:?}", e);
-                                }
+                        if cfg!(windows) && ui.button("...").clicked() {
+                            if let Some(folder) =
+                                pick_folder_dialog(self.insert_folder.folder_string.as_str())
+                            {
+                                self.insert_folder.folder_string = folder;
+                                self.insert_folder.set_insert_folder_error();
                             }
                         }
                     });
 
+                    if let Some(folder) = self.discovered_folders_ui(ui, "Found Game Folders ") {
+                        self.insert_folder = ArmySetupsFolder::new(folder.as_str());
+                        self.insert_folder.ca_game = self.selected_game.clone();
+                        self.insert_folder.set_insert_folder_error();
+                    }
+
                     if !self.insert_folder.folder_error.is_empty() {
                         ui.label(self.insert_folder.folder_error.clone());
 
@@ -0,0 +1,419 @@
+#![forbid(unsafe_code)]
+
+// Headless access to the WarbossWaaghit library, for scripts & machines without a display
+use serde_json::json;
+use std::path::Path;
+use warboss_waaghit_lib::army_build::ArmyBuild;
+use warboss_waaghit_lib::army_setups_manager::ArmySetupsManager;
+use warboss_waaghit_lib::build_sync::{SyncConflictPolicy, SyncPolicy};
+use warboss_waaghit_lib::ca_game::{
+    get_ca_game_army_setup_ext, get_ca_game_from_name, get_ca_game_title, CaGame,
+};
+use warboss_waaghit_lib::match_log::{get_match_log_file, load_match_log, MatchCounts};
+use warboss_waaghit_lib::match_stats::get_match_stats;
+use warboss_waaghit_lib::search_query::SEARCH_QUERY_HELP;
+
+const USAGE: &str = "waaghit-cli [--game <game>] [--json] <command>
+
+Commands
+  list                          every build in the library
+  search <query>                builds matching a search box query
+  import <folder|pack>          copy a folder's setups, or a .zip/.7z/.rar pack's, into the library
+                                a .rar needs unrar or 7-Zip installed
+  insert <build> --as <name>    put a library build, by name or id, in the game's army_setups
+         [--to <folder>]        or another army_setups folder
+  export <folder> [query]       copy library setups, or the ones matching query, out to folder
+  export <pack.zip> [query]     or zip them with a manifest of their metadata, import takes it back
+  code <build>                  a build code to paste in chat
+  import-code <code>            add the build from a code to the library
+  sync [--dry-run]              two way sync of the game's army_setups & the library
+       [--conflicts <policy>]   for builds changed on both sides, game, library, both or skip
+       [--deletes]              trash on one side what was deleted on the other
+  backup                        snapshot the whole library, setups, metadata & match logs
+  backups                       list the snapshots, newest first
+  restore <snapshot> [file]     put a snapshot, or latest, back, or just one of its setups
+  delete <build>                move a library build to the trash
+  trash                         what's in the trash, newest first
+  untrash <file>                put a build from the trash back where it was
+  empty-trash                   delete everything in the trash for good
+  stats                         wins, losses & draws by build and faction
+
+--game takes the folder name or title, e.g. Warhammer2 or \"Warhammer 2\", defaults to Warhammer2";
+
+struct CliArgs {
+    ca_game: CaGame,
+    json: bool,
+    command: String,
+    positional: Vec<String>,
+    insert_as: Option<String>,
+    insert_to: Option<String>,
+    dry_run: bool,
+    sync_policy: SyncPolicy,
+}
+
+fn parse_args(args: Vec<String>) -> Result<CliArgs, String> {
+    let mut ca_game = CaGame::Warhammer2;
+    let mut json = false;
+    let mut insert_as = None;
+    let mut insert_to = None;
+    let mut dry_run = false;
+    let mut sync_policy = SyncPolicy::default();
+    let mut rest = vec![];
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = |flag: &str| args.next().ok_or_else(|| format!("{} needs a value", flag));
+        match arg.as_str() {
+            "--json" => json = true,
+            "--game" => {
+                let name = value("--game")?;
+                ca_game = get_ca_game_from_name(name.as_str())
+                    .ok_or_else(|| format!("Don't know the game \'{}\'", name))?;
+            }
+            "--as" => insert_as = Some(value("--as")?),
+            "--to" => insert_to = Some(value("--to")?),
+            "--dry-run" => dry_run = true,
+            "--deletes" => sync_policy.apply_deletes = true,
+            "--conflicts" => {
+                sync_policy.on_conflict = match value("--conflicts")?.as_str() {
+                    "game" => SyncConflictPolicy::GameWins,
+                    "library" => SyncConflictPolicy::LibraryWins,
+                    "both" => SyncConflictPolicy::KeepBoth,
+                    "skip" => SyncConflictPolicy::Skip,
+                    p => return Err(format!("Unknown conflict policy {}\n\n{}", p, USAGE)),
+                }
+            }
+            _ if arg.starts_with("--") => {
+                return Err(format!("Unknown option {}\n\n{}", arg, USAGE))
+            }
+            _ => rest.push(arg),
+        }
+    }
+    if rest.is_empty() {
+        return Err(USAGE.to_string());
+    }
+    let command = rest.remove(0);
+    Ok(CliArgs {
+        ca_game,
+        json,
+        command,
+        positional: rest,
+        insert_as,
+        insert_to,
+        dry_run,
+        sync_policy,
+    })
+}
+
+fn get_positional(args: &CliArgs, indx: usize, what: &str) -> Result<String, String> {
+    match args.positional.get(indx) {
+        Some(p) => Ok(p.clone()),
+        None => Err(format!("{} needs a {}\n\n{}", args.command, what, USAGE)),
+    }
+}
+
+fn get_counts_string(counts: &MatchCounts) -> String {
+    format!("{}W {}L {}D", counts.wins, counts.losses, counts.draws)
+}
+
+fn print_builds(builds: &[ArmyBuild], json: bool) -> Result<(), String> {
+    if json {
+        return print_json(&builds);
+    }
+    for b in builds {
+        let counts = MatchCounts {
+            wins: b.win_count,
+            losses: b.loss_count,
+            draws: b.draw_count,
+        };
+        println!(
+            "{:<40} {:<20} vs {:<20} {:>6} {:<12} {}",
+            b.file_stem,
+            b.faction_str,
+            b.vs_faction_str,
+            b.funds,
+            get_counts_string(&counts),
+            b.build_id.get(..6).unwrap_or_default()
+        );
+    }
+    println!("{} builds", builds.len());
+    Ok(())
+}
+
+fn print_json<T: serde::Serialize>(value: &T) -> Result<(), String> {
+    match serde_json::to_string_pretty(value) {
+        Ok(s) => {
+            println!("{}", s);
+            Ok(())
+        }
+        Err(e) => Err(format!("Couldn't write json {}", e)),
+    }
+}
+
+fn print_message(message: &str, json: bool) -> Result<(), String> {
+    if json {
+        print_json(&json!({ "message": message }))
+    } else {
+        println!("{}", message);
+        Ok(())
+    }
+}
+
+fn search(args: &CliArgs, manager: &mut ArmySetupsManager) -> Result<(), String> {
+    let query = args.positional.join(" ");
+    if query.trim().is_empty() {
+        return Err(format!("search needs a query\n\n{}", SEARCH_QUERY_HELP));
+    }
+    manager.set_search_query(query.as_str())?;
+    print_builds(manager.get_display_builds(), args.json)
+}
+
+fn import(args: &CliArgs, manager: &mut ArmySetupsManager) -> Result<(), String> {
+    let folder = get_positional(args, 0, "folder")?;
+    manager.set_load_folder(folder.as_str());
+    let message = manager.load_folder_to_owaagh_appdata()?;
+    let report = manager.get_load_report();
+    if args.json {
+        let files: Vec<_> = report
+            .iter()
+            .map(|r| match &r.result {
+                Ok(s) => json!({ "member": r.member, "added": s }),
+                Err(e) => json!({ "member": r.member, "skipped": e }),
+            })
+            .collect();
+        return print_json(&json!({ "message": message, "files": files }));
+    }
+    for r in report {
+        match &r.result {
+            Ok(s) => println!("{}  {}", r.member, s),
+            Err(e) => println!("{}  skipped, {}", r.member, e),
+        }
+    }
+    print_message(message.as_str(), args.json)
+}
+
+fn insert(args: &CliArgs, manager: &mut ArmySetupsManager) -> Result<(), String> {
+    let build = get_positional(args, 0, "build")?;
+    let insert_as = match args.insert_as.as_ref() {
+        Some(name) => name.clone(),
+        None => return Err(format!("insert needs --as <name>\n\n{}", USAGE)),
+    };
+    manager.select_army_build(build.as_str())?;
+    manager.set_insert_name(insert_as.as_str());
+    if let Some(folder) = args.insert_to.as_ref() {
+        manager.set_insert_folder(folder.as_str());
+    }
+    manager.insert_army()?;
+    print_message(
+        format!(
+            "Inserted {} as {}.{} for {}",
+            build,
+            insert_as,
+            get_ca_game_army_setup_ext(args.ca_game.clone()),
+            get_ca_game_title(&args.ca_game)
+        )
+        .as_str(),
+        args.json,
+    )
+}
+
+fn code(args: &CliArgs, manager: &mut ArmySetupsManager) -> Result<(), String> {
+    let build = get_positional(args, 0, "build")?;
+    manager.select_army_build(build.as_str())?;
+    let code = manager.get_selected_build_code()?;
+    if args.json {
+        return print_json(&json!({ "build": build, "code": code }));
+    }
+    println!("{}", code);
+    Ok(())
+}
+
+fn import_code(args: &CliArgs, manager: &mut ArmySetupsManager) -> Result<(), String> {
+    let code = get_positional(args, 0, "code")?;
+    let message = manager.import_build_code(code.as_str())?;
+    print_message(message.as_str(), args.json)
+}
+
+fn export(args: &CliArgs, manager: &mut ArmySetupsManager) -> Result<(), String> {
+    let folder = get_positional(args, 0, "folder")?;
+    let query = args.positional[1..].join(" ");
+    manager.set_search_query(query.as_str())?;
+    if folder.to_lowercase().ends_with(".zip") {
+        let n = manager.export_display_builds_pack(Path::new(folder.as_str()))?;
+        return print_message(
+            format!("Packed {} builds into {}", n, folder).as_str(),
+            args.json,
+        );
+    }
+    let exported = manager.export_display_builds(Path::new(folder.as_str()))?;
+    if args.json {
+        return print_json(&exported);
+    }
+    for f in exported.iter() {
+        println!("{}", f.to_string_lossy());
+    }
+    println!("{} builds exported", exported.len());
+    Ok(())
+}
+
+fn sync(args: &CliArgs, manager: &mut ArmySetupsManager) -> Result<(), String> {
+    manager.set_sync_policy(args.sync_policy.clone());
+    let message = manager.sync_with_game_folder(!args.dry_run)?;
+    let report = manager.get_sync_report();
+    if args.json {
+        let files: Vec<_> = report
+            .iter()
+            .map(|r| {
+                json!({
+                    "file": r.file_name,
+                    "change": format!("{}", r.change),
+                    "action": format!("{}", r.action),
+                    "error": r.result.as_ref().err(),
+                })
+            })
+            .collect();
+        return print_json(&json!({ "message": message, "dry_run": args.dry_run, "files": files }));
+    }
+    for r in report {
+        println!("{}", r);
+    }
+    print_message(message.as_str(), args.json)
+}
+
+fn backups(args: &CliArgs, manager: &mut ArmySetupsManager) -> Result<(), String> {
+    let snapshots = manager.get_library_snapshots()?;
+    if args.json {
+        let snapshots: Vec<_> = snapshots
+            .iter()
+            .map(|s| json!({ "name": s.name, "builds": s.get_build_files(&args.ca_game) }))
+            .collect();
+        return print_json(&snapshots);
+    }
+    for s in snapshots {
+        println!(
+            "{}  {} builds",
+            s.name,
+            s.get_build_files(&args.ca_game).len()
+        );
+    }
+    println!("{} snapshots", snapshots.len());
+    Ok(())
+}
+
+fn restore(args: &CliArgs, manager: &mut ArmySetupsManager) -> Result<(), String> {
+    let snapshot = get_positional(args, 0, "snapshot")?;
+    let message = match args.positional.get(1) {
+        Some(file) => manager.restore_library_snapshot_build(snapshot.as_str(), file.as_str())?,
+        None => manager.restore_library_snapshot(snapshot.as_str())?,
+    };
+    print_message(message.as_str(), args.json)
+}
+
+fn delete(args: &CliArgs, manager: &mut ArmySetupsManager) -> Result<(), String> {
+    let build = get_positional(args, 0, "build")?;
+    manager.select_army_build(build.as_str())?;
+    let selected = manager.get_selected_army_build().clone();
+    let message = manager.delete_army_build(&selected)?;
+    print_message(message.as_str(), args.json)
+}
+
+fn trash(args: &CliArgs, manager: &mut ArmySetupsManager) -> Result<(), String> {
+    let trash = manager.get_trash()?;
+    if args.json {
+        return print_json(&trash);
+    }
+    for t in trash {
+        println!(
+            "{:<50} {:<40} {}",
+            t.trash_file,
+            t.file_stem,
+            get_ca_game_title(&t.ca_game)
+        );
+    }
+    println!("{} builds in the trash", trash.len());
+    Ok(())
+}
+
+fn stats(args: &CliArgs, manager: &ArmySetupsManager) -> Result<(), String> {
+    let log = get_match_log_file(&args.ca_game).and_then(|f| load_match_log(f.as_path()))?;
+    let stats = get_match_stats(&log, manager.get_display_builds(), &args.ca_game);
+    if args.json {
+        return print_json(&stats);
+    }
+    println!("Builds");
+    for b in stats.builds.iter() {
+        println!(
+            "  {:<40} {:<20} {:<12} {:.0}%",
+            b.name,
+            b.faction,
+            get_counts_string(&b.counts),
+            b.counts.get_win_percent()
+        );
+    }
+    println!("Factions");
+    for f in stats.factions.iter() {
+        println!(
+            "  {:<20} {:<12} {:.0}%",
+            f.faction,
+            get_counts_string(&f.counts),
+            f.counts.get_win_percent()
+        );
+    }
+    if stats.unmatched_games > 0 {
+        println!(
+            "{} games for builds no longer in the library",
+            stats.unmatched_games
+        );
+    }
+    Ok(())
+}
+
+fn run(args: &CliArgs) -> Result<(), String> {
+    let mut manager = ArmySetupsManager::from_library(args.ca_game.clone())?;
+    match args.command.as_str() {
+        "list" => print_builds(manager.get_display_builds(), args.json),
+        "search" => search(args, &mut manager),
+        "import" => import(args, &mut manager),
+        "insert" => insert(args, &mut manager),
+        "export" => export(args, &mut manager),
+        "code" => code(args, &mut manager),
+        "import-code" => import_code(args, &mut manager),
+        "sync" => sync(args, &mut manager),
+        "backup" => {
+            let message = manager.create_library_snapshot()?;
+            print_message(message.as_str(), args.json)
+        }
+        "backups" => backups(args, &mut manager),
+        "restore" => restore(args, &mut manager),
+        "delete" => delete(args, &mut manager),
+        "trash" => trash(args, &mut manager),
+        "untrash" => {
+            let file = get_positional(args, 0, "file")?;
+            let message = manager.restore_trashed_build(file.as_str())?;
+            print_message(message.as_str(), args.json)
+        }
+        "empty-trash" => {
+            let message = manager.empty_build_trash()?;
+            print_message(message.as_str(), args.json)
+        }
+        "stats" => stats(args, &manager),
+        c => Err(format!("Unknown command {}\n\n{}", c, USAGE)),
+    }
+}
+
+fn main() {
+    if std::env::args().any(|a| a == "-h" || a == "--help") {
+        println!("{}", USAGE);
+        return;
+    }
+    let args = match parse_args(std::env::args().skip(1).collect()) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+    if let Err(e) = run(&args) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,438 @@
+use crate::build_pack::{is_pack_manifest_member, parse_pack_manifest, PackManifest};
+use crate::ca_game::{
+    get_ca_game_army_setup_ext, get_ca_game_from_name, get_ca_game_subfolder, CaGame,
+};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// Build packs get passed around zipped, Felkon's comes as a .rar
+// zip & 7z open in process, rar has no pure rust decoder so it goes through whichever of
+// unrar or 7-Zip is installed
+const SETUP_EXTS: [&str; 2] = ["army_setup", "battle_preferences"];
+const RAR_TOOLS: [&str; 4] = ["unrar", "7z", "7za", "C:\\Program Files\\7-Zip\\7z.exe"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArchiveKind {
+    Zip,
+    SevenZip,
+    Rar,
+}
+
+pub fn get_archive_kind(file: &Path) -> Option<ArchiveKind> {
+    let ext = file.extension()?.to_string_lossy().to_ascii_lowercase();
+    match ext.as_str() {
+        "zip" => Some(ArchiveKind::Zip),
+        "7z" => Some(ArchiveKind::SevenZip),
+        "rar" => Some(ArchiveKind::Rar),
+        _ => None,
+    }
+}
+
+pub fn is_archive_file(file: &Path) -> bool {
+    file.is_file() && get_archive_kind(file).is_some()
+}
+
+pub struct ArchiveMember {
+    pub path: String, // as stored, folders & all
+    pub bytes: Vec<u8>,
+}
+
+//What became of one file in the archive, Ok says where it went, Err why it was skipped
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArchiveEntryReport {
+    pub member: String,
+    pub result: Result<String, String>,
+}
+
+fn get_member_ext(member: &str) -> Option<String> {
+    let file_name = get_member_file_name(member);
+    let (stem, ext) = file_name.rsplit_once('.')?;
+    if stem.is_empty() {
+        return None;
+    }
+    Some(ext.to_ascii_lowercase())
+}
+
+fn get_member_file_name(member: &str) -> &str {
+    member.rsplit(['/', '\\']).next().unwrap_or(member)
+}
+
+pub fn is_setup_member(member: &str) -> bool {
+    match get_member_ext(member) {
+        Some(ext) => SETUP_EXTS.contains(&ext.as_str()),
+        None => false,
+    }
+}
+
+fn is_wanted_member(member: &str) -> bool {
+    is_setup_member(member) || is_pack_manifest_member(member)
+}
+
+//Folder names in the archive pick the game, "Felkon/Warhammer2/GS vs HE.army_setup" is Warhammer 2
+//Without one .battle_preferences can only be Empire, anything else goes to the fallback game
+pub fn get_member_ca_game(member: &str, fallback: &CaGame) -> CaGame {
+    let mut folders: Vec<&str> = member.split(['/', '\\']).collect();
+    folders.pop();
+    if let Some(ca_game) = folders.iter().rev().find_map(|f| get_ca_game_from_name(f)) {
+        return ca_game;
+    }
+    match get_member_ext(member) {
+        Some(ext) if ext == get_ca_game_army_setup_ext(CaGame::Empire) => CaGame::Empire,
+        _ => fallback.clone(),
+    }
+}
+
+fn read_zip_members(file: &Path) -> Result<Vec<ArchiveMember>, String> {
+    let f = fs::File::open(file).map_err(|e| format!("{}", e))?;
+    let mut archive = zip::ZipArchive::new(f).map_err(|e| format!("{}", e))?;
+    let mut members = vec![];
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i).map_err(|e| format!("{}", e))?;
+        if entry.is_dir() {
+            continue;
+        }
+        let mut bytes = vec![];
+        if is_wanted_member(entry.name()) {
+            entry
+                .read_to_end(&mut bytes)
+                .map_err(|e| format!("{} {}", entry.name(), e))?;
+        }
+        members.push(ArchiveMember {
+            path: entry.name().to_string(),
+            bytes,
+        });
+    }
+    Ok(members)
+}
+
+fn read_7z_members(file: &Path) -> Result<Vec<ArchiveMember>, String> {
+    let mut archive = sevenz_rust::SevenZReader::open(file, sevenz_rust::Password::empty())
+        .map_err(|e| format!("{:?}", e))?;
+    let mut members = vec![];
+    let res = archive.for_each_entries(|entry, reader| {
+        if entry.is_directory() {
+            return Ok(true);
+        }
+        let mut bytes = vec![];
+        if is_wanted_member(entry.name()) {
+            reader
+                .read_to_end(&mut bytes)
+                .map_err(sevenz_rust::Error::io)?;
+        } else {
+            std::io::copy(reader, &mut std::io::sink()).map_err(sevenz_rust::Error::io)?;
+        }
+        members.push(ArchiveMember {
+            path: entry.name().to_string(),
+            bytes,
+        });
+        Ok(true)
+    });
+    match res {
+        Ok(()) => Ok(members),
+        Err(e) => Err(format!("{:?}", e)),
+    }
+}
+
+//Err(None) when the tool ain't installed, Err(Some(why)) when it couldn't extract
+fn run_rar_tool(tool: &str, file: &Path, dest: &Path) -> Result<(), Option<String>> {
+    let mut command = Command::new(tool);
+    if tool.contains("unrar") {
+        command.arg("x").arg("-o+").arg("-idq").arg(file).arg(dest);
+    } else {
+        command
+            .arg("x")
+            .arg("-y")
+            .arg(format!("-o{}", dest.to_string_lossy()))
+            .arg(file);
+    }
+    let output = match command.output() {
+        Ok(output) => output,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Err(None),
+        Err(e) => return Err(Some(format!("{} {}", tool, e))),
+    };
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(output.stderr.as_slice());
+    Err(Some(format!(
+        "{} couldn't extract it {}",
+        tool,
+        stderr.trim()
+    )))
+}
+
+fn read_rar_members(file: &Path) -> Result<Vec<ArchiveMember>, String> {
+    read_rar_members_with(file, &RAR_TOOLS)
+}
+
+//Tries each tool in turn, the error says which ones are there & why they failed
+fn read_rar_members_with(file: &Path, tools: &[&str]) -> Result<Vec<ArchiveMember>, String> {
+    let dest = std::env::temp_dir().join(format!("owaagh_rar_{}", std::process::id()));
+    let _ = fs::remove_dir_all(dest.as_path());
+    fs::create_dir_all(dest.as_path()).map_err(|e| format!("{}", e))?;
+
+    let mut failures = vec![];
+    let mut extracted = false;
+    for tool in tools.iter() {
+        match run_rar_tool(tool, file, dest.as_path()) {
+            Ok(()) => {
+                extracted = true;
+                break;
+            }
+            Err(Some(why)) => failures.push(why),
+            Err(None) => continue,
+        }
+    }
+    if !extracted {
+        let _ = fs::remove_dir_all(dest.as_path());
+        if !failures.is_empty() {
+            return Err(failures.join(", "));
+        }
+        return Err(
+            "Opening a .rar needs unrar or 7-Zip installed, or unpack it by hand & load the folder"
+                .to_string(),
+        );
+    }
+
+    let mut members = vec![];
+    for entry in walkdir::WalkDir::new(dest.as_path()).into_iter().flatten() {
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let path = match entry.path().strip_prefix(dest.as_path()) {
+            Ok(p) => p.to_string_lossy().replace('\\', "/"),
+            Err(_) => continue,
+        };
+        let bytes = if is_wanted_member(path.as_str()) {
+            fs::read(entry.path()).map_err(|e| format!("{} {}", path, e))?
+        } else {
+            vec![]
+        };
+        members.push(ArchiveMember { path, bytes });
+    }
+    let _ = fs::remove_dir_all(dest.as_path());
+    Ok(members)
+}
+
+//Every file in the archive, only setup files & a pack manifest get their bytes read
+pub fn read_archive_members(file: &Path) -> Result<Vec<ArchiveMember>, String> {
+    let res = match get_archive_kind(file) {
+        Some(ArchiveKind::Zip) => read_zip_members(file),
+        Some(ArchiveKind::SevenZip) => read_7z_members(file),
+        Some(ArchiveKind::Rar) => read_rar_members(file),
+        None => {
+            return Err(format!(
+                "{} ain't a .zip, .7z or .rar",
+                file.to_string_lossy()
+            ))
+        }
+    };
+    res.map_err(|e| format!("Couldn't open {} {}", file.to_string_lossy(), e))
+}
+
+//Setup files written out by game, ready for load_army_builds, & where each came from in the archive
+pub struct StagedArchive {
+    pub game_folders: HashMap<CaGame, PathBuf>,
+    pub member_by_file: HashMap<PathBuf, String>,
+    pub skipped: Vec<ArchiveEntryReport>,
+    pub manifest: Option<PackManifest>, // when it's one of our build packs
+}
+
+//Writes the archive's setup files into staging/<game>/, everything else is reported as skipped
+pub fn stage_archive_setups(
+    file: &Path,
+    fallback: &CaGame,
+    staging: &Path,
+) -> Result<StagedArchive, String> {
+    let members = read_archive_members(file)?;
+    let mut staged = StagedArchive {
+        game_folders: HashMap::new(),
+        member_by_file: HashMap::new(),
+        skipped: vec![],
+        manifest: None,
+    };
+    for member in members {
+        let skip = |reason: &str| ArchiveEntryReport {
+            member: member.path.clone(),
+            result: Err(reason.to_string()),
+        };
+        if is_pack_manifest_member(member.path.as_str()) {
+            match parse_pack_manifest(member.bytes.as_slice()) {
+                Ok(manifest) => staged.manifest = Some(manifest),
+                Err(e) => staged.skipped.push(skip(e.as_str())),
+            }
+            continue;
+        }
+        if !is_setup_member(member.path.as_str()) {
+            staged.skipped.push(skip("Not an army setup"));
+            continue;
+        }
+        let ca_game = get_member_ca_game(member.path.as_str(), fallback);
+        let game_ext = get_ca_game_army_setup_ext(ca_game.clone());
+        if get_member_ext(member.path.as_str()).as_deref() != Some(game_ext.as_str()) {
+            staged.skipped.push(skip(
+                format!(
+                    "{} builds are .{} files",
+                    get_ca_game_subfolder(&ca_game),
+                    game_ext
+                )
+                .as_str(),
+            ));
+            continue;
+        }
+
+        let folder = staging.join(get_ca_game_subfolder(&ca_game));
+        if let Err(e) = fs::create_dir_all(folder.as_path()) {
+            return Err(format!("Couldn't make {} {}", folder.to_string_lossy(), e));
+        }
+        //load_army_builds reads one flat folder, same named files from different folders can't both go
+        let staged_file = folder.join(get_member_file_name(member.path.as_str()));
+        if let Some(other) = staged.member_by_file.get(&staged_file) {
+            staged
+                .skipped
+                .push(skip(format!("Same file name as {}", other).as_str()));
+            continue;
+        }
+        if let Err(e) = fs::write(staged_file.as_path(), member.bytes.as_slice()) {
+            staged.skipped.push(skip(format!("{}", e).as_str()));
+            continue;
+        }
+        staged.game_folders.insert(ca_game, folder);
+        staged.member_by_file.insert(staged_file, member.path);
+    }
+    Ok(staged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn maps_members_to_games() {
+        let wh2 = CaGame::Warhammer2;
+        assert!(is_setup_member("Felkon/GS vs HE.army_setup"));
+        assert!(!is_setup_member("Felkon/readme.txt"));
+        assert!(!is_setup_member("Felkon/.army_setup"));
+        assert_eq!(
+            get_member_ca_game("Felkon/Warhammer3/GS vs HE.army_setup", &wh2),
+            CaGame::Warhammer3
+        );
+        assert_eq!(
+            get_member_ca_game("Pack\\Three Kingdoms\\Cao Cao.army_setup", &wh2),
+            CaGame::ThreeKingdoms
+        );
+        assert_eq!(
+            get_member_ca_game("Warhammer3.army_setup", &wh2),
+            CaGame::Warhammer2
+        );
+        assert_eq!(
+            get_member_ca_game("old/line infantry.battle_preferences", &wh2),
+            CaGame::Empire
+        );
+    }
+
+    #[test]
+    fn stages_zip_setups_by_game() {
+        let dir = std::env::temp_dir().join(format!("owaagh_archive_{}", std::process::id()));
+        fs::create_dir_all(dir.as_path()).unwrap();
+        let zip_file = dir.join("pack.zip");
+        {
+            let mut zip = zip::ZipWriter::new(fs::File::create(zip_file.as_path()).unwrap());
+            let options = zip::write::FileOptions::default();
+            for (name, bytes) in [
+                ("Felkon/Warhammer2/GS vs HE.army_setup", "gs"),
+                ("Felkon/Warhammer3/GS vs HE.army_setup", "gs3"),
+                ("Felkon/Warhammer2/sub/GS vs HE.army_setup", "gs other"),
+                ("Felkon/readme.txt", "read me"),
+            ] {
+                zip.start_file(name, options).unwrap();
+                zip.write_all(bytes.as_bytes()).unwrap();
+            }
+            zip.finish().unwrap();
+        }
+
+        let staging = dir.join("staging");
+        let staged = stage_archive_setups(zip_file.as_path(), &CaGame::Warhammer2, &staging);
+        let staged = staged.unwrap();
+        let wh2_file = staging.join("Warhammer2").join("GS vs HE.army_setup");
+        let wh2_bytes = fs::read(wh2_file.as_path()).unwrap();
+        fs::remove_dir_all(dir.as_path()).unwrap();
+
+        assert_eq!(wh2_bytes, b"gs");
+        assert_eq!(staged.game_folders.len(), 2);
+        assert_eq!(
+            staged.member_by_file[&wh2_file],
+            "Felkon/Warhammer2/GS vs HE.army_setup"
+        );
+        let skipped: Vec<&str> = staged.skipped.iter().map(|s| s.member.as_str()).collect();
+        assert_eq!(
+            skipped,
+            vec![
+                "Felkon/Warhammer2/sub/GS vs HE.army_setup",
+                "Felkon/readme.txt"
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_7z_members() {
+        let dir = std::env::temp_dir().join(format!("owaagh_archive_7z_{}", std::process::id()));
+        let _ = fs::remove_dir_all(dir.as_path());
+        let pack = dir.join("Felkon").join("Warhammer2");
+        fs::create_dir_all(pack.as_path()).unwrap();
+        fs::write(pack.join("GS vs HE.army_setup"), b"gs").unwrap();
+        fs::write(dir.join("Felkon").join("readme.txt"), b"read me").unwrap();
+        let file = std::env::temp_dir().join(format!("owaagh_pack_{}.7z", std::process::id()));
+        sevenz_rust::compress_to_path(dir.as_path(), file.as_path()).unwrap();
+
+        let members = read_archive_members(file.as_path());
+        fs::remove_dir_all(dir.as_path()).unwrap();
+        fs::remove_file(file.as_path()).unwrap();
+        let mut members: Vec<(String, Vec<u8>)> = members
+            .unwrap()
+            .into_iter()
+            .map(|m| (m.path.replace('\\', "/"), m.bytes))
+            .collect();
+        members.sort();
+        assert_eq!(
+            members,
+            vec![
+                (
+                    "Felkon/Warhammer2/GS vs HE.army_setup".to_string(),
+                    b"gs".to_vec()
+                ),
+                ("Felkon/readme.txt".to_string(), vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn rar_says_what_it_needs() {
+        let rar = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("assets")
+            .join("felkons_army builds_.rar");
+        let res = read_rar_members_with(rar.as_path(), &["owaagh-no-such-unrar"]);
+        assert!(res
+            .err()
+            .unwrap()
+            .starts_with("Opening a .rar needs unrar or 7-Zip"));
+        //there but failing, no stdout chatter, it's in the error
+        #[cfg(unix)]
+        assert!(read_rar_members_with(rar.as_path(), &["false"])
+            .err()
+            .unwrap()
+            .starts_with("false couldn't extract it"));
+
+        //Felkon's pack itself, when there's a tool to open it
+        match read_rar_members(rar.as_path()) {
+            Ok(members) => assert!(members
+                .iter()
+                .any(|m| is_setup_member(m.path.as_str()) && !m.bytes.is_empty())),
+            Err(e) => assert!(e.starts_with("Opening a .rar needs"), "{}", e),
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use crate::army_build::{get_army_build_id, ArmyBuild};
+use crate::build_pack::PackManifestEntry;
+use crate::ca_game::{get_ca_game_army_setup_ext, get_ca_game_subfolder};
+use sha2::{Digest, Sha256};
+use std::fmt;
+
+// A build code is a build & its setup file squeezed into one line of text for pasting in chat
+//   owaagh<version>:<base64 url safe>
+// The base64 is deflated [u32 le metadata json len][metadata json][setup bytes][4 byte checksum],
+// the checksum being the start of the sha256 of everything before it
+// Metadata is the same entry build packs use so both carry the same fields
+pub const BUILD_CODE_PREFIX: &str = "owaagh";
+pub const BUILD_CODE_VERSION: u32 = 1;
+const CHECKSUM_LEN: usize = 4;
+const COMPRESSION_LEVEL: u8 = 9;
+const MAX_DECODED_LEN: usize = 1024 * 1024; // setups are a few KB, anything near this is junk
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BuildCodeError {
+    NotACode,
+    UnsupportedVersion(u32),
+    Corrupt(String),
+    ChecksumMismatch,
+}
+
+impl fmt::Display for BuildCodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildCodeError::NotACode => write!(
+                f,
+                "Dat ain't a build code, they start with {}",
+                BUILD_CODE_PREFIX
+            ),
+            BuildCodeError::UnsupportedVersion(v) => write!(
+                f,
+                "Build code is version {}, only know up to {}, update WarbossWaaghit",
+                v, BUILD_CODE_VERSION
+            ),
+            BuildCodeError::Corrupt(e) => write!(f, "Build code is broken {}", e),
+            BuildCodeError::ChecksumMismatch => {
+                write!(f, "Build code got mangled, copy the whole thing again")
+            }
+        }
+    }
+}
+
+impl std::error::Error for BuildCodeError {}
+
+fn get_checksum(bytes: &[u8]) -> Vec<u8> {
+    Sha256::digest(bytes)[..CHECKSUM_LEN].to_vec()
+}
+
+pub fn encode_build_code(build: &ArmyBuild, setup_bytes: &[u8]) -> Result<String, String> {
+    let file = format!(
+        "{}/{}.{}",
+        get_ca_game_subfolder(&build.ca_game),
+        build.file_stem,
+        get_ca_game_army_setup_ext(build.ca_game.clone())
+    );
+    let mut entry = PackManifestEntry::new(build, file.as_str());
+    entry.sha256 = get_army_build_id(setup_bytes);
+    let meta = match serde_json::to_vec(&entry) {
+        Ok(meta) => meta,
+        Err(e) => return Err(format!("Couldn't serialize build {}", e)),
+    };
+
+    let mut payload = vec![];
+    payload.extend((meta.len() as u32).to_le_bytes().iter());
+    payload.extend(meta);
+    payload.extend(setup_bytes);
+    let checksum = get_checksum(payload.as_slice());
+    payload.extend(checksum);
+
+    let compressed = miniz_oxide::deflate::compress_to_vec(payload.as_slice(), COMPRESSION_LEVEL);
+    Ok(format!(
+        "{}{}:{}",
+        BUILD_CODE_PREFIX,
+        BUILD_CODE_VERSION,
+        base64::encode_config(compressed, base64::URL_SAFE_NO_PAD)
+    ))
+}
+
+//Takes it however it got pasted, chat likes to add backticks & line breaks
+pub fn decode_build_code(code: &str) -> Result<(PackManifestEntry, Vec<u8>), BuildCodeError> {
+    let code: String = code
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '`')
+        .collect();
+    let rest = code
+        .strip_prefix(BUILD_CODE_PREFIX)
+        .ok_or(BuildCodeError::NotACode)?;
+    let (version, data) = rest.split_once(':').ok_or(BuildCodeError::NotACode)?;
+    let version = version
+        .parse::<u32>()
+        .map_err(|_| BuildCodeError::NotACode)?;
+    if version == 0 || version > BUILD_CODE_VERSION {
+        return Err(BuildCodeError::UnsupportedVersion(version));
+    }
+
+    let compressed = base64::decode_config(data, base64::URL_SAFE_NO_PAD)
+        .map_err(|e| BuildCodeError::Corrupt(format!("{}", e)))?;
+    let payload =
+        miniz_oxide::inflate::decompress_to_vec_with_limit(compressed.as_slice(), MAX_DECODED_LEN)
+            .map_err(|e| BuildCodeError::Corrupt(format!("{:?}", e)))?;
+    if payload.len() < 4 + CHECKSUM_LEN {
+        return Err(BuildCodeError::Corrupt("too short".to_string()));
+    }
+    let (payload, checksum) = payload.split_at(payload.len() - CHECKSUM_LEN);
+    if get_checksum(payload) != checksum {
+        return Err(BuildCodeError::ChecksumMismatch);
+    }
+
+    let meta_len = u32::from_le_bytes([payload[0], payload[1], payload[2], payload[3]]) as usize;
+    let meta = payload
+        .get(4..4 + meta_len)
+        .ok_or_else(|| BuildCodeError::Corrupt("metadata runs past the end".to_string()))?;
+    let entry = serde_json::from_slice::<PackManifestEntry>(meta)
+        .map_err(|e| BuildCodeError::Corrupt(format!("{}", e)))?;
+    let setup_bytes = payload[4 + meta_len..].to_vec();
+    if get_army_build_id(setup_bytes.as_slice()) != entry.sha256 {
+        return Err(BuildCodeError::ChecksumMismatch);
+    }
+    Ok((entry, setup_bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ca_game::CaGame;
+
+    #[test]
+    fn round_trips_and_catches_damage() {
+        let setup_bytes: Vec<u8> = (0..2048u32).map(|i| (i % 7) as u8).collect();
+        let build = ArmyBuild {
+            file_stem: "GS vs HE".to_string(),
+            ca_game: CaGame::Warhammer2,
+            faction_str: "Greenskins".to_string(),
+            vs_faction_str: "High Elves".to_string(),
+            funds: 12400,
+            notes: "spam the gobbos".to_string(),
+            ..ArmyBuild::default()
+        };
+        let code = encode_build_code(&build, setup_bytes.as_slice()).unwrap();
+        assert!(code.starts_with("owaagh1:"));
+        assert!(code.len() < setup_bytes.len());
+
+        let pasted = format!("```\n{}\n{}\n```", &code[..40], &code[40..]);
+        let (entry, bytes) = decode_build_code(pasted.as_str()).unwrap();
+        assert_eq!(bytes, setup_bytes);
+        assert_eq!(entry.name, "GS vs HE");
+        assert_eq!(entry.file, "Warhammer2/GS vs HE.army_setup");
+        assert_eq!(entry.notes, "spam the gobbos");
+
+        assert_eq!(decode_build_code("GS vs HE"), Err(BuildCodeError::NotACode));
+        assert_eq!(
+            decode_build_code(code.replacen("owaagh1", "owaagh9", 1).as_str()),
+            Err(BuildCodeError::UnsupportedVersion(9))
+        );
+        let mut damaged = code.clone().into_bytes();
+        let last = damaged.len() - 3;
+        damaged[last] = if damaged[last] == b'A' { b'B' } else { b'A' };
+        assert!(decode_build_code(String::from_utf8(damaged).unwrap().as_str()).is_err());
+    }
+}
@@ -0,0 +1,229 @@
+use crate::army_build::{get_army_build_id, ArmyBuild};
+use crate::build_renamer::sanitize_file_stem;
+use crate::ca_game::{get_ca_game_army_setup_ext, get_ca_game_subfolder, CaGame};
+use crate::factions::get_wh2_faction_from_name;
+use crate::ymd_hms_dash_format;
+use chrono::{DateTime, Utc};
+use std::collections::HashSet;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+// A build pack is a zip of setup files under <game>/ plus a manifest at the root carrying what the
+// files can't, names, factions, notes... Loading the zip back in goes through the archive import
+pub const PACK_MANIFEST_FILE: &str = "owaagh_pack.json";
+pub const PACK_MANIFEST_VERSION: u32 = 1;
+
+#[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackManifestEntry {
+    pub file: String, // where it is in the zip
+    pub name: String,
+    pub ca_game: CaGame,
+    pub faction: String,
+    pub vs_faction: String,
+    pub funds: u32,
+    pub author: String,
+    pub game_mod: String,
+    pub notes: String,
+    pub sha256: String, // of the setup file, same as the build id
+}
+
+impl PackManifestEntry {
+    pub fn new(build: &ArmyBuild, file: &str) -> Self {
+        Self {
+            file: file.to_string(),
+            name: build.file_stem.clone(),
+            ca_game: build.ca_game.clone(),
+            faction: build.faction_str.clone(),
+            vs_faction: build.vs_faction_str.clone(),
+            funds: build.funds,
+            author: build.created_by.clone(),
+            game_mod: build.game_mod.clone(),
+            notes: build.notes.clone(),
+            sha256: build.build_id.clone(),
+        }
+    }
+
+    //Only for the file it was written with, a changed file keeps what was read from it
+    pub fn apply_to(&self, build: &mut ArmyBuild) -> Result<(), String> {
+        if build.build_id != self.sha256 {
+            return Err("Setup changed since the pack was made".to_string());
+        }
+        let name = sanitize_file_stem(self.name.as_str());
+        if !name.is_empty() && !name.starts_with('.') {
+            build.file_stem = name;
+        }
+        if build.ca_game == CaGame::Warhammer2 {
+            build.faction = get_wh2_faction_from_name(self.faction.as_str());
+            build.vs_faction = get_wh2_faction_from_name(self.vs_faction.as_str());
+        }
+        build.faction_str = self.faction.clone();
+        build.vs_faction_str = self.vs_faction.clone();
+        build.funds = self.funds;
+        build.created_by = self.author.clone();
+        build.game_mod = self.game_mod.clone();
+        build.notes = self.notes.clone();
+        Ok(())
+    }
+}
+
+#[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackManifest {
+    pub version: u32,
+    #[serde(with = "ymd_hms_dash_format")]
+    pub created_on: DateTime<Utc>,
+    pub builds: Vec<PackManifestEntry>,
+}
+
+impl PackManifest {
+    pub fn get_entry(&self, file: &str) -> Option<&PackManifestEntry> {
+        self.builds.iter().find(|e| e.file == file)
+    }
+}
+
+pub fn is_pack_manifest_member(member: &str) -> bool {
+    member == PACK_MANIFEST_FILE
+}
+
+pub fn parse_pack_manifest(bytes: &[u8]) -> Result<PackManifest, String> {
+    let manifest = match serde_json::from_slice::<PackManifest>(bytes) {
+        Ok(m) => m,
+        Err(e) => return Err(format!("Couldn't parse {} {}", PACK_MANIFEST_FILE, e)),
+    };
+    if manifest.version > PACK_MANIFEST_VERSION {
+        return Err(format!(
+            "{} is version {}, only know up to {}",
+            PACK_MANIFEST_FILE, manifest.version, PACK_MANIFEST_VERSION
+        ));
+    }
+    Ok(manifest)
+}
+
+//<game>/<name>.<ext>, numbered when two builds would land on the same name
+fn get_pack_member_path(build: &ArmyBuild, used: &HashSet<String>) -> String {
+    let folder = get_ca_game_subfolder(&build.ca_game);
+    let ext = get_ca_game_army_setup_ext(build.ca_game.clone());
+    let mut member = format!("{}/{}.{}", folder, build.file_stem, ext);
+    let mut n = 2;
+    while used.contains(&member.to_lowercase()) {
+        member = format!("{}/{} ({}).{}", folder, build.file_stem, n, ext);
+        n += 1;
+    }
+    member
+}
+
+fn write_build_pack(builds: &[ArmyBuild], file: &Path) -> Result<usize, String> {
+    let f = fs::File::create(file).map_err(|e| format!("{}", e))?;
+    let mut zip = zip::ZipWriter::new(f);
+    let options = zip::write::FileOptions::default();
+    let mut manifest = PackManifest {
+        version: PACK_MANIFEST_VERSION,
+        created_on: Utc::now(),
+        builds: vec![],
+    };
+    let mut used = HashSet::new();
+    for b in builds {
+        let bytes = match fs::read(b.file.as_path()) {
+            Ok(bytes) => bytes,
+            Err(e) => return Err(format!("Couldn't read {} {}", b.file.to_string_lossy(), e)),
+        };
+        let member = get_pack_member_path(b, &used);
+        zip.start_file(member.as_str(), options)
+            .map_err(|e| format!("{}", e))?;
+        zip.write_all(bytes.as_slice())
+            .map_err(|e| format!("{}", e))?;
+        used.insert(member.to_lowercase());
+
+        let mut entry = PackManifestEntry::new(b, member.as_str());
+        entry.sha256 = get_army_build_id(bytes.as_slice());
+        manifest.builds.push(entry);
+    }
+
+    let s = match serde_json::to_string_pretty(&manifest) {
+        Ok(s) => s,
+        Err(e) => return Err(format!("Couldn't serialize the manifest {}", e)),
+    };
+    zip.start_file(PACK_MANIFEST_FILE, options)
+        .map_err(|e| format!("{}", e))?;
+    zip.write_all(s.as_bytes()).map_err(|e| format!("{}", e))?;
+    zip.finish().map_err(|e| format!("{}", e))?;
+    Ok(manifest.builds.len())
+}
+
+//Written next to the target first so a failed export doesn't leave half a zip behind
+pub fn export_build_pack(builds: &[ArmyBuild], file: &Path) -> Result<usize, String> {
+    if builds.is_empty() {
+        return Err("No builds to pack".to_string());
+    }
+    let mut tmp = file.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = Path::new(&tmp);
+    let res = write_build_pack(builds, tmp).and_then(|n| match fs::rename(tmp, file) {
+        Ok(()) => Ok(n),
+        Err(e) => Err(format!("{}", e)),
+    });
+    if res.is_err() {
+        let _ = fs::remove_file(tmp);
+    }
+    res.map_err(|e| format!("Couldn't write {} {}", file.to_string_lossy(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build_archive::stage_archive_setups;
+
+    #[test]
+    fn pack_round_trips_through_archive_import() {
+        let dir = std::env::temp_dir().join(format!("owaagh_pack_{}", std::process::id()));
+        fs::create_dir_all(dir.as_path()).unwrap();
+        let setup = dir.join("GS vs HE.army_setup");
+        fs::write(setup.as_path(), b"setup bytes").unwrap();
+        let build = ArmyBuild {
+            file: setup.clone(),
+            file_stem: "GS vs HE".to_string(),
+            ca_game: CaGame::Warhammer2,
+            faction_str: "Greenskins".to_string(),
+            vs_faction_str: "High Elves".to_string(),
+            funds: 12400,
+            created_by: "Gudgitz".to_string(),
+            notes: "spam the gobbos".to_string(),
+            build_id: get_army_build_id(b"setup bytes"),
+            ..ArmyBuild::default()
+        };
+        let pack = dir.join("pack.zip");
+        let builds = vec![build.clone(), build.clone()];
+        assert_eq!(export_build_pack(&builds, pack.as_path()), Ok(2));
+
+        let staged =
+            stage_archive_setups(pack.as_path(), &CaGame::Warhammer3, &dir.join("staging"))
+                .unwrap();
+        fs::remove_dir_all(dir.as_path()).unwrap();
+        assert!(staged.skipped.is_empty());
+        assert_eq!(staged.member_by_file.len(), 2);
+        let manifest = staged.manifest.unwrap();
+        assert_eq!(
+            manifest.builds[1].file,
+            "Warhammer2/GS vs HE (2).army_setup"
+        );
+
+        let mut loaded = ArmyBuild {
+            file_stem: "something else".to_string(),
+            ca_game: CaGame::Warhammer2,
+            build_id: build.build_id.clone(),
+            ..ArmyBuild::default()
+        };
+        let entry = manifest
+            .get_entry("Warhammer2/GS vs HE.army_setup")
+            .unwrap();
+        entry.apply_to(&mut loaded).unwrap();
+        assert_eq!(loaded.file_stem, "GS vs HE");
+        assert_eq!(loaded.vs_faction_str, "High Elves");
+        assert_eq!(loaded.notes, "spam the gobbos");
+
+        loaded.build_id = "changed".to_string();
+        assert!(entry.apply_to(&mut loaded).is_err());
+    }
+}
@@ -0,0 +1,327 @@
+use crate::army_build::ArmyBuild;
+use crate::ca_game::{get_ca_game_army_setup_ext, CaGame};
+use crate::faction_registry::{get_faction_registry, ALL_FACTIONS};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_CANONICAL_TEMPLATE: &str = "{faction} vs {vs_faction} {funds} {author} {tag}";
+const SHORT_ID_LEN: usize = 6;
+
+pub fn get_default_canonical_template() -> String {
+    DEFAULT_CANONICAL_TEMPLATE.to_string()
+}
+
+fn get_faction_field(ca_game: &CaGame, faction_str: &str) -> String {
+    if faction_str == ALL_FACTIONS {
+        return "AA".to_string();
+    }
+    match get_faction_registry().find_faction(ca_game, faction_str) {
+        Some(f) => f.id.clone(),
+        None => String::new(),
+    }
+}
+
+pub fn sanitize_file_stem(stem: &str) -> String {
+    let cleaned: String = stem
+        .chars()
+        .filter(|c| !matches!(c, '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*'))
+        .collect();
+    cleaned.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+// Fills {faction} {vs_faction} {funds} {author} {tag} & {id} (start of the build hash), empty fields drop out
+pub fn render_canonical_name(
+    template: &str,
+    build: &ArmyBuild,
+    ca_game: &CaGame,
+    tag: &str,
+) -> Result<String, String> {
+    let mut name = String::new();
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        name.push_str(&rest[..open]);
+        let close = match rest[open..].find('}') {
+            Some(close) => open + close,
+            None => return Err(format!("\'{}\' has a {{ that never closes", template)),
+        };
+        let value = match &rest[open + 1..close] {
+            "faction" => get_faction_field(ca_game, build.faction_str.as_str()),
+            "vs_faction" => get_faction_field(ca_game, build.vs_faction_str.as_str()),
+            "funds" => build.funds.to_string(),
+            "author" => build.created_by.clone(),
+            "tag" => tag.to_string(),
+            "id" => build.build_id.chars().take(SHORT_ID_LEN).collect(),
+            field => {
+                return Err(format!(
+                    "Don't know {{{}}}, use {{faction}} {{vs_faction}} {{funds}} {{author}} {{tag}} or {{id}}",
+                    field
+                ))
+            }
+        };
+        //no opponent, so the "vs" in front of it goes too
+        if value.is_empty() && &rest[open + 1..close] == "vs_faction" {
+            let trimmed = name.trim_end();
+            let lower = trimmed.to_ascii_lowercase();
+            if lower == "vs" || lower.ends_with(" vs") {
+                name.truncate(trimmed.len() - 2);
+            }
+        }
+        name.push_str(value.as_str());
+        rest = &rest[close + 1..];
+    }
+    name.push_str(rest);
+    let name = sanitize_file_stem(name.as_str());
+    if name.is_empty() {
+        return Err(format!("{} comes out with no name", build.file_stem));
+    }
+    Ok(name)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenameStep {
+    pub identity: String, // ArmyBuild::get_identity
+    pub from_stem: String,
+    pub to_stem: String,
+    pub collided: bool, // got a number added to stay unique
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RenamePlan {
+    pub steps: Vec<RenameStep>,
+    pub unchanged: usize,
+    pub errors: Vec<String>,
+}
+
+// Works out every rename up front, names that would clash get " (2)", " (3)" ...
+// Windows doesn't care about case so neither does the clash check
+pub fn plan_canonical_renames(
+    builds: &[ArmyBuild],
+    template: &str,
+    tag: &str,
+    ca_game: &CaGame,
+) -> RenamePlan {
+    let mut plan = RenamePlan::default();
+    let mut sorted: Vec<&ArmyBuild> = builds.iter().collect();
+    sorted.sort();
+
+    let mut targets = vec![];
+    for b in sorted {
+        match render_canonical_name(template, b, ca_game, tag) {
+            Ok(name) => targets.push((b, name)),
+            Err(e) => {
+                plan.errors.push(e);
+                //keeps its name, so nothing else can take it
+                targets.push((b, b.file_stem.clone()));
+            }
+        }
+    }
+
+    //already canonical never moves, whatever sorts ahead of it has to number around it
+    let mut taken: HashSet<String> = targets
+        .iter()
+        .filter(|(b, target)| *target == b.file_stem)
+        .map(|(_, target)| target.to_lowercase())
+        .collect();
+    for (b, target) in targets {
+        if target == b.file_stem {
+            plan.unchanged += 1;
+            continue;
+        }
+        let mut to_stem = target.clone();
+        let mut n = 2;
+        while taken.contains(&to_stem.to_lowercase()) {
+            to_stem = format!("{} ({})", target, n);
+            n += 1;
+        }
+        taken.insert(to_stem.to_lowercase());
+        if to_stem == b.file_stem {
+            plan.unchanged += 1;
+            continue;
+        }
+        plan.steps.push(RenameStep {
+            identity: b.get_identity(),
+            from_stem: b.file_stem.clone(),
+            collided: to_stem != target,
+            to_stem,
+        });
+    }
+    plan
+}
+
+fn get_stem_file(folder: &Path, stem: &str, ca_game: &CaGame) -> PathBuf {
+    folder.join(format!(
+        "{}.{}",
+        stem,
+        get_ca_game_army_setup_ext(ca_game.clone())
+    ))
+}
+
+// Renames in two passes through temporary names so swaps & chains never clobber a file.
+// Returns the steps that made it, a failed step leaves that build on its old name
+pub fn apply_rename_plan(
+    plan: &RenamePlan,
+    folder: &Path,
+    ca_game: &CaGame,
+) -> (Vec<RenameStep>, Vec<String>) {
+    let mut errors = vec![];
+    let mut staged = vec![];
+    for (i, step) in plan.steps.iter().enumerate() {
+        let from = get_stem_file(folder, step.from_stem.as_str(), ca_game);
+        let tmp = folder.join(format!(".owaagh_rename_{}.tmp", i));
+        match fs::rename(from.as_path(), tmp.as_path()) {
+            Ok(_) => staged.push((step, from, tmp)),
+            Err(e) => errors.push(format!("Couldn't rename {} {}", step.from_stem, e)),
+        }
+    }
+
+    let mut done = vec![];
+    for (step, from, tmp) in staged {
+        let to = get_stem_file(folder, step.to_stem.as_str(), ca_game);
+        let res = if to.exists() {
+            Err(format!("{} is already there", step.to_stem))
+        } else {
+            fs::rename(tmp.as_path(), to.as_path()).map_err(|e| format!("{}", e))
+        };
+        match res {
+            Ok(_) => done.push(step.clone()),
+            Err(e) => {
+                errors.push(format!(
+                    "Couldn't rename {} to {} {}",
+                    step.from_stem, step.to_stem, e
+                ));
+                if let Err(e) = fs::rename(tmp.as_path(), from.as_path()) {
+                    errors.push(format!(
+                        "{} stuck at {} {}",
+                        step.from_stem,
+                        tmp.to_string_lossy(),
+                        e
+                    ));
+                }
+            }
+        }
+    }
+    (done, errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build(file_stem: &str, faction: &str, vs_faction: &str, author: &str) -> ArmyBuild {
+        ArmyBuild {
+            file_stem: file_stem.to_string(),
+            build_id: format!("{}id", file_stem),
+            faction_str: faction.to_string(),
+            vs_faction_str: vs_faction.to_string(),
+            funds: 12400,
+            created_by: author.to_string(),
+            ..ArmyBuild::default()
+        }
+    }
+
+    #[test]
+    fn renders_ids_and_drops_empty_fields() {
+        let b = build("my greenskins", "Greenskins", "High Elves", "");
+        assert_eq!(
+            render_canonical_name(DEFAULT_CANONICAL_TEMPLATE, &b, &CaGame::Warhammer2, "").unwrap(),
+            "GS vs HE 12400"
+        );
+        let b = build("x", "Greenskins", "Unknown", "Boss");
+        assert_eq!(
+            render_canonical_name(
+                "{faction} vs {vs_faction} {author}",
+                &b,
+                &CaGame::Warhammer2,
+                ""
+            )
+            .unwrap(),
+            "GS Boss"
+        );
+        assert!(render_canonical_name("{faction} {nope}", &b, &CaGame::Warhammer2, "").is_err());
+    }
+
+    #[test]
+    fn plan_skips_named_builds_and_numbers_clashes() {
+        let builds = vec![
+            build("GS vs HE 12400", "Greenskins", "High Elves", ""),
+            build("a", "Greenskins", "High Elves", ""),
+            build("b", "Greenskins", "high elves", ""),
+            build("c", "Dwarfs", "Vampire Counts", ""),
+        ];
+        let plan =
+            plan_canonical_renames(&builds, DEFAULT_CANONICAL_TEMPLATE, "", &CaGame::Warhammer2);
+        assert_eq!(plan.unchanged, 1);
+        let to: Vec<&str> = plan.steps.iter().map(|s| s.to_stem.as_str()).collect();
+        assert_eq!(
+            to,
+            vec!["GS vs HE 12400 (2)", "GS vs HE 12400 (3)", "DW vs VC 12400"]
+        );
+        assert!(plan.steps[0].collided && !plan.steps[2].collided);
+
+        //sorts ahead of the one already named right, still can't take its name
+        let builds = vec![
+            build("GS vs HE 12400", "Greenskins", "High Elves", ""),
+            build("0", "Greenskins", "High Elves", ""),
+        ];
+        let plan =
+            plan_canonical_renames(&builds, DEFAULT_CANONICAL_TEMPLATE, "", &CaGame::Warhammer2);
+        assert_eq!(plan.unchanged, 1);
+        assert_eq!(plan.steps.len(), 1);
+        assert_eq!(plan.steps[0].from_stem, "0");
+        assert_eq!(plan.steps[0].to_stem, "GS vs HE 12400 (2)");
+    }
+
+    fn step(from_stem: &str, to_stem: &str) -> RenameStep {
+        RenameStep {
+            identity: from_stem.to_string(),
+            from_stem: from_stem.to_string(),
+            to_stem: to_stem.to_string(),
+            collided: false,
+        }
+    }
+
+    #[test]
+    fn applies_swaps_and_chains() {
+        let dir = std::env::temp_dir().join(format!("owaagh_rename_{}", std::process::id()));
+        let _ = fs::remove_dir_all(dir.as_path());
+        fs::create_dir_all(dir.as_path()).unwrap();
+        let wh2 = CaGame::Warhammer2;
+        let read = |stem: &str| fs::read_to_string(get_stem_file(dir.as_path(), stem, &wh2)).ok();
+        for stem in ["A", "B"].iter() {
+            fs::write(get_stem_file(dir.as_path(), stem, &wh2), stem).unwrap();
+        }
+
+        let swap = RenamePlan {
+            steps: vec![step("A", "B"), step("B", "A")],
+            ..RenamePlan::default()
+        };
+        let (done, errors) = apply_rename_plan(&swap, dir.as_path(), &wh2);
+        assert_eq!((done.len(), errors.len()), (2, 0));
+        assert_eq!(read("A").as_deref(), Some("B"));
+        assert_eq!(read("B").as_deref(), Some("A"));
+
+        //A to B to C, B's old file moves on before A's arrives
+        let chain = RenamePlan {
+            steps: vec![step("A", "B"), step("B", "C")],
+            ..RenamePlan::default()
+        };
+        let (done, errors) = apply_rename_plan(&chain, dir.as_path(), &wh2);
+        assert_eq!((done.len(), errors.len()), (2, 0));
+        assert_eq!(read("A"), None);
+        assert_eq!(read("B").as_deref(), Some("B"));
+        assert_eq!(read("C").as_deref(), Some("A"));
+
+        //a name that's taken outside the plan leaves the build where it was
+        let blocked = RenamePlan {
+            steps: vec![step("B", "C")],
+            ..RenamePlan::default()
+        };
+        let (done, errors) = apply_rename_plan(&blocked, dir.as_path(), &wh2);
+        let left = fs::read_dir(dir.as_path()).unwrap().count();
+        assert_eq!((done.len(), errors.len()), (0, 1));
+        assert_eq!(read("B").as_deref(), Some("B"));
+        fs::remove_dir_all(dir.as_path()).unwrap();
+        assert_eq!(left, 2, "no tmp files left behind");
+    }
+}
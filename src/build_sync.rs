@@ -0,0 +1,565 @@
+use crate::army_build::{get_army_build_id, ArmyBuild};
+use crate::army_setups_folder::{get_owaagh_appdata_dir, is_ca_game_setup_path};
+use crate::build_trash::trash_build;
+use crate::ca_game::{get_ca_game_subfolder, CaGame};
+use crate::library_store::{
+    get_library_index_file, load_library_index, update_library_index, write_file_atomic,
+};
+use crate::match_log::{get_match_log_file, load_match_log, save_match_log};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Two way sync between the game's army_setups folder & the library. Files pair up by file name
+// & compare by content hash, the hashes both sides agreed on at the last sync tell which side
+// changed or lost a file. Without a last sync nothing counts as deleted.
+const SYNC_STATE_FILE: &str = "sync_state.json";
+
+#[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SyncState {
+    pub files: BTreeMap<String, String>, // file name -> hash both sides had
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyncChange {
+    NewInGame,
+    NewInLibrary,
+    ModifiedInGame,
+    ModifiedInLibrary,
+    ModifiedBoth,
+    DeletedInGame,
+    DeletedInLibrary,
+    SameContentAs(String), // new on one side but the other has it under this name
+}
+
+impl fmt::Display for SyncChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SyncChange::NewInGame => write!(f, "New in game"),
+            SyncChange::NewInLibrary => write!(f, "New in library"),
+            SyncChange::ModifiedInGame => write!(f, "Changed in game"),
+            SyncChange::ModifiedInLibrary => write!(f, "Changed in library"),
+            SyncChange::ModifiedBoth => write!(f, "Changed on both sides"),
+            SyncChange::DeletedInGame => write!(f, "Deleted in game"),
+            SyncChange::DeletedInLibrary => write!(f, "Deleted in library"),
+            SyncChange::SameContentAs(name) => write!(f, "Same build as {}", name),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyncItem {
+    pub file_name: String,
+    pub change: SyncChange,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SyncPlan {
+    pub items: Vec<SyncItem>,
+    pub in_sync: usize,
+}
+
+#[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SyncConflictPolicy {
+    GameWins,
+    LibraryWins,
+    KeepBoth, // game's copy takes the name, library's is kept as "<name> (library)"
+    Skip,
+}
+
+pub const SYNC_CONFLICT_POLICIES: [SyncConflictPolicy; 4] = [
+    SyncConflictPolicy::GameWins,
+    SyncConflictPolicy::LibraryWins,
+    SyncConflictPolicy::KeepBoth,
+    SyncConflictPolicy::Skip,
+];
+
+pub fn get_sync_conflict_policy_title(policy: &SyncConflictPolicy) -> &'static str {
+    match policy {
+        SyncConflictPolicy::GameWins => "Game Wins",
+        SyncConflictPolicy::LibraryWins => "Library Wins",
+        SyncConflictPolicy::KeepBoth => "Keep Both",
+        SyncConflictPolicy::Skip => "Skip",
+    }
+}
+
+#[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyncPolicy {
+    pub on_conflict: SyncConflictPolicy,
+    pub apply_deletes: bool, // off leaves the other side's copy & keeps reporting the delete
+}
+
+impl Default for SyncPolicy {
+    fn default() -> Self {
+        Self {
+            on_conflict: SyncConflictPolicy::Skip,
+            apply_deletes: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyncAction {
+    CopyToLibrary,
+    CopyToGame,
+    DeleteFromLibrary,
+    DeleteFromGame,
+    KeepBoth(String), // library's copy moves to this file name
+    Skip,
+}
+
+impl fmt::Display for SyncAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SyncAction::CopyToLibrary => write!(f, "copy to library"),
+            SyncAction::CopyToGame => write!(f, "copy to game"),
+            SyncAction::DeleteFromLibrary => write!(f, "trash from library"),
+            SyncAction::DeleteFromGame => write!(f, "trash from game"),
+            SyncAction::KeepBoth(name) => {
+                write!(f, "copy to library, library's kept as {}", name)
+            }
+            SyncAction::Skip => write!(f, "left alone"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyncReportEntry {
+    pub file_name: String,
+    pub change: SyncChange,
+    pub action: SyncAction,
+    pub result: Result<(), String>,
+}
+
+impl fmt::Display for SyncReportEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.result {
+            Ok(()) => write!(f, "{}  {}, {}", self.file_name, self.change, self.action),
+            Err(e) => write!(
+                f,
+                "{}  {}, couldn't {} {}",
+                self.file_name, self.change, self.action, e
+            ),
+        }
+    }
+}
+
+//Next to the library index, one per game
+pub fn get_sync_state_file(ca_game: &CaGame) -> Result<PathBuf, String> {
+    let p = get_owaagh_appdata_dir()?.join(get_ca_game_subfolder(ca_game));
+    Ok(p.join(SYNC_STATE_FILE))
+}
+
+//Missing file is never synced, a broken one is an error so deletes never get guessed
+pub fn load_sync_state(file: &Path) -> Result<SyncState, String> {
+    if !file.exists() {
+        return Ok(SyncState::default());
+    }
+    let s = match fs::read_to_string(file) {
+        Ok(s) => s,
+        Err(e) => return Err(format!("Couldn't read {} {}", file.to_string_lossy(), e)),
+    };
+    match serde_json::from_str::<SyncState>(s.as_str()) {
+        Ok(state) => Ok(state),
+        Err(e) => Err(format!("Couldn't parse {} {}", file.to_string_lossy(), e)),
+    }
+}
+
+pub fn save_sync_state(file: &Path, state: &SyncState) -> Result<(), String> {
+    let s = match serde_json::to_string_pretty(state) {
+        Ok(s) => s,
+        Err(e) => return Err(format!("Couldn't serialize sync state {}", e)),
+    };
+    write_file_atomic(file, s.as_str())
+}
+
+//File name -> content hash of the game's setups in a folder
+pub fn get_folder_hashes(
+    folder: &Path,
+    ca_game: &CaGame,
+) -> Result<BTreeMap<String, String>, String> {
+    let mut hashes = BTreeMap::new();
+    let entries = match fs::read_dir(folder) {
+        Ok(entries) => entries,
+        Err(e) => return Err(format!("Couldn't read {} {}", folder.to_string_lossy(), e)),
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if !path.is_file() || !is_ca_game_setup_path(path.as_path(), ca_game) {
+            continue;
+        }
+        let bytes = match fs::read(path.as_path()) {
+            Ok(bytes) => bytes,
+            Err(e) => return Err(format!("Couldn't read {} {}", path.to_string_lossy(), e)),
+        };
+        hashes.insert(
+            entry.file_name().to_string_lossy().to_string(),
+            get_army_build_id(bytes.as_slice()),
+        );
+    }
+    Ok(hashes)
+}
+
+fn find_name_with_hash(hashes: &BTreeMap<String, String>, hash: &str) -> Option<String> {
+    hashes
+        .iter()
+        .find(|(_, h)| h.as_str() == hash)
+        .map(|(name, _)| name.clone())
+}
+
+pub fn plan_sync(
+    game: &BTreeMap<String, String>,
+    library: &BTreeMap<String, String>,
+    state: &SyncState,
+) -> SyncPlan {
+    let mut plan = SyncPlan::default();
+    let names: BTreeSet<&String> = game.keys().chain(library.keys()).collect();
+    for name in names {
+        let last = state.files.get(name);
+        let change = match (game.get(name), library.get(name)) {
+            (Some(g), Some(l)) if g == l => {
+                plan.in_sync += 1;
+                continue;
+            }
+            (Some(g), Some(l)) => {
+                if last == Some(g) {
+                    SyncChange::ModifiedInLibrary
+                } else if last == Some(l) {
+                    SyncChange::ModifiedInGame
+                } else {
+                    SyncChange::ModifiedBoth
+                }
+            }
+            //changed in game since it went from the library still counts as new, nothing gets lost
+            (Some(g), None) if last == Some(g) => SyncChange::DeletedInLibrary,
+            (Some(g), None) => match find_name_with_hash(library, g) {
+                Some(other) => SyncChange::SameContentAs(other),
+                None => SyncChange::NewInGame,
+            },
+            (None, Some(l)) if last == Some(l) => SyncChange::DeletedInGame,
+            (None, Some(l)) => match find_name_with_hash(game, l) {
+                Some(other) => SyncChange::SameContentAs(other),
+                None => SyncChange::NewInLibrary,
+            },
+            (None, None) => continue,
+        };
+        plan.items.push(SyncItem {
+            file_name: name.clone(),
+            change,
+        });
+    }
+    plan
+}
+
+//"<stem> (library).<ext>", numbered past ones already there
+fn get_keep_both_name(file_name: &str, library: &Path) -> String {
+    let path = Path::new(file_name);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = path.extension().unwrap_or_default().to_string_lossy();
+    let mut name = format!("{} (library).{}", stem, ext);
+    let mut n = 2;
+    while library.join(name.as_str()).exists() {
+        name = format!("{} (library {}).{}", stem, n, ext);
+        n += 1;
+    }
+    name
+}
+
+pub fn get_sync_action(item: &SyncItem, policy: &SyncPolicy, library: &Path) -> SyncAction {
+    match item.change {
+        SyncChange::NewInGame | SyncChange::ModifiedInGame => SyncAction::CopyToLibrary,
+        SyncChange::NewInLibrary | SyncChange::ModifiedInLibrary => SyncAction::CopyToGame,
+        SyncChange::ModifiedBoth => match policy.on_conflict {
+            SyncConflictPolicy::GameWins => SyncAction::CopyToLibrary,
+            SyncConflictPolicy::LibraryWins => SyncAction::CopyToGame,
+            SyncConflictPolicy::KeepBoth => {
+                SyncAction::KeepBoth(get_keep_both_name(item.file_name.as_str(), library))
+            }
+            SyncConflictPolicy::Skip => SyncAction::Skip,
+        },
+        SyncChange::DeletedInGame if policy.apply_deletes => SyncAction::DeleteFromLibrary,
+        SyncChange::DeletedInLibrary if policy.apply_deletes => SyncAction::DeleteFromGame,
+        SyncChange::DeletedInGame | SyncChange::DeletedInLibrary => SyncAction::Skip,
+        SyncChange::SameContentAs(_) => SyncAction::Skip,
+    }
+}
+
+// The library index & match log go by content hash, a build copied over another takes the old
+// content's notes & results with it unless its new content already has its own
+fn carry_build_metadata(ca_game: &CaGame, old_id: &str, new_id: &str) -> Result<(), String> {
+    if old_id == new_id {
+        return Ok(());
+    }
+    let index = load_library_index(get_library_index_file(ca_game)?.as_path())?;
+    if !index.builds.contains_key(new_id) {
+        if let Some(m) = index.builds.get(old_id).cloned() {
+            update_library_index(ca_game, |index| {
+                index.builds.insert(new_id.to_string(), m);
+            })?;
+        }
+    }
+    let log_file = get_match_log_file(ca_game)?;
+    let mut log = load_match_log(log_file.as_path())?;
+    if log.records.iter().any(|r| r.build_id == new_id)
+        || !log.records.iter().any(|r| r.build_id == old_id)
+    {
+        return Ok(());
+    }
+    for r in log.records.iter_mut().filter(|r| r.build_id == old_id) {
+        r.build_id = new_id.to_string();
+    }
+    save_match_log(log_file.as_path(), &log)
+}
+
+//Into the trash with its metadata like a delete from the build list
+fn trash_sync_file(ca_game: &CaGame, file: PathBuf, hash: &str) -> Result<(), String> {
+    let metadata = get_library_index_file(ca_game)
+        .and_then(|f| load_library_index(f.as_path()))?
+        .builds
+        .get(hash)
+        .cloned();
+    let build = ArmyBuild {
+        file_stem: file
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string(),
+        file,
+        ca_game: ca_game.clone(),
+        build_id: hash.to_string(),
+        ..ArmyBuild::default()
+    };
+    trash_build(&build, metadata).map(|_| ())
+}
+
+fn apply_sync_action(
+    ca_game: &CaGame,
+    action: &SyncAction,
+    file_name: &str,
+    game: &Path,
+    library: &Path,
+    game_hashes: &BTreeMap<String, String>,
+    library_hashes: &BTreeMap<String, String>,
+) -> Result<(), String> {
+    let game_file = game.join(file_name);
+    let library_file = library.join(file_name);
+    let game_hash = game_hashes.get(file_name).cloned().unwrap_or_default();
+    let library_hash = library_hashes.get(file_name).cloned().unwrap_or_default();
+    let io = |e: std::io::Error| format!("{}", e);
+    match action {
+        SyncAction::CopyToLibrary => {
+            fs::copy(game_file, library_file).map_err(io)?;
+            match library_hashes.get(file_name) {
+                Some(old) => carry_build_metadata(ca_game, old, game_hash.as_str()),
+                None => Ok(()),
+            }
+        }
+        SyncAction::CopyToGame => {
+            fs::copy(library_file, game_file).map_err(io)?;
+            match game_hashes.get(file_name) {
+                Some(old) => carry_build_metadata(ca_game, old, library_hash.as_str()),
+                None => Ok(()),
+            }
+        }
+        SyncAction::DeleteFromLibrary => {
+            trash_sync_file(ca_game, library_file, library_hash.as_str())
+        }
+        SyncAction::DeleteFromGame => trash_sync_file(ca_game, game_file, game_hash.as_str()),
+        SyncAction::KeepBoth(name) => fs::rename(library_file.as_path(), library.join(name))
+            .and_then(|_| fs::copy(game_file, library_file).map(|_| ()))
+            .map_err(io),
+        SyncAction::Skip => Ok(()),
+    }
+}
+
+//Dry run when apply is false, the report says what would happen without touching anything
+pub fn run_sync(
+    ca_game: &CaGame,
+    game: &Path,
+    library: &Path,
+    policy: &SyncPolicy,
+    apply: bool,
+) -> Result<(SyncPlan, Vec<SyncReportEntry>), String> {
+    let state_file = get_sync_state_file(ca_game)?;
+    let state = load_sync_state(state_file.as_path())?;
+    let game_hashes = get_folder_hashes(game, ca_game)?;
+    let library_hashes = get_folder_hashes(library, ca_game)?;
+    let plan = plan_sync(&game_hashes, &library_hashes, &state);
+
+    let mut report = vec![];
+    for item in plan.items.iter() {
+        let action = get_sync_action(item, policy, library);
+        let result = if apply {
+            apply_sync_action(
+                ca_game,
+                &action,
+                item.file_name.as_str(),
+                game,
+                library,
+                &game_hashes,
+                &library_hashes,
+            )
+        } else {
+            Ok(())
+        };
+        report.push(SyncReportEntry {
+            file_name: item.file_name.clone(),
+            change: item.change.clone(),
+            action,
+            result,
+        });
+    }
+    if !apply {
+        return Ok((plan, report));
+    }
+
+    //what both sides agree on now, plus the last sync's hash for anything left alone so it
+    //turns up the same way next time
+    let game_hashes = get_folder_hashes(game, ca_game)?;
+    let library_hashes = get_folder_hashes(library, ca_game)?;
+    let mut new_state = SyncState::default();
+    for (name, hash) in game_hashes.iter() {
+        if library_hashes.get(name) == Some(hash) {
+            new_state.files.insert(name.clone(), hash.clone());
+        }
+    }
+    for entry in report.iter() {
+        if entry.action == SyncAction::Skip || entry.result.is_err() {
+            if let Some(hash) = state.files.get(&entry.file_name) {
+                new_state
+                    .files
+                    .entry(entry.file_name.clone())
+                    .or_insert_with(|| hash.clone());
+            }
+        }
+    }
+    save_sync_state(state_file.as_path(), &new_state)?;
+    Ok((plan, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hashes(files: &[(&str, &str)]) -> BTreeMap<String, String> {
+        files
+            .iter()
+            .map(|(name, hash)| (name.to_string(), hash.to_string()))
+            .collect()
+    }
+
+    fn get_change<'a>(plan: &'a SyncPlan, name: &str) -> &'a SyncChange {
+        &plan
+            .items
+            .iter()
+            .find(|i| i.file_name == name)
+            .unwrap()
+            .change
+    }
+
+    #[test]
+    fn plans_by_content_and_last_sync() {
+        let state = SyncState {
+            files: hashes(&[
+                ("same.army_setup", "a"),
+                ("game edit.army_setup", "b"),
+                ("library edit.army_setup", "c"),
+                ("both edit.army_setup", "d"),
+                ("gone from game.army_setup", "e"),
+                ("gone from library.army_setup", "f"),
+            ]),
+        };
+        let game = hashes(&[
+            ("same.army_setup", "a"),
+            ("game edit.army_setup", "b2"),
+            ("library edit.army_setup", "c"),
+            ("both edit.army_setup", "d2"),
+            ("gone from library.army_setup", "f"),
+            ("new.army_setup", "g"),
+            ("renamed in game.army_setup", "h"),
+        ]);
+        let library = hashes(&[
+            ("same.army_setup", "a"),
+            ("game edit.army_setup", "b"),
+            ("library edit.army_setup", "c2"),
+            ("both edit.army_setup", "d3"),
+            ("gone from game.army_setup", "e"),
+            ("renamed in library.army_setup", "h"),
+        ]);
+        let plan = plan_sync(&game, &library, &state);
+        assert_eq!(plan.in_sync, 1);
+        assert_eq!(
+            get_change(&plan, "game edit.army_setup"),
+            &SyncChange::ModifiedInGame
+        );
+        assert_eq!(
+            get_change(&plan, "library edit.army_setup"),
+            &SyncChange::ModifiedInLibrary
+        );
+        assert_eq!(
+            get_change(&plan, "both edit.army_setup"),
+            &SyncChange::ModifiedBoth
+        );
+        assert_eq!(
+            get_change(&plan, "gone from game.army_setup"),
+            &SyncChange::DeletedInGame
+        );
+        assert_eq!(
+            get_change(&plan, "gone from library.army_setup"),
+            &SyncChange::DeletedInLibrary
+        );
+        assert_eq!(get_change(&plan, "new.army_setup"), &SyncChange::NewInGame);
+        assert_eq!(
+            get_change(&plan, "renamed in game.army_setup"),
+            &SyncChange::SameContentAs("renamed in library.army_setup".to_string())
+        );
+
+        //never synced, nothing is a delete
+        let plan = plan_sync(&game, &library, &SyncState::default());
+        assert_eq!(
+            get_change(&plan, "gone from game.army_setup"),
+            &SyncChange::NewInLibrary
+        );
+        assert_eq!(
+            get_change(&plan, "game edit.army_setup"),
+            &SyncChange::ModifiedBoth
+        );
+    }
+
+    #[test]
+    fn policy_picks_the_action() {
+        let library = Path::new("no such folder");
+        let conflict = SyncItem {
+            file_name: "GS vs HE.army_setup".to_string(),
+            change: SyncChange::ModifiedBoth,
+        };
+        let deleted = SyncItem {
+            file_name: "GS vs HE.army_setup".to_string(),
+            change: SyncChange::DeletedInGame,
+        };
+        let mut policy = SyncPolicy::default();
+        assert_eq!(
+            get_sync_action(&conflict, &policy, library),
+            SyncAction::Skip
+        );
+        assert_eq!(
+            get_sync_action(&deleted, &policy, library),
+            SyncAction::Skip
+        );
+
+        policy.on_conflict = SyncConflictPolicy::KeepBoth;
+        policy.apply_deletes = true;
+        assert_eq!(
+            get_sync_action(&conflict, &policy, library),
+            SyncAction::KeepBoth("GS vs HE (library).army_setup".to_string())
+        );
+        assert_eq!(
+            get_sync_action(&deleted, &policy, library),
+            SyncAction::DeleteFromLibrary
+        );
+    }
+}
@@ -0,0 +1,245 @@
+use crate::army_build::ArmyBuild;
+use crate::army_setups_folder::get_owaagh_trash_dir;
+use crate::ca_game::CaGame;
+use crate::library_store::{write_file_atomic, BuildMetadata};
+use crate::ymd_hms_dash_format;
+use chrono::{DateTime, Utc};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Deleting a build moves its file into the trash folder, the trash index remembers where it came
+// from & its metadata so restoring puts both back. Emptying the trash is the only real delete.
+const TRASH_INDEX_FILE: &str = "trash.json";
+const TRASH_NAME_FORMAT: &str = "%Y-%m-%d_%H-%M-%S";
+
+#[cfg_attr(
+    feature = "persistence",
+    derive(serde::Deserialize, serde::Serialize, Clone)
+)]
+pub struct TrashEntry {
+    pub trash_file: String, // file name in the trash folder
+    pub original_file: PathBuf,
+    pub ca_game: CaGame,
+    pub file_stem: String,
+    pub build_id: String,
+    #[serde(with = "ymd_hms_dash_format")]
+    pub deleted_on: DateTime<Utc>,
+    pub metadata: Option<BuildMetadata>, // None when it wasn't in the library index
+}
+
+#[cfg_attr(
+    feature = "persistence",
+    derive(serde::Deserialize, serde::Serialize, Clone)
+)]
+#[derive(Default)]
+struct TrashIndex {
+    entries: Vec<TrashEntry>,
+}
+
+fn load_trash_index(trash: &Path) -> Result<TrashIndex, String> {
+    let file = trash.join(TRASH_INDEX_FILE);
+    if !file.exists() {
+        return Ok(TrashIndex::default());
+    }
+    let s = match fs::read_to_string(file.as_path()) {
+        Ok(s) => s,
+        Err(e) => return Err(format!("Couldn't read {} {}", file.to_string_lossy(), e)),
+    };
+    match serde_json::from_str::<TrashIndex>(s.as_str()) {
+        Ok(index) => Ok(index),
+        Err(e) => Err(format!("Couldn't parse {} {}", file.to_string_lossy(), e)),
+    }
+}
+
+fn save_trash_index(trash: &Path, index: &TrashIndex) -> Result<(), String> {
+    let s = match serde_json::to_string_pretty(index) {
+        Ok(s) => s,
+        Err(e) => return Err(format!("Couldn't serialize the trash index {}", e)),
+    };
+    write_file_atomic(trash.join(TRASH_INDEX_FILE).as_path(), s.as_str())
+}
+
+//Renames when it can, the game's folder can be on another drive from appdata
+fn move_file(from: &Path, to: &Path) -> Result<(), String> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    fs::copy(from, to)
+        .and_then(|_| fs::remove_file(from))
+        .map_err(|e| {
+            let _ = fs::remove_file(to);
+            format!("Couldn't move {} {}", from.to_string_lossy(), e)
+        })
+}
+
+//"<name> (restored).<ext>", numbered past ones already there
+fn get_free_file(file: &Path) -> PathBuf {
+    if !file.exists() {
+        return file.to_path_buf();
+    }
+    let stem = file.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = file.extension().unwrap_or_default().to_string_lossy();
+    let mut free = file.with_file_name(format!("{} (restored).{}", stem, ext));
+    let mut n = 2;
+    while free.exists() {
+        free = file.with_file_name(format!("{} (restored {}).{}", stem, n, ext));
+        n += 1;
+    }
+    free
+}
+
+fn trash_build_in(
+    trash: &Path,
+    build: &ArmyBuild,
+    metadata: Option<BuildMetadata>,
+) -> Result<TrashEntry, String> {
+    let mut index = load_trash_index(trash)?;
+    let deleted_on = Utc::now();
+    let file_name = build
+        .file
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+    let stamp = deleted_on.format(TRASH_NAME_FORMAT);
+    let mut trash_file = format!("{}_{}", stamp, file_name);
+    let mut n = 2;
+    while trash.join(trash_file.as_str()).exists() {
+        trash_file = format!("{}_{}_{}", stamp, n, file_name);
+        n += 1;
+    }
+    move_file(
+        build.file.as_path(),
+        trash.join(trash_file.as_str()).as_path(),
+    )?;
+
+    let entry = TrashEntry {
+        trash_file,
+        original_file: build.file.clone(),
+        ca_game: build.ca_game.clone(),
+        file_stem: build.file_stem.clone(),
+        build_id: build.build_id.clone(),
+        deleted_on,
+        metadata,
+    };
+    index.entries.push(entry.clone());
+    if let Err(e) = save_trash_index(trash, &index) {
+        //put it back rather than lose track of it
+        let _ = move_file(
+            trash.join(entry.trash_file.as_str()).as_path(),
+            build.file.as_path(),
+        );
+        return Err(e);
+    }
+    Ok(entry)
+}
+
+//Back where it was deleted from, next to it if the name got taken since
+fn restore_from_trash_in(trash: &Path, trash_file: &str) -> Result<(TrashEntry, PathBuf), String> {
+    let mut index = load_trash_index(trash)?;
+    let indx = match index
+        .entries
+        .iter()
+        .position(|e| e.trash_file == trash_file)
+    {
+        Some(indx) => indx,
+        None => return Err(format!("{} ain't in the trash", trash_file)),
+    };
+    let entry = index.entries[indx].clone();
+    if let Some(parent) = entry.original_file.parent() {
+        if let Err(e) = fs::create_dir_all(parent) {
+            return Err(format!("Couldn't make {} {}", parent.to_string_lossy(), e));
+        }
+    }
+    let to = get_free_file(entry.original_file.as_path());
+    move_file(trash.join(trash_file).as_path(), to.as_path())?;
+    index.entries.remove(indx);
+    save_trash_index(trash, &index)?;
+    Ok((entry, to))
+}
+
+fn empty_trash_in(trash: &Path) -> Result<usize, String> {
+    let mut index = load_trash_index(trash)?;
+    let mut n_removed = 0;
+    while let Some(entry) = index.entries.pop() {
+        let file = trash.join(entry.trash_file.as_str());
+        if let Err(e) = fs::remove_file(file.as_path()) {
+            if file.exists() {
+                index.entries.push(entry);
+                save_trash_index(trash, &index)?;
+                return Err(format!("Couldn't delete {} {}", file.to_string_lossy(), e));
+            }
+        }
+        n_removed += 1;
+    }
+    save_trash_index(trash, &index)?;
+    Ok(n_removed)
+}
+
+pub fn trash_build(
+    build: &ArmyBuild,
+    metadata: Option<BuildMetadata>,
+) -> Result<TrashEntry, String> {
+    trash_build_in(get_owaagh_trash_dir()?.as_path(), build, metadata)
+}
+
+//Newest first
+pub fn list_trash() -> Result<Vec<TrashEntry>, String> {
+    let mut entries = load_trash_index(get_owaagh_trash_dir()?.as_path())?.entries;
+    entries.reverse();
+    Ok(entries)
+}
+
+pub fn restore_from_trash(trash_file: &str) -> Result<(TrashEntry, PathBuf), String> {
+    restore_from_trash_in(get_owaagh_trash_dir()?.as_path(), trash_file)
+}
+
+pub fn empty_trash() -> Result<usize, String> {
+    empty_trash_in(get_owaagh_trash_dir()?.as_path())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trash_restores_next_to_a_taken_name() {
+        let dir = std::env::temp_dir().join(format!("owaagh_trash_{}", std::process::id()));
+        let _ = fs::remove_dir_all(dir.as_path());
+        let trash = dir.join("trash");
+        let library = dir.join("army_setups");
+        fs::create_dir_all(trash.as_path()).unwrap();
+        fs::create_dir_all(library.as_path()).unwrap();
+        let file = library.join("GS vs HE.army_setup");
+        fs::write(file.as_path(), b"gobbos").unwrap();
+        let build = ArmyBuild {
+            file: file.clone(),
+            file_stem: "GS vs HE".to_string(),
+            ..ArmyBuild::default()
+        };
+
+        let first = trash_build_in(trash.as_path(), &build, None).unwrap();
+        assert!(!file.exists());
+        fs::write(file.as_path(), b"more gobbos").unwrap();
+        let second = trash_build_in(trash.as_path(), &build, None).unwrap();
+        assert_ne!(first.trash_file, second.trash_file);
+        assert_eq!(load_trash_index(trash.as_path()).unwrap().entries.len(), 2);
+
+        let (entry, restored) =
+            restore_from_trash_in(trash.as_path(), first.trash_file.as_str()).unwrap();
+        assert_eq!(entry.original_file, file);
+        assert_eq!(restored, file);
+        assert_eq!(fs::read(file.as_path()).unwrap(), b"gobbos");
+
+        //the name's taken now so the other one comes back beside it
+        let (_, restored) =
+            restore_from_trash_in(trash.as_path(), second.trash_file.as_str()).unwrap();
+        assert_eq!(restored, library.join("GS vs HE (restored).army_setup"));
+
+        trash_build_in(trash.as_path(), &build, None).unwrap();
+        assert_eq!(empty_trash_in(trash.as_path()), Ok(1));
+        let left = load_trash_index(trash.as_path()).unwrap().entries.len();
+        fs::remove_dir_all(dir.as_path()).unwrap();
+        assert_eq!(left, 0);
+    }
+}
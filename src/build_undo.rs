@@ -0,0 +1,105 @@
+use crate::army_build::ArmyBuild;
+use crate::build_trash::TrashEntry;
+use std::collections::VecDeque;
+
+// The last few destructive things done to the library, newest on top. Only what's needed to take
+// each one back is kept, the manager does the undoing.
+pub const UNDO_LIMIT: usize = 20;
+
+#[cfg_attr(feature = "persistence", derive(Clone))]
+pub enum UndoAction {
+    Delete(Box<TrashEntry>),
+    Rename(Vec<(ArmyBuild, ArmyBuild)>), // before & after, a rename plan can do lots at once
+    MetadataEdit(Box<(ArmyBuild, ArmyBuild)>), // before & after
+}
+
+impl UndoAction {
+    pub fn get_title(&self) -> String {
+        match self {
+            UndoAction::Delete(entry) => format!("Delete {}", entry.file_stem),
+            UndoAction::Rename(renames) if renames.len() == 1 => {
+                format!("Rename {}", renames[0].0.file_stem)
+            }
+            UndoAction::Rename(renames) => format!("Rename {} Builds", renames.len()),
+            UndoAction::MetadataEdit(edit) => format!("Edit {}", edit.0.file_stem),
+        }
+    }
+}
+
+#[cfg_attr(feature = "persistence", derive(Clone))]
+pub struct UndoStack {
+    actions: VecDeque<UndoAction>,
+    limit: usize,
+}
+
+impl Default for UndoStack {
+    fn default() -> Self {
+        Self::new(UNDO_LIMIT)
+    }
+}
+
+impl UndoStack {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            actions: VecDeque::new(),
+            limit: limit.max(1),
+        }
+    }
+
+    //Oldest falls off past the limit
+    pub fn push(&mut self, action: UndoAction) {
+        self.actions.push_back(action);
+        while self.actions.len() > self.limit {
+            self.actions.pop_front();
+        }
+    }
+
+    //Drops the ones that can't be undone any more, like deletes already restored or emptied
+    pub fn retain<F: FnMut(&UndoAction) -> bool>(&mut self, keep: F) {
+        self.actions.retain(keep);
+    }
+
+    pub fn pop(&mut self) -> Option<UndoAction> {
+        self.actions.pop_back()
+    }
+
+    pub fn peek(&self) -> Option<&UndoAction> {
+        self.actions.back()
+    }
+
+    pub fn len(&self) -> usize {
+        self.actions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit(name: &str) -> UndoAction {
+        let build = ArmyBuild {
+            file_stem: name.to_string(),
+            ..ArmyBuild::default()
+        };
+        UndoAction::MetadataEdit(Box::new((build.clone(), build)))
+    }
+
+    #[test]
+    fn keeps_the_newest_up_to_the_limit() {
+        let mut stack = UndoStack::new(2);
+        stack.push(edit("one"));
+        stack.push(edit("two"));
+        stack.push(edit("three"));
+        assert_eq!(stack.len(), 2);
+        assert_eq!(stack.peek().unwrap().get_title(), "Edit three");
+        assert_eq!(stack.pop().unwrap().get_title(), "Edit three");
+        stack.push(edit("four"));
+        stack.retain(|a| a.get_title() != "Edit four");
+        assert_eq!(stack.pop().unwrap().get_title(), "Edit two");
+        assert!(stack.pop().is_none());
+    }
+}
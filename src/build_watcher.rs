@@ -0,0 +1,307 @@
+use crate::army_build::ArmyBuild;
+use crate::army_setups_folder::{
+    apply_library_metadata, get_owaagh_army_setups_dir, is_ca_game_setup_path, load_army_build,
+    load_army_builds,
+};
+use crate::ca_game::{get_ca_game_army_setups_folder, CaGame};
+use eframe::epi::RepaintSignal;
+use notify::{DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::Arc;
+use std::time::Duration;
+
+// Watches the game's army_setups folder & the library folder so builds saved in game, or files
+// moved around by hand, show up without a reload. notify debounces on its own thread, ours reads
+// & parses the changed files then pokes the ui so it repaints to pick up the finished builds
+const DEBOUNCE_MS: u64 = 500;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BuildChange {
+    Upserted(PathBuf), // new or written to, read it again
+    Removed(PathBuf),
+    Rescan, // notify lost track, reload the lot
+}
+
+impl BuildChange {
+    pub fn get_file(&self) -> Option<&PathBuf> {
+        match self {
+            BuildChange::Upserted(f) | BuildChange::Removed(f) => Some(f),
+            BuildChange::Rescan => None,
+        }
+    }
+}
+
+//Notices come before the debounced event for the same file, only the debounced ones count
+pub fn get_build_changes(event: DebouncedEvent, ca_game: &CaGame) -> Vec<BuildChange> {
+    let is_setup = |f: &PathBuf| is_ca_game_setup_path(f.as_path(), ca_game);
+    match event {
+        DebouncedEvent::Create(f) | DebouncedEvent::Write(f) if is_setup(&f) => {
+            vec![BuildChange::Upserted(f)]
+        }
+        DebouncedEvent::Remove(f) if is_setup(&f) => vec![BuildChange::Removed(f)],
+        DebouncedEvent::Rename(from, to) => {
+            let mut changes = vec![];
+            if is_setup(&from) {
+                changes.push(BuildChange::Removed(from));
+            }
+            if is_setup(&to) {
+                changes.push(BuildChange::Upserted(to));
+            }
+            changes
+        }
+        DebouncedEvent::Rescan => vec![BuildChange::Rescan],
+        DebouncedEvent::Error(e, f) => {
+            println!("build watcher {} {:?}", e, f);
+            vec![]
+        }
+        _ => vec![],
+    }
+}
+
+//The game's library folder & its army_setups folder when it's installed, library first
+pub fn get_watched_folders(ca_game: &CaGame) -> Vec<PathBuf> {
+    let mut folders = vec![];
+    if let Ok(p) = get_owaagh_army_setups_dir(ca_game) {
+        folders.push(p);
+    }
+    if let Ok(p) = get_ca_game_army_setups_folder(ca_game.clone()) {
+        folders.push(p);
+    }
+    folders.into_iter().filter(|p| p.is_dir()).collect()
+}
+
+//A change with the file already read, what the manager puts straight into its builds
+pub enum BuildUpdate {
+    Upserted(ArmyBuild),
+    // builds collapse by content, another file with the same content takes the removed one's place
+    Removed {
+        file: PathBuf,
+        same_content: Option<ArmyBuild>,
+    },
+    Reloaded(Vec<ArmyBuild>), // library first
+}
+
+// Every build in the watched folders as of the last change, kept on the watcher thread so a
+// removed file's content can be found elsewhere without reading the folders again
+struct WatchedBuilds {
+    ca_game: CaGame,
+    folders: Vec<PathBuf>, // library first, its copies win for the same content
+    builds: HashMap<PathBuf, ArmyBuild>,
+}
+
+impl WatchedBuilds {
+    fn load(ca_game: &CaGame, folders: Vec<PathBuf>) -> Self {
+        let mut watched = Self {
+            ca_game: ca_game.clone(),
+            folders,
+            builds: HashMap::new(),
+        };
+        watched.reload();
+        watched
+    }
+
+    fn reload(&mut self) {
+        self.builds = self
+            .folders
+            .iter()
+            .flat_map(|f| load_army_builds(f.to_string_lossy().as_ref(), &self.ca_game))
+            .map(|b| (b.file.clone(), b))
+            .collect();
+    }
+
+    fn get_folder_rank(&self, file: &Path) -> usize {
+        self.folders
+            .iter()
+            .position(|f| file.parent() == Some(f.as_path()))
+            .unwrap_or(self.folders.len())
+    }
+
+    fn get_sorted_builds(&self) -> Vec<ArmyBuild> {
+        let mut builds: Vec<ArmyBuild> = self.builds.values().cloned().collect();
+        builds.sort_by(|a, b| {
+            let rank = |b: &ArmyBuild| self.get_folder_rank(b.file.as_path());
+            rank(a).cmp(&rank(b)).then_with(|| a.file.cmp(&b.file))
+        });
+        builds
+    }
+
+    //Metadata is read again, it could have been edited since the file was
+    fn get_same_content(&self, build: &ArmyBuild) -> Option<ArmyBuild> {
+        let mut same = vec![self.get_sorted_builds().into_iter().find(|b| b == build)?];
+        apply_library_metadata(&mut same, &self.ca_game);
+        same.pop()
+    }
+
+    fn apply(&mut self, change: BuildChange) -> Vec<BuildUpdate> {
+        match change {
+            BuildChange::Upserted(file) => {
+                let build = match load_army_build(file.as_path(), &self.ca_game) {
+                    Some(b) => b,
+                    None => return self.apply(BuildChange::Removed(file)), // gone again or unreadable
+                };
+                //written over with new content, the old content can still be in another file
+                let is_new_content = matches!(self.builds.get(&file), Some(old) if *old != build);
+                let mut updates = if is_new_content {
+                    self.apply(BuildChange::Removed(file.clone()))
+                } else {
+                    vec![]
+                };
+                self.builds.insert(file, build.clone());
+                updates.push(BuildUpdate::Upserted(build));
+                updates
+            }
+            BuildChange::Removed(file) => {
+                let same_content = self
+                    .builds
+                    .remove(&file)
+                    .and_then(|old| self.get_same_content(&old));
+                vec![BuildUpdate::Removed { file, same_content }]
+            }
+            BuildChange::Rescan => {
+                self.reload();
+                vec![BuildUpdate::Reloaded(self.get_sorted_builds())]
+            }
+        }
+    }
+}
+
+pub struct BuildWatcher {
+    _watcher: RecommendedWatcher, // dropping it stops the events & ends the forwarding thread
+    updates: Receiver<BuildUpdate>,
+}
+
+impl BuildWatcher {
+    pub fn new(ca_game: &CaGame, repaint: Arc<dyn RepaintSignal>) -> Result<Self, String> {
+        let folders = get_watched_folders(ca_game);
+        if folders.is_empty() {
+            return Err("No army_setups folders to watch".to_string());
+        }
+        let (event_tx, event_rx) = channel();
+        let mut watcher = notify::watcher(event_tx, Duration::from_millis(DEBOUNCE_MS))
+            .map_err(|e| format!("Couldn't start the build watcher {}", e))?;
+        for f in folders.iter() {
+            watcher
+                .watch(f.as_path(), RecursiveMode::NonRecursive)
+                .map_err(|e| format!("Couldn't watch {} {}", f.to_string_lossy(), e))?;
+        }
+
+        let (update_tx, updates) = channel();
+        let thread_game = ca_game.clone();
+        std::thread::spawn(move || {
+            //events queue up while the folders are first read
+            let mut watched = WatchedBuilds::load(&thread_game, folders);
+            for event in event_rx.iter() {
+                let changes = get_build_changes(event, &thread_game);
+                if changes.is_empty() {
+                    continue;
+                }
+                for c in changes {
+                    for u in watched.apply(c) {
+                        if update_tx.send(u).is_err() {
+                            return;
+                        }
+                    }
+                }
+                repaint.request_repaint();
+            }
+        });
+
+        Ok(Self {
+            _watcher: watcher,
+            updates,
+        })
+    }
+
+    //Whatever came in since the last poll, never waits
+    pub fn poll(&self) -> Vec<BuildUpdate> {
+        self.updates.try_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_setup_files_make_changes() {
+        let setup = PathBuf::from("army_setups/GS vs HE.army_setup");
+        let other = PathBuf::from("army_setups/GS vs HE.army_setup.tmp");
+        let game = CaGame::Warhammer2;
+        assert_eq!(
+            get_build_changes(DebouncedEvent::Write(setup.clone()), &game),
+            vec![BuildChange::Upserted(setup.clone())]
+        );
+        assert!(get_build_changes(DebouncedEvent::Create(other.clone()), &game).is_empty());
+        assert!(get_build_changes(DebouncedEvent::NoticeRemove(setup.clone()), &game).is_empty());
+        assert!(get_build_changes(
+            DebouncedEvent::Write(PathBuf::from("GS.battle_preferences")),
+            &game
+        )
+        .is_empty());
+
+        //saving over a temp file comes through as a rename
+        assert_eq!(
+            get_build_changes(DebouncedEvent::Rename(other, setup.clone()), &game),
+            vec![BuildChange::Upserted(setup.clone())]
+        );
+        let renamed = PathBuf::from("army_setups/GS vs HE 12400.army_setup");
+        assert_eq!(
+            get_build_changes(
+                DebouncedEvent::Rename(setup.clone(), renamed.clone()),
+                &game
+            ),
+            vec![BuildChange::Removed(setup), BuildChange::Upserted(renamed)]
+        );
+    }
+
+    #[test]
+    fn removed_content_comes_back_from_the_other_folder() {
+        let dir = std::env::temp_dir().join(format!("owaagh_watcher_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(dir.as_path());
+        let library = dir.join("library");
+        let game = dir.join("game");
+        std::fs::create_dir_all(library.as_path()).unwrap();
+        std::fs::create_dir_all(game.as_path()).unwrap();
+        let lib_file = library.join("GS vs HE.army_setup");
+        let game_file = game.join("GS vs HE.army_setup");
+        std::fs::write(lib_file.as_path(), b"gobbos").unwrap();
+        std::fs::write(game_file.as_path(), b"gobbos").unwrap();
+        std::fs::write(game.join("HE vs GS.army_setup"), b"elves").unwrap();
+
+        let mut watched =
+            WatchedBuilds::load(&CaGame::Warhammer2, vec![library.clone(), game.clone()]);
+        let files =
+            |builds: &[ArmyBuild]| builds.iter().map(|b| b.file.clone()).collect::<Vec<_>>();
+        assert_eq!(files(&watched.get_sorted_builds())[0], lib_file);
+
+        //written over, the old content is still in the game's folder
+        std::fs::write(lib_file.as_path(), b"more gobbos").unwrap();
+        let updates = watched.apply(BuildChange::Upserted(lib_file.clone()));
+        assert_eq!(updates.len(), 2);
+        assert!(matches!(&updates[0],
+            BuildUpdate::Removed { file, same_content: Some(b) } if *file == lib_file && b.file == game_file));
+        assert!(matches!(&updates[1], BuildUpdate::Upserted(b) if b.file == lib_file));
+
+        std::fs::remove_file(game_file.as_path()).unwrap();
+        let updates = watched.apply(BuildChange::Removed(game_file.clone()));
+        assert!(matches!(
+            &updates[0],
+            BuildUpdate::Removed {
+                same_content: None,
+                ..
+            }
+        ));
+
+        let updates = watched.apply(BuildChange::Rescan);
+        std::fs::remove_dir_all(dir.as_path()).unwrap();
+        match &updates[0] {
+            BuildUpdate::Reloaded(builds) => assert_eq!(
+                files(builds),
+                vec![lib_file, game.join("HE vs GS.army_setup")]
+            ),
+            _ => panic!("expected a reload"),
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use crate::army_setups_manager::ArmySetupsManager;
+use crate::ca_game_discovery::get_ca_game_army_setups_candidates;
 use crate::central_panel_state::AppState;
 use eframe::egui;
 use eframe::egui::{Color32, Ui};
@@ -32,6 +33,7 @@ pub enum CaGame {
     ThronesOfBritannia,
     Warhammer,
     Warhammer2,
+    Warhammer3,
 }
 
 pub fn is_ca_game_folder(folder_str: &str, ca_game: &CaGame) -> bool {
@@ -57,21 +59,27 @@ fn get_subdir_str_ca_game_map() -> HashMap<String, CaGame> {
 
 pub fn get_ca_game_from_folder_name(folder_str: &str) -> CaGame {
     let subdir_str_ca_game_map = get_subdir_str_ca_game_map();
-    let p = PathBuf::from(folder_str);
-    let ca_game = CaGame::Warhammer2;
-    let game_subdir = get_ca_game_subfolder(&ca_game);
-    for _d in p.iter() {
-        if subdir_str_ca_game_map.contains_key(game_subdir.as_str()) {
-            return subdir_str_ca_game_map
-                .get(game_subdir.as_str())
-                .unwrap()
-                .clone();
+    //split by hand so windows style paths still map when running elsewhere
+    for d in folder_str.split(['/', '\\']) {
+        if let Some(ca_game) = subdir_str_ca_game_map.get(d) {
+            return ca_game.clone();
         }
     }
 
     CaGame::Warhammer2
 }
 
+//"Warhammer2", "warhammer 2" & "Warhammer 2" all find Warhammer2
+pub fn get_ca_game_from_name(name: &str) -> Option<CaGame> {
+    let squash = |s: &str| s.replace(' ', "").to_ascii_lowercase();
+    let name = squash(name);
+    CaGame::into_enum_iter().find(|ca_game| {
+        let subfolder = get_ca_game_subfolder(ca_game);
+        (subfolder != "todo" && squash(subfolder.as_str()) == name)
+            || squash(get_ca_game_title(ca_game).as_str()) == name
+    })
+}
+
 pub fn get_ca_game_subfolder(ca_game: &CaGame) -> String {
     let game_subdir = match ca_game {
         CaGame::Attila => "todo",
@@ -86,6 +94,7 @@ pub fn get_ca_game_subfolder(ca_game: &CaGame) -> String {
         CaGame::ThronesOfBritannia => "todo",
         CaGame::Warhammer => "Warhammer",
         CaGame::Warhammer2 => "Warhammer2",
+        CaGame::Warhammer3 => "Warhammer3",
     };
     String::from(game_subdir)
 }
@@ -104,27 +113,17 @@ pub fn get_ca_game_title(ca_game: &CaGame) -> String {
         CaGame::ThronesOfBritannia => "Thrones of Britannia",
         CaGame::Warhammer => "Warhammer",
         CaGame::Warhammer2 => "Warhammer 2",
+        CaGame::Warhammer3 => "Warhammer 3",
     };
     String::from(game_subdir)
 }
 
-//
+//First discovered army setups folder, falls back to the windows layout when nothing exists yet
 pub fn get_ca_game_army_setups_folder(ca_game: CaGame) -> Result<PathBuf, String> {
-    let army_setups_subdir = match ca_game.clone() {
-        CaGame::Empire => "battle_preferences",
-        _ => "army_setups",
-    };
-
-    let game_subdir = get_ca_game_subfolder(&ca_game);
-
-    if let Some(mut p) = dirs::home_dir() {
-        p = p.join("AppData\\Roaming\\The Creative Assembly");
-        p = p.join(game_subdir);
-        p = p.join(army_setups_subdir);
-        return Ok(p);
+    match get_ca_game_army_setups_candidates(&ca_game).into_iter().next() {
+        Some(candidate) => Ok(candidate.folder),
+        None => Err("dirs::home_dir() None".to_string()),
     }
-    let err = format!("dirs::home_dir() None",);
-    Err(err)
 }
 
 pub fn get_ca_game_army_setup_ext(ca_game: CaGame) -> String {
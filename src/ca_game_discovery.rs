@@ -0,0 +1,327 @@
+use crate::ca_game::{get_ca_game_subfolder, CaGame};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Every layout ends in the same Windows style roaming folder, whether it's real windows,
+// a proton prefix or a feral VFS
+const CA_ROAMING_COMPONENTS: [&str; 3] = ["AppData", "Roaming", "The Creative Assembly"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CandidateReason {
+    Windows,
+    Proton { steam_library: PathBuf, app_id: u32 },
+    FeralNativeLinux,
+}
+
+pub fn get_candidate_reason_string(reason: &CandidateReason) -> String {
+    match reason {
+        CandidateReason::Windows => "Windows AppData".to_string(),
+        CandidateReason::Proton {
+            steam_library,
+            app_id,
+        } => format!(
+            "Steam Proton prefix {} in {}",
+            app_id,
+            steam_library.to_string_lossy()
+        ),
+        CandidateReason::FeralNativeLinux => "Native Linux port".to_string(),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ArmySetupsCandidate {
+    pub folder: PathBuf,
+    pub reason: CandidateReason,
+    pub exists: bool,
+}
+
+impl ArmySetupsCandidate {
+    fn new(folder: PathBuf, reason: CandidateReason) -> Self {
+        let exists = folder.is_dir();
+        Self {
+            folder,
+            reason,
+            exists,
+        }
+    }
+
+    pub fn get_display_string(&self) -> String {
+        let found = if self.exists { "" } else { " (not found)" };
+        format!(
+            "{} [{}]{}",
+            self.folder.to_string_lossy(),
+            get_candidate_reason_string(&self.reason),
+            found
+        )
+    }
+}
+
+pub fn get_ca_game_steam_app_id(ca_game: &CaGame) -> Option<u32> {
+    match ca_game {
+        CaGame::Attila => Some(325610),
+        CaGame::Empire => Some(10500),
+        CaGame::Medieval2 => Some(4700),
+        CaGame::Napoleon => Some(34030),
+        CaGame::Rome => Some(4760),
+        CaGame::Rome2 => Some(214950),
+        CaGame::RomeRemastered => Some(885970),
+        CaGame::Shogun2 => Some(201270),
+        CaGame::ThreeKingdoms => Some(779340),
+        CaGame::ThronesOfBritannia => Some(712100),
+        CaGame::Warhammer => Some(364360),
+        CaGame::Warhammer2 => Some(594570),
+        CaGame::Warhammer3 => Some(1142710),
+    }
+}
+
+// Folder feral-interactive keeps user data in for the native ports
+pub fn get_ca_game_feral_folder(ca_game: &CaGame) -> Option<&'static str> {
+    match ca_game {
+        CaGame::Attila => Some("Total War ATTILA"),
+        CaGame::Empire => Some("Empire Total War"),
+        CaGame::Medieval2 => Some("Medieval II Total War"),
+        CaGame::Napoleon => Some("Napoleon Total War"),
+        CaGame::Rome2 => Some("Total War ROME II"),
+        CaGame::RomeRemastered => Some("Total War ROME REMASTERED"),
+        CaGame::Shogun2 => Some("Total War SHOGUN 2"),
+        CaGame::ThreeKingdoms => Some("Total War THREE KINGDOMS"),
+        CaGame::ThronesOfBritannia => Some("Total War Saga THRONES OF BRITANNIA"),
+        CaGame::Warhammer => Some("Total War WARHAMMER"),
+        CaGame::Warhammer2 => Some("Total War WARHAMMER II"),
+        CaGame::Rome | CaGame::Warhammer3 => None,
+    }
+}
+
+pub fn get_ca_game_army_setups_subdir(ca_game: &CaGame) -> &'static str {
+    match ca_game {
+        CaGame::Empire => "battle_preferences",
+        _ => "army_setups",
+    }
+}
+
+// Where the game keeps its replays, same for every game so far
+pub const CA_GAME_REPLAYS_SUBDIR: &str = "replays";
+
+fn join_ca_game_roaming(root: PathBuf, ca_game: &CaGame, subdir: &str) -> PathBuf {
+    let mut p = root;
+    for c in CA_ROAMING_COMPONENTS.iter() {
+        p = p.join(c);
+    }
+    p.join(get_ca_game_subfolder(ca_game)).join(subdir)
+}
+
+// Reads the extra library locations out of steam's libraryfolders.vdf. Newer ones have a "path"
+// entry in each numbered block, older ones had the numbered entries as the paths themselves
+fn parse_library_folders_vdf(vdf: &str) -> Vec<PathBuf> {
+    let mut libraries = vec![];
+    let mut depth = 0;
+    for line in vdf.lines() {
+        match line.trim() {
+            "{" => depth += 1,
+            "}" => depth -= 1,
+            _ => {}
+        }
+        let parts: Vec<&str> = line.split('"').collect();
+        // "path"		"/mnt/games/SteamLibrary" splits into 5 parts
+        if parts.len() < 4 {
+            continue;
+        }
+        let is_old_library =
+            depth == 1 && !parts[1].is_empty() && parts[1].bytes().all(|c| c.is_ascii_digit());
+        if parts[1] == "path" || is_old_library {
+            libraries.push(PathBuf::from(parts[3].replace("\\\\", "\\")));
+        }
+    }
+    libraries
+}
+
+pub fn get_steam_libraries() -> Vec<PathBuf> {
+    let mut roots = vec![];
+    if let Some(home) = dirs::home_dir() {
+        roots.push(home.join(".steam").join("steam"));
+        roots.push(home.join(".steam").join("root"));
+        roots.push(
+            home.join(".var")
+                .join("app")
+                .join("com.valvesoftware.Steam")
+                .join(".local")
+                .join("share")
+                .join("Steam"),
+        );
+    }
+    if let Some(data) = dirs::data_dir() {
+        roots.push(data.join("Steam"));
+    }
+
+    let mut libraries: Vec<PathBuf> = vec![];
+    let mut push_unique = |p: PathBuf| {
+        let p = fs::canonicalize(&p).unwrap_or(p);
+        if p.is_dir() && !libraries.contains(&p) {
+            libraries.push(p);
+        }
+    };
+    for root in roots {
+        let vdf = root.join("steamapps").join("libraryfolders.vdf");
+        if let Ok(s) = fs::read_to_string(vdf) {
+            for library in parse_library_folders_vdf(s.as_str()) {
+                push_unique(library);
+            }
+        }
+        push_unique(root);
+    }
+    libraries
+}
+
+fn get_proton_candidates(
+    ca_game: &CaGame,
+    subdir: &str,
+    steam_libraries: &[PathBuf],
+) -> Vec<ArmySetupsCandidate> {
+    let app_id = match get_ca_game_steam_app_id(ca_game) {
+        Some(id) => id,
+        None => return vec![],
+    };
+    steam_libraries
+        .iter()
+        .map(|library| {
+            let prefix_user = library
+                .join("steamapps")
+                .join("compatdata")
+                .join(app_id.to_string())
+                .join("pfx")
+                .join("drive_c")
+                .join("users")
+                .join("steamuser");
+            ArmySetupsCandidate::new(
+                join_ca_game_roaming(prefix_user, ca_game, subdir),
+                CandidateReason::Proton {
+                    steam_library: library.clone(),
+                    app_id,
+                },
+            )
+        })
+        .collect()
+}
+
+fn get_feral_candidate(ca_game: &CaGame, subdir: &str) -> Option<ArmySetupsCandidate> {
+    let feral_folder = get_ca_game_feral_folder(ca_game)?;
+    let data = dirs::data_dir()?;
+    let vfs_user = data
+        .join("feral-interactive")
+        .join(feral_folder)
+        .join("VFS")
+        .join("User");
+    Some(ArmySetupsCandidate::new(
+        join_ca_game_roaming(vfs_user, ca_game, subdir),
+        CandidateReason::FeralNativeLinux,
+    ))
+}
+
+fn get_windows_candidate(ca_game: &CaGame, subdir: &str) -> Option<ArmySetupsCandidate> {
+    let home = dirs::home_dir()?;
+    Some(ArmySetupsCandidate::new(
+        join_ca_game_roaming(home, ca_game, subdir),
+        CandidateReason::Windows,
+    ))
+}
+
+//All the places a game may keep a roaming subfolder, existing folders first, windows layout always last
+pub fn get_ca_game_folder_candidates(ca_game: &CaGame, subdir: &str) -> Vec<ArmySetupsCandidate> {
+    let mut candidates = vec![];
+    if cfg!(not(windows)) {
+        candidates.extend(get_proton_candidates(
+            ca_game,
+            subdir,
+            &get_steam_libraries(),
+        ));
+        candidates.extend(get_feral_candidate(ca_game, subdir));
+    }
+    candidates.extend(get_windows_candidate(ca_game, subdir));
+    //stable so windows stays the last of the missing
+    candidates.sort_by_key(|c| !c.exists);
+    candidates
+}
+
+pub fn get_ca_game_army_setups_candidates(ca_game: &CaGame) -> Vec<ArmySetupsCandidate> {
+    get_ca_game_folder_candidates(ca_game, get_ca_game_army_setups_subdir(ca_game))
+}
+
+pub fn get_ca_game_replays_candidates(ca_game: &CaGame) -> Vec<ArmySetupsCandidate> {
+    get_ca_game_folder_candidates(ca_game, CA_GAME_REPLAYS_SUBDIR)
+}
+
+//True if the folder ends with The Creative Assembly/<Game>/<army_setups>, regardless of path separator
+pub fn is_ca_game_army_setups_path(folder: &Path, ca_game: &CaGame) -> bool {
+    let components: Vec<String> = folder
+        .to_string_lossy()
+        .split(['/', '\\'])
+        .filter(|c| !c.is_empty())
+        .map(|c| c.to_string())
+        .collect();
+    let expected = [
+        CA_ROAMING_COMPONENTS[2].to_string(),
+        get_ca_game_subfolder(ca_game),
+        get_ca_game_army_setups_subdir(ca_game).to_string(),
+    ];
+    components.ends_with(&expected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_old_flat_library_folders() {
+        let vdf = r#""LibraryFolders"
+{
+	"TimeNextStatsReport"		"1621154422"
+	"ContentStatsID"		"-4532423876463535187"
+	"1"		"D:\\SteamLibrary"
+	"2"		"/mnt/games/SteamLibrary"
+}
+"#;
+        assert_eq!(
+            parse_library_folders_vdf(vdf),
+            vec![
+                PathBuf::from("D:\\SteamLibrary"),
+                PathBuf::from("/mnt/games/SteamLibrary")
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_new_path_library_folders() {
+        let vdf = r#""libraryfolders"
+{
+	"contentstatsid"		"-4532423876463535187"
+	"0"
+	{
+		"path"		"C:\\Program Files (x86)\\Steam"
+		"label"		""
+		"contentid"		"5823466374569432451"
+		"totalsize"		"0"
+		"apps"
+		{
+			"228980"		"398615327"
+		}
+	}
+	"1"
+	{
+		"path"		"/home/felkon/.local/share/Steam"
+		"apps"
+		{
+			"594570"		"67391527424"
+		}
+	}
+}
+"#;
+        assert_eq!(
+            parse_library_folders_vdf(vdf),
+            vec![
+                PathBuf::from("C:\\Program Files (x86)\\Steam"),
+                PathBuf::from("/home/felkon/.local/share/Steam")
+            ]
+        );
+        assert!(parse_library_folders_vdf("").is_empty());
+    }
+}
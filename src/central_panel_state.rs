@@ -20,7 +20,7 @@ pub enum CentralPanelState {
     GameSelection,
     BuildManager,
     TierList,
-    Replays,
+    Stats,
     Resources,
     Acknowledgements,
 }
@@ -52,14 +52,14 @@ impl AppState {
             CentralPanelState::BuildManager => "Build Boss".to_string(),
             CentralPanelState::TierList => "Tier Lists".to_string(),
             CentralPanelState::Resources => "Resources".to_string(),
-            CentralPanelState::Replays => "Replays".to_string(),
+            CentralPanelState::Stats => "Stats".to_string(),
             CentralPanelState::OwaaghSettings => "Settings".to_string(),
             CentralPanelState::Acknowledgements => "Acknowledgments".to_string(),
         }
     }
 
     pub fn side_bar_ui(&mut self, ui: &mut Ui, ctx: &egui::CtxRef) {
-        let supported_states = vec![CentralPanelState::GameSelection, CentralPanelState::BuildManager, CentralPanelState::TierList, CentralPanelState::Resources];
+        let supported_states = vec![CentralPanelState::GameSelection, CentralPanelState::BuildManager, CentralPanelState::TierList, CentralPanelState::Stats, CentralPanelState::Resources];
         //for central_panel_state in CentralPanelState::into_enum_iter() {
         for central_panel_state in supported_states {
             if ui
@@ -0,0 +1,138 @@
+use crate::ca_game::CaGame;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+// Per game faction list, edit the data file to add a game or faction, no code needed
+const FACTIONS_DATA: &str = include_str!("../assets/factions.json");
+
+//Names that aren't a faction but can sit where one goes
+pub const ALL_FACTIONS: &str = "All";
+pub const UNKNOWN_FACTION: &str = "Unknown";
+
+fn default_opponent() -> bool {
+    true
+}
+
+#[derive(serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct Faction {
+    pub id: String, // short abbreviation, "GS"
+    pub name: String,
+    #[serde(default)]
+    pub key_codes: Vec<String>, // bits of the game's faction keys, "_grn_"
+    #[serde(default)]
+    pub faction_key: Option<String>, // what to write into an army setup
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    #[serde(default = "default_opponent")]
+    pub opponent: bool, // shows up in the vs dropdown
+}
+
+fn squash_faction_str(s: &str) -> String {
+    s.chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_ascii_lowercase()
+}
+
+impl Faction {
+    //id, name & aliases, lower case
+    pub fn get_match_names(&self) -> Vec<String> {
+        let mut names = vec![self.id.to_ascii_lowercase(), self.name.to_ascii_lowercase()];
+        names.extend(self.aliases.iter().map(|a| a.to_ascii_lowercase()));
+        names
+    }
+
+    //Ignores case & spaces, so "gs", "Greenskins" or "woodelves" all hit
+    pub fn is_called(&self, faction_str: &str) -> bool {
+        let wanted = squash_faction_str(faction_str);
+        self.get_match_names()
+            .iter()
+            .any(|n| squash_faction_str(n) == wanted)
+    }
+
+    pub fn is_key(&self, faction_key: &str) -> bool {
+        self.key_codes.iter().any(|c| faction_key.contains(c.as_str()))
+            || self.faction_key.as_deref() == Some(faction_key)
+    }
+}
+
+pub struct FactionRegistry {
+    games: HashMap<CaGame, Vec<Faction>>,
+}
+
+impl FactionRegistry {
+    pub fn parse(json: &str) -> Result<Self, String> {
+        match serde_json::from_str::<HashMap<CaGame, Vec<Faction>>>(json) {
+            Ok(games) => Ok(Self { games }),
+            Err(e) => Err(format!("Couldn't parse faction data {}", e)),
+        }
+    }
+
+    //Empty for games nobody has written factions up for yet
+    pub fn get_game_factions(&self, ca_game: &CaGame) -> &[Faction] {
+        match self.games.get(ca_game) {
+            Some(factions) => factions.as_slice(),
+            None => &[],
+        }
+    }
+
+    pub fn get_all_factions(&self) -> impl Iterator<Item = &Faction> {
+        self.games.values().flatten()
+    }
+
+    pub fn find_faction(&self, ca_game: &CaGame, faction_str: &str) -> Option<&Faction> {
+        self.get_game_factions(ca_game)
+            .iter()
+            .find(|f| f.is_called(faction_str))
+    }
+
+    pub fn find_faction_by_key(&self, ca_game: &CaGame, faction_key: &str) -> Option<&Faction> {
+        self.get_game_factions(ca_game)
+            .iter()
+            .find(|f| f.is_key(faction_key))
+    }
+}
+
+pub fn get_faction_registry() -> &'static FactionRegistry {
+    static REGISTRY: OnceLock<FactionRegistry> = OnceLock::new();
+    REGISTRY.get_or_init(|| {
+        FactionRegistry::parse(FACTIONS_DATA).expect("Bundled assets/factions.json is broken")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn bundled_data_parses_with_unique_ids() {
+        let registry = FactionRegistry::parse(FACTIONS_DATA).unwrap();
+        assert!(!registry.get_game_factions(&CaGame::Warhammer3).is_empty());
+        for factions in registry.games.values() {
+            let ids: HashSet<&String> = factions.iter().map(|f| &f.id).collect();
+            assert_eq!(ids.len(), factions.len());
+        }
+    }
+
+    #[test]
+    fn finds_by_name_alias_and_key() {
+        let registry = get_faction_registry();
+        let gs = registry
+            .find_faction(&CaGame::Warhammer2, "greenskins")
+            .unwrap();
+        assert_eq!(gs.id, "GS");
+        assert_eq!(
+            registry.find_faction(&CaGame::Warhammer2, "Wood Elves").unwrap().id,
+            "WE"
+        );
+        assert_eq!(
+            registry
+                .find_faction_by_key(&CaGame::Warhammer2, "wh2_main_hef_high_elves")
+                .unwrap()
+                .id,
+            "HE"
+        );
+        assert!(registry.find_faction(&CaGame::Shogun2, "gs").is_none());
+    }
+}
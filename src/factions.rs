@@ -1,3 +1,5 @@
+use crate::ca_game::CaGame;
+use crate::faction_registry::{get_faction_registry, ALL_FACTIONS, UNKNOWN_FACTION};
 use eframe::egui;
 use eframe::egui::{Response, Ui};
 
@@ -25,6 +27,25 @@ pub enum Wh2Factions {
     ALL,
 }
 
+//Every real faction, no UNKNOWN or ALL
+pub const WH2_FACTIONS: [Wh2Factions; 15] = [
+    Wh2Factions::BM,
+    Wh2Factions::BRT,
+    Wh2Factions::CH,
+    Wh2Factions::DE,
+    Wh2Factions::DW,
+    Wh2Factions::EMP,
+    Wh2Factions::GS,
+    Wh2Factions::HE,
+    Wh2Factions::LM,
+    Wh2Factions::NRS,
+    Wh2Factions::SKV,
+    Wh2Factions::TK,
+    Wh2Factions::VC,
+    Wh2Factions::VP,
+    Wh2Factions::WE,
+];
+
 pub fn get_faction_abbreviations(faction: Wh2Factions) -> &'static str {
     match faction {
         Wh2Factions::BM => "BM",
@@ -69,183 +90,47 @@ pub fn get_faction_names(faction: &Wh2Factions) -> &'static str {
     }
 }
 
-pub fn parse_faction(file_name: &String) -> Wh2Factions {
-    let lower_file = file_name.to_ascii_lowercase();
-    if lower_file.contains("bm vs") {
-        return Wh2Factions::BM;
-    } else if lower_file.contains("brt vs") {
-        return Wh2Factions::BRT;
-    } else if lower_file.contains("ch vs") {
-        return Wh2Factions::CH;
-    } else if lower_file.contains("dw vs") {
-        return Wh2Factions::DW;
-    } else if lower_file.contains("emp vs") {
-        return Wh2Factions::EMP;
-    } else if lower_file.contains("ew vs") {
-        return Wh2Factions::DW;
-    } else if lower_file.contains("de vs") {
-        return Wh2Factions::DE;
-    } else if lower_file.contains("gs vs") {
-        return Wh2Factions::GS;
-    } else if lower_file.contains("he vs") {
-        return Wh2Factions::HE;
-    } else if lower_file.contains("lm vs") {
-        return Wh2Factions::LM;
-    } else if lower_file.contains("nrs vs") {
-        return Wh2Factions::NRS;
-    } else if lower_file.contains("skv vs") {
-        return Wh2Factions::SKV;
-    } else if lower_file.contains("tk vs") {
-        return Wh2Factions::TK;
-    } else if lower_file.contains("vc vs") {
-        return Wh2Factions::VC;
-    } else if lower_file.contains("vp vs") {
-        return Wh2Factions::VP;
-    } else if lower_file.contains("we vs") {
-        return Wh2Factions::WE;
-    }
-    Wh2Factions::UNKNOWN
-}
-
-pub fn parse_vs_faction(file_name: &String) -> Wh2Factions {
-    let lower_file = file_name.to_ascii_lowercase();
-    if lower_file.contains("vs bm") {
-        return Wh2Factions::BM;
-    } else if lower_file.contains("vs brt") {
-        return Wh2Factions::BRT;
-    } else if lower_file.contains("vs ch") {
-        return Wh2Factions::CH;
-    } else if lower_file.contains("vs de") {
-        return Wh2Factions::DE;
-    } else if lower_file.contains("vs dw") {
-        return Wh2Factions::DW;
-    } else if lower_file.contains("vs emp") {
-        return Wh2Factions::EMP;
-    } else if lower_file.contains("vs gs") {
-        return Wh2Factions::GS;
-    } else if lower_file.contains("vs he") {
-        return Wh2Factions::HE;
-    } else if lower_file.contains("vs lm") {
-        return Wh2Factions::LM;
-    } else if lower_file.contains("vs nrs") {
-        return Wh2Factions::NRS;
-    } else if lower_file.contains("vs skv") {
-        return Wh2Factions::SKV;
-    } else if lower_file.contains("vs tk") {
-        return Wh2Factions::TK;
-    } else if lower_file.contains("vs vc") {
-        return Wh2Factions::VC;
-    } else if lower_file.contains("vs vp") {
-        return Wh2Factions::VP;
-    } else if lower_file.contains("vs we") {
-        return Wh2Factions::WE;
-    } else if lower_file.contains("vs aa") {
+//Wh2 builds still carry the enum, this keeps it lined up with the registry name
+pub fn get_wh2_faction_from_name(faction_name: &str) -> Wh2Factions {
+    if faction_name == ALL_FACTIONS {
         return Wh2Factions::ALL;
     }
-    Wh2Factions::UNKNOWN
+    match get_faction_registry().find_faction(&CaGame::Warhammer2, faction_name) {
+        Some(f) => WH2_FACTIONS
+            .iter()
+            .find(|w| get_faction_abbreviations((*w).clone()) == f.id)
+            .cloned()
+            .unwrap_or(Wh2Factions::UNKNOWN),
+        None => Wh2Factions::UNKNOWN,
+    }
 }
 
+//Picks a faction name from the game's registry, plus All & Unknown
 pub fn faction_dropdown_button(
     ui: &mut Ui,
-    faction: &mut Wh2Factions,
+    ca_game: &CaGame,
+    faction_name: &mut String,
     label: &str,
     is_vs: bool,
 ) -> Response {
-    let faction_btn_response = egui::ComboBox::from_label(label)
-        .selected_text(format!("{:?}", faction))
+    if faction_name.is_empty() {
+        *faction_name = ALL_FACTIONS.to_string();
+    }
+    let selected_text = match get_faction_registry().find_faction(ca_game, faction_name) {
+        Some(f) => f.id.clone(),
+        None => faction_name.clone(),
+    };
+    egui::ComboBox::from_label(label)
+        .selected_text(selected_text)
         .show_ui(ui, |ui| {
-            ui.selectable_value(
-                faction,
-                Wh2Factions::ALL,
-                get_faction_abbreviations(Wh2Factions::ALL),
-            );
-            ui.selectable_value(
-                faction,
-                Wh2Factions::BM,
-                get_faction_abbreviations(Wh2Factions::BM),
-            );
-            ui.selectable_value(
-                faction,
-                Wh2Factions::BRT,
-                get_faction_abbreviations(Wh2Factions::BRT),
-            );
-            if !is_vs {
-                ui.selectable_value(
-                    faction,
-                    Wh2Factions::CH,
-                    get_faction_abbreviations(Wh2Factions::CH),
-                );
+            ui.selectable_value(faction_name, ALL_FACTIONS.to_string(), ALL_FACTIONS);
+            for f in get_faction_registry().get_game_factions(ca_game) {
+                if is_vs && !f.opponent {
+                    continue;
+                }
+                ui.selectable_value(faction_name, f.name.clone(), f.id.as_str())
+                    .on_hover_text(f.name.as_str());
             }
-            ui.selectable_value(
-                faction,
-                Wh2Factions::DE,
-                get_faction_abbreviations(Wh2Factions::DE),
-            );
-            ui.selectable_value(
-                faction,
-                Wh2Factions::DW,
-                get_faction_abbreviations(Wh2Factions::DW),
-            );
-            ui.selectable_value(
-                faction,
-                Wh2Factions::EMP,
-                get_faction_abbreviations(Wh2Factions::EMP),
-            );
-            ui.selectable_value(
-                faction,
-                Wh2Factions::GS,
-                get_faction_abbreviations(Wh2Factions::GS),
-            );
-            ui.selectable_value(
-                faction,
-                Wh2Factions::HE,
-                get_faction_abbreviations(Wh2Factions::HE),
-            );
-            ui.selectable_value(
-                faction,
-                Wh2Factions::LM,
-                get_faction_abbreviations(Wh2Factions::LM),
-            );
-            ui.selectable_value(
-                faction,
-                Wh2Factions::NRS,
-                get_faction_abbreviations(Wh2Factions::NRS),
-            );
-            ui.selectable_value(
-                faction,
-                Wh2Factions::SKV,
-                get_faction_abbreviations(Wh2Factions::SKV),
-            );
-            ui.selectable_value(
-                faction,
-                Wh2Factions::TK,
-                get_faction_abbreviations(Wh2Factions::TK),
-            );
-            ui.selectable_value(
-                faction,
-                Wh2Factions::VC,
-                get_faction_abbreviations(Wh2Factions::VC),
-            );
-            ui.selectable_value(
-                faction,
-                Wh2Factions::VP,
-                get_faction_abbreviations(Wh2Factions::VP),
-            );
-            ui.selectable_value(
-                faction,
-                Wh2Factions::WE,
-                get_faction_abbreviations(Wh2Factions::WE),
-            );
-            ui.selectable_value(
-                faction,
-                Wh2Factions::WE,
-                get_faction_abbreviations(Wh2Factions::WE),
-            );
-            ui.selectable_value(
-                faction,
-                Wh2Factions::UNKNOWN,
-                get_faction_abbreviations(Wh2Factions::UNKNOWN),
-            );
-        });
-    faction_btn_response
+            ui.selectable_value(faction_name, UNKNOWN_FACTION.to_string(), UNKNOWN_FACTION);
+        })
 }
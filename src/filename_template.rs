@@ -0,0 +1,520 @@
+use crate::army_build::{get_funds_amount, FundsLevel};
+use crate::army_setups_folder::get_owaagh_appdata_dir;
+use crate::ca_game::CaGame;
+use crate::faction_registry::{get_faction_registry, ALL_FACTIONS};
+use std::fs;
+use std::path::PathBuf;
+
+// Tried in order, the one covering the most of the name wins
+pub const DEFAULT_FILENAME_TEMPLATES: [&str; 5] = [
+    "{faction} vs {vs_faction} {funds} {author}",
+    "{faction} vs {vs_faction} {funds}",
+    "{faction} vs {vs_faction}",
+    "{faction} v {vs_faction}",
+    "vs {vs_faction}",
+];
+const FILENAME_TEMPLATES_FILE: &str = "filename_templates.json";
+
+const MAX_FACTION_WORDS: usize = 3;
+const MAX_AUTHOR_WORDS: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TemplateField {
+    Faction,
+    VsFaction,
+    Funds,
+    Author,
+    Anything,
+}
+
+fn get_template_field(field_name: &str) -> Option<TemplateField> {
+    match field_name {
+        "faction" => Some(TemplateField::Faction),
+        "vs_faction" => Some(TemplateField::VsFaction),
+        "funds" => Some(TemplateField::Funds),
+        "author" => Some(TemplateField::Author),
+        "*" => Some(TemplateField::Anything),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TemplateSegment {
+    Literal(Vec<char>), // lower case
+    Field(TemplateField),
+}
+
+// A naming convention like "{faction} vs {vs_faction} {funds}", spaces match any run of space, _ or -
+#[derive(Debug, Clone)]
+pub struct FilenameTemplate {
+    pub pattern: String,
+    segments: Vec<TemplateSegment>,
+}
+
+impl FilenameTemplate {
+    pub fn new(pattern: &str) -> Result<Self, String> {
+        let mut segments = vec![];
+        let mut literal = String::new();
+        let mut rest = pattern;
+        while let Some(open) = rest.find('{') {
+            literal.push_str(&rest[..open]);
+            let close = match rest[open..].find('}') {
+                Some(close) => open + close,
+                None => return Err(format!("\'{}\' has a {{ that never closes", pattern)),
+            };
+            let field_name = &rest[open + 1..close];
+            let field = get_template_field(field_name).ok_or_else(|| {
+                format!(
+                    "Don't know {{{}}}, use {{faction}} {{vs_faction}} {{funds}} {{author}} or {{*}}",
+                    field_name
+                )
+            })?;
+            if !literal.is_empty() {
+                segments.push(TemplateSegment::Literal(get_lower_chars(literal.as_str())));
+                literal.clear();
+            }
+            segments.push(TemplateSegment::Field(field));
+            rest = &rest[close + 1..];
+        }
+        literal.push_str(rest);
+        if !literal.is_empty() {
+            segments.push(TemplateSegment::Literal(get_lower_chars(literal.as_str())));
+        }
+        if !segments
+            .iter()
+            .any(|s| matches!(s, TemplateSegment::Field(_)))
+        {
+            return Err(format!("\'{}\' doesn't pull anything out", pattern));
+        }
+        Ok(Self {
+            pattern: pattern.to_string(),
+            segments,
+        })
+    }
+}
+
+pub fn get_default_filename_templates() -> Vec<FilenameTemplate> {
+    DEFAULT_FILENAME_TEMPLATES
+        .iter()
+        .filter_map(|p| FilenameTemplate::new(p).ok())
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ParsedFilename {
+    pub faction: Option<String>, // registry faction name
+    pub vs_faction: Option<String>,
+    pub funds: Option<u32>,
+    pub author: Option<String>,
+    pub confidence: f64, // 0 no template matched, 1 a template matched the whole name
+}
+
+fn is_separator(c: char) -> bool {
+    c.is_whitespace() || c == '_' || c == '-' || c == '.'
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum FieldValue {
+    Text(String),
+    Funds(u32),
+}
+
+// What a field pulled out & where, start to end in chars
+type FieldMatch = (TemplateField, FieldValue, usize, usize);
+
+struct FilenameMatcher<'a> {
+    ca_game: &'a CaGame,
+    chars: Vec<char>, // as written, for pulling out author
+    lower: Vec<char>, // char for char with chars, positions in one are good in the other
+}
+
+//A char at a time, some lowercase to more than one char & the positions would drift
+fn get_lower_chars(s: &str) -> Vec<char> {
+    s.chars()
+        .map(|c| c.to_lowercase().next().unwrap_or(c))
+        .collect()
+}
+
+impl<'a> FilenameMatcher<'a> {
+    fn is_word_start(&self, pos: usize) -> bool {
+        pos < self.lower.len()
+            && is_word_char(self.lower[pos])
+            && (pos == 0 || !is_word_char(self.lower[pos - 1]))
+    }
+
+    //Ends of the next max_words words from pos, shortest first
+    fn get_word_ends(&self, pos: usize, max_words: usize) -> Vec<usize> {
+        let mut ends = vec![];
+        let mut i = pos;
+        while ends.len() < max_words && i < self.lower.len() {
+            while i < self.lower.len() && is_word_char(self.lower[i]) {
+                i += 1;
+            }
+            ends.push(i);
+            while i < self.lower.len() && is_separator(self.lower[i]) {
+                i += 1;
+            }
+            if i < self.lower.len() && !is_word_char(self.lower[i]) {
+                break;
+            }
+        }
+        ends
+    }
+
+    fn match_literal(&self, literal: &[char], pos: usize) -> Option<usize> {
+        let mut i = pos;
+        let mut l = 0;
+        while l < literal.len() {
+            if is_separator(literal[l]) {
+                let start = i;
+                while i < self.lower.len() && is_separator(self.lower[i]) {
+                    i += 1;
+                }
+                if i == start {
+                    return None;
+                }
+                while l < literal.len() && is_separator(literal[l]) {
+                    l += 1;
+                }
+            } else {
+                if i >= self.lower.len() || self.lower[i] != literal[l] {
+                    return None;
+                }
+                i += 1;
+                l += 1;
+            }
+        }
+        //"vs" mustn't match the front of "vsomething"
+        let ends_in_word = literal.last().map(|c| is_word_char(*c)).unwrap_or(false);
+        if ends_in_word && i < self.lower.len() && is_word_char(self.lower[i]) {
+            return None;
+        }
+        Some(i)
+    }
+
+    fn resolve_field(&self, field: TemplateField, text: &str) -> Option<FieldValue> {
+        match field {
+            TemplateField::Faction | TemplateField::VsFaction => {
+                if field == TemplateField::VsFaction && (text == "aa" || text == "all") {
+                    return Some(FieldValue::Text(ALL_FACTIONS.to_string()));
+                }
+                get_faction_registry()
+                    .find_faction(self.ca_game, text)
+                    .map(|f| FieldValue::Text(f.name.clone()))
+            }
+            TemplateField::Funds => {
+                let level = match text {
+                    "small" => Some(FundsLevel::Small),
+                    "medium" => Some(FundsLevel::Medium),
+                    "large" => Some(FundsLevel::Large),
+                    "ultra" => Some(FundsLevel::Ultra),
+                    _ => None,
+                };
+                match level {
+                    Some(level) => Some(FieldValue::Funds(get_funds_amount(level, self.ca_game))),
+                    None => text
+                        .parse::<u32>()
+                        .ok()
+                        .filter(|f| *f >= 1000)
+                        .map(FieldValue::Funds),
+                }
+            }
+            TemplateField::Author | TemplateField::Anything => {
+                Some(FieldValue::Text(String::new()))
+            }
+        }
+    }
+
+    fn get_field_ends(&self, field: TemplateField, pos: usize) -> Vec<usize> {
+        match field {
+            //longest first so "high elves" beats "high"
+            TemplateField::Faction | TemplateField::VsFaction => {
+                let mut ends = self.get_word_ends(pos, MAX_FACTION_WORDS);
+                ends.reverse();
+                ends
+            }
+            TemplateField::Funds => self.get_word_ends(pos, 1),
+            TemplateField::Author => self.get_word_ends(pos, MAX_AUTHOR_WORDS),
+            TemplateField::Anything => {
+                let mut ends = vec![pos];
+                ends.extend(self.get_word_ends(pos, usize::MAX));
+                ends
+            }
+        }
+    }
+
+    //Index past the match & every field pulled out, backtracking over how many words each field takes
+    fn match_segments(
+        &self,
+        segments: &[TemplateSegment],
+        pos: usize,
+    ) -> Option<(usize, Vec<FieldMatch>)> {
+        let (segment, rest) = match segments.split_first() {
+            Some(s) => s,
+            None => return Some((pos, vec![])),
+        };
+        match segment {
+            TemplateSegment::Literal(literal) => {
+                let end = self.match_literal(literal, pos)?;
+                self.match_segments(rest, end)
+            }
+            TemplateSegment::Field(field) => {
+                if !self.is_word_start(pos) && *field != TemplateField::Anything {
+                    return None;
+                }
+                for end in self.get_field_ends(*field, pos) {
+                    let text: String = self.lower[pos..end].iter().collect();
+                    let value = match self.resolve_field(*field, text.as_str()) {
+                        Some(v) => v,
+                        None => continue,
+                    };
+                    if let Some((match_end, mut fields)) = self.match_segments(rest, end) {
+                        fields.insert(0, (*field, value, pos, end));
+                        return Some((match_end, fields));
+                    }
+                }
+                None
+            }
+        }
+    }
+}
+
+// Best reading of a build's file name over every template, faction names come from the game's registry
+pub fn parse_filename(
+    ca_game: &CaGame,
+    file_stem: &str,
+    templates: &[FilenameTemplate],
+) -> ParsedFilename {
+    let matcher = FilenameMatcher {
+        ca_game,
+        chars: file_stem.chars().collect(),
+        lower: get_lower_chars(file_stem),
+    };
+    let word_chars = matcher.lower.iter().filter(|c| is_word_char(**c)).count();
+    if word_chars == 0 {
+        return ParsedFilename::default();
+    }
+
+    let mut best = ParsedFilename::default();
+    for template in templates {
+        for start in (0..matcher.lower.len()).filter(|p| matcher.is_word_start(*p)) {
+            let (end, fields) = match matcher.match_segments(&template.segments, start) {
+                Some(m) => m,
+                None => continue,
+            };
+            let covered = matcher.lower[start..end]
+                .iter()
+                .filter(|c| is_word_char(**c))
+                .count();
+            let confidence = 0.5 + 0.5 * covered as f64 / word_chars as f64;
+            if confidence <= best.confidence {
+                continue;
+            }
+            let mut parsed = ParsedFilename {
+                confidence,
+                ..ParsedFilename::default()
+            };
+            for (field, value, field_start, field_end) in fields {
+                match (field, value) {
+                    (TemplateField::Faction, FieldValue::Text(t)) => parsed.faction = Some(t),
+                    (TemplateField::VsFaction, FieldValue::Text(t)) => parsed.vs_faction = Some(t),
+                    (TemplateField::Funds, FieldValue::Funds(f)) => parsed.funds = Some(f),
+                    (TemplateField::Author, _) => {
+                        parsed.author = Some(matcher.chars[field_start..field_end].iter().collect())
+                    }
+                    _ => {}
+                }
+            }
+            best = parsed;
+        }
+    }
+    best
+}
+
+pub fn get_filename_templates_file() -> Result<PathBuf, String> {
+    Ok(get_owaagh_appdata_dir()?.join(FILENAME_TEMPLATES_FILE))
+}
+
+//The user's templates, or the defaults if they never saved any
+pub fn load_filename_templates() -> Vec<String> {
+    let file = match get_filename_templates_file() {
+        Ok(f) => f,
+        Err(_) => {
+            return DEFAULT_FILENAME_TEMPLATES
+                .iter()
+                .map(|t| t.to_string())
+                .collect()
+        }
+    };
+    match fs::read_to_string(file.as_path()) {
+        Ok(s) => match serde_json::from_str::<Vec<String>>(s.as_str()) {
+            Ok(templates) => templates,
+            Err(e) => {
+                println!("Couldn't parse {} {}", file.to_string_lossy(), e);
+                DEFAULT_FILENAME_TEMPLATES
+                    .iter()
+                    .map(|t| t.to_string())
+                    .collect()
+            }
+        },
+        Err(_) => DEFAULT_FILENAME_TEMPLATES
+            .iter()
+            .map(|t| t.to_string())
+            .collect(),
+    }
+}
+
+pub fn save_filename_templates(templates: &[String]) -> Result<(), String> {
+    let file = get_filename_templates_file()?;
+    let s = match serde_json::to_string_pretty(templates) {
+        Ok(s) => s,
+        Err(e) => return Err(format!("Couldn't serialize templates {}", e)),
+    };
+    match fs::write(file.as_path(), s) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("Couldn't write {} {}", file.to_string_lossy(), e)),
+    }
+}
+
+//Bad patterns are skipped, the error for each comes back with it
+pub fn compile_filename_templates(patterns: &[String]) -> (Vec<FilenameTemplate>, Vec<String>) {
+    let mut templates = vec![];
+    let mut errors = vec![];
+    for p in patterns {
+        match FilenameTemplate::new(p.as_str()) {
+            Ok(t) => templates.push(t),
+            Err(e) => errors.push(e),
+        }
+    }
+    (templates, errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::faction_registry::get_faction_registry;
+
+    fn parse(name: &str) -> ParsedFilename {
+        parse_filename(&CaGame::Warhammer2, name, &get_default_filename_templates())
+    }
+
+    fn wh2_name(id: &str) -> String {
+        get_faction_registry()
+            .find_faction(&CaGame::Warhammer2, id)
+            .unwrap()
+            .name
+            .clone()
+    }
+
+    #[test]
+    fn every_felkon_pack_name() {
+        let ids = [
+            "BM", "BRT", "CH", "DE", "DW", "EMP", "GS", "HE", "LM", "NRS", "SKV", "TK", "VC", "VP",
+            "WE",
+        ];
+        for faction in ids.iter() {
+            for vs_faction in ids.iter() {
+                let name = format!("{} vs {}", faction, vs_faction);
+                let parsed = parse(name.as_str());
+                assert_eq!(parsed.faction, Some(wh2_name(faction)), "{}", name);
+                assert_eq!(parsed.vs_faction, Some(wh2_name(vs_faction)), "{}", name);
+                assert_eq!(parsed.confidence, 1.0, "{}", name);
+            }
+        }
+    }
+
+    // name, faction, vs faction, funds, author
+    type NameCase = (
+        &'static str,
+        Option<&'static str>,
+        Option<&'static str>,
+        Option<u32>,
+        Option<&'static str>,
+    );
+
+    #[test]
+    fn naming_styles() {
+        let cases: [NameCase; 13] = [
+            ("GS vs HE 12400", Some("GS"), Some("HE"), Some(12400), None),
+            (
+                "GS vs HE 12400 Felkon",
+                Some("GS"),
+                Some("HE"),
+                Some(12400),
+                Some("Felkon"),
+            ),
+            ("gs_vs_he", Some("GS"), Some("HE"), None, None),
+            ("GS v HE", Some("GS"), Some("HE"), None, None),
+            (
+                "High Elves vs Wood Elves",
+                Some("HE"),
+                Some("WE"),
+                None,
+                None,
+            ),
+            (
+                "DW vs SKV large",
+                Some("DW"),
+                Some("SKV"),
+                Some(12400),
+                None,
+            ),
+            ("Night Goblins GS vs HE", Some("GS"), Some("HE"), None, None),
+            ("BRT vs aa", Some("BRT"), Some("All"), None, None),
+            ("Crew vs GS", None, Some("GS"), None, None),
+            ("Made vs HE", None, Some("HE"), None, None),
+            ("Stuff", None, None, None, None),
+            //İ lowercases to two chars
+            (
+                "İİİİ GS vs HE 12400 Felkon",
+                Some("GS"),
+                Some("HE"),
+                Some(12400),
+                Some("Felkon"),
+            ),
+            (
+                "GS vs HE 12400 İİ",
+                Some("GS"),
+                Some("HE"),
+                Some(12400),
+                Some("İİ"),
+            ),
+        ];
+        for (name, faction, vs_faction, funds, author) in cases.iter() {
+            let parsed = parse(name);
+            let expected = |id: &Option<&str>| match id {
+                Some("All") => Some("All".to_string()),
+                Some(id) => Some(wh2_name(id)),
+                None => None,
+            };
+            assert_eq!(parsed.faction, expected(faction), "{}", name);
+            assert_eq!(parsed.vs_faction, expected(vs_faction), "{}", name);
+            assert_eq!(parsed.funds, *funds, "{}", name);
+            assert_eq!(parsed.author.as_deref(), *author, "{}", name);
+        }
+    }
+
+    #[test]
+    fn confidence_drops_with_leftovers() {
+        assert_eq!(parse("GS vs HE").confidence, 1.0);
+        let partial = parse("Night Goblins GS vs HE").confidence;
+        assert!(partial > 0.5 && partial < 1.0);
+        assert_eq!(parse("Stuff").confidence, 0.0);
+    }
+
+    #[test]
+    fn custom_templates() {
+        let templates = vec![FilenameTemplate::new("{author}_{faction}-{vs_faction}").unwrap()];
+        let parsed = parse_filename(&CaGame::Warhammer2, "felkon_skv-lm", &templates);
+        assert_eq!(parsed.author.as_deref(), Some("felkon"));
+        assert_eq!(parsed.faction, Some(wh2_name("SKV")));
+        assert_eq!(parsed.vs_faction, Some(wh2_name("LM")));
+        assert!(FilenameTemplate::new("{faction} vs {opponent}").is_err());
+        assert!(FilenameTemplate::new("{faction vs").is_err());
+        assert!(FilenameTemplate::new("just words").is_err());
+    }
+}
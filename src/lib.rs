@@ -2,15 +2,37 @@
 #![warn(clippy::all, rust_2018_idioms)]
 
 mod resources_panel;
+mod stats_panel;
+mod tier_list_panel;
 mod advanced_search_container;
 mod app;
 pub mod army_build;
+pub mod army_setup_format;
 pub mod army_setups_folder;
 pub mod army_setups_manager;
-mod ca_game;
+pub mod build_archive;
+pub mod build_code;
+pub mod build_pack;
+pub mod build_renamer;
+pub mod build_sync;
+pub mod build_trash;
+pub mod build_undo;
+pub mod build_watcher;
+pub mod ca_game;
+mod ca_game_discovery;
 mod central_panel_state;
+pub mod faction_registry;
 pub mod factions;
+pub mod filename_template;
+pub mod library_backup;
+pub mod library_store;
+pub mod match_log;
+pub mod match_stats;
 mod misc_folders;
+pub mod name_search;
+pub mod replays;
+pub mod search_query;
+pub mod tier_list;
 pub mod ymd_hms_dash_format;
 
 pub use app::OwaaghApp;
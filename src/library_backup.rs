@@ -0,0 +1,303 @@
+use crate::army_build::get_army_build_id;
+use crate::army_setups_folder::{
+    get_owaagh_appdata_dir, get_owaagh_army_setups_dir, get_owaagh_backups_dir,
+    is_ca_game_setup_path, OWAAGH_BACKUPS_SUBDIR, OWAAGH_TRASH_SUBDIR,
+};
+use crate::ca_game::{get_ca_game_subfolder, CaGame};
+use crate::library_store::{
+    get_library_index_file, load_library_index, update_library_index, LIBRARY_INDEX_FILE,
+};
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// A snapshot is a copy of the whole appdata store, every game's setups, library index, match log
+// & the rest, in backups/<timestamp>. Restoring a whole one snapshots what's there first.
+pub const DEFAULT_BACKUP_RETENTION: usize = 10;
+const SNAPSHOT_NAME_FORMAT: &str = "%Y-%m-%d_%H-%M-%S";
+const SNAPSHOT_STAMP_LEN: usize = 19; // 2021-01-31_20-00-00
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    pub name: String, // the folder name, its timestamp
+    pub dir: PathBuf,
+    pub created_on: DateTime<Utc>,
+}
+
+impl Snapshot {
+    fn from_dir(dir: &Path) -> Option<Self> {
+        let name = dir.file_name()?.to_string_lossy().to_string();
+        //<stamp> or <stamp>_<n> for same second snapshots, nothing else so .tmp ones never count
+        let stamp = name.get(..SNAPSHOT_STAMP_LEN)?;
+        let n = &name[SNAPSHOT_STAMP_LEN..];
+        if !n.is_empty() {
+            let n = n.strip_prefix('_')?;
+            if n.is_empty() || !n.bytes().all(|c| c.is_ascii_digit()) {
+                return None;
+            }
+        }
+        let created_on = NaiveDateTime::parse_from_str(stamp, SNAPSHOT_NAME_FORMAT).ok()?;
+        let created_on = Utc.from_utc_datetime(&created_on);
+        Some(Self {
+            name,
+            dir: dir.to_path_buf(),
+            created_on,
+        })
+    }
+
+    pub fn get_army_setups_dir(&self, ca_game: &CaGame) -> PathBuf {
+        self.dir
+            .join(get_ca_game_subfolder(ca_game))
+            .join("army_setups")
+    }
+
+    //File names of the game's setups in the snapshot
+    pub fn get_build_files(&self, ca_game: &CaGame) -> Vec<String> {
+        let mut files: Vec<String> = match fs::read_dir(self.get_army_setups_dir(ca_game)) {
+            Ok(entries) => entries
+                .flatten()
+                .map(|e| e.path())
+                .filter(|p| p.is_file() && is_ca_game_setup_path(p.as_path(), ca_game))
+                .filter_map(|p| p.file_name().map(|f| f.to_string_lossy().to_string()))
+                .collect(),
+            Err(_) => vec![],
+        };
+        files.sort();
+        files
+    }
+}
+
+//The backups & the trash stay out of snapshots & restores
+fn is_kept_out(top_level: &std::ffi::OsStr) -> bool {
+    top_level == OWAAGH_BACKUPS_SUBDIR || top_level == OWAAGH_TRASH_SUBDIR
+}
+
+//Everything in the store but the backups & trash
+fn copy_store(from: &Path, to: &Path) -> Result<(), String> {
+    for entry in walkdir::WalkDir::new(from)
+        .min_depth(1)
+        .into_iter()
+        .filter_entry(|e| e.depth() > 1 || !is_kept_out(e.file_name()))
+    {
+        let entry = entry.map_err(|e| format!("{}", e))?;
+        let rel = match entry.path().strip_prefix(from) {
+            Ok(rel) => rel,
+            Err(e) => return Err(format!("{}", e)),
+        };
+        let dest = to.join(rel);
+        let res = if entry.file_type().is_dir() {
+            fs::create_dir_all(dest.as_path())
+        } else {
+            fs::copy(entry.path(), dest.as_path()).map(|_| ())
+        };
+        if let Err(e) = res {
+            return Err(format!("Couldn't copy {} {}", rel.to_string_lossy(), e));
+        }
+    }
+    Ok(())
+}
+
+fn create_snapshot_in(store: &Path, backups: &Path) -> Result<Snapshot, String> {
+    let stamp = Utc::now().format(SNAPSHOT_NAME_FORMAT).to_string();
+    let mut dir = backups.join(stamp.as_str());
+    let mut n = 2;
+    while dir.exists() {
+        dir = backups.join(format!("{}_{}", stamp, n));
+        n += 1;
+    }
+    //built under a tmp name so a half copied snapshot never gets listed
+    let mut tmp = dir.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    let res = fs::create_dir_all(tmp.as_path())
+        .map_err(|e| format!("{}", e))
+        .and_then(|_| copy_store(store, tmp.as_path()))
+        .and_then(|_| fs::rename(tmp.as_path(), dir.as_path()).map_err(|e| format!("{}", e)));
+    if let Err(e) = res {
+        let _ = fs::remove_dir_all(tmp.as_path());
+        return Err(format!("Couldn't snapshot the library {}", e));
+    }
+    Snapshot::from_dir(dir.as_path()).ok_or_else(|| "Snapshot name didn't parse".to_string())
+}
+
+//Newest first
+fn list_snapshots_in(backups: &Path) -> Result<Vec<Snapshot>, String> {
+    let entries = match fs::read_dir(backups) {
+        Ok(entries) => entries,
+        Err(e) => return Err(format!("Couldn't read {} {}", backups.to_string_lossy(), e)),
+    };
+    let mut snapshots: Vec<Snapshot> = entries
+        .flatten()
+        .filter(|e| e.path().is_dir())
+        .filter_map(|e| Snapshot::from_dir(e.path().as_path()))
+        .collect();
+    snapshots.sort_by(|a, b| {
+        (b.created_on, b.name.len(), &b.name).cmp(&(a.created_on, a.name.len(), &a.name))
+    });
+    Ok(snapshots)
+}
+
+fn prune_snapshots_in(backups: &Path, retention: usize) -> Result<usize, String> {
+    let mut n_removed = 0;
+    for s in list_snapshots_in(backups)?
+        .into_iter()
+        .skip(retention.max(1))
+    {
+        match fs::remove_dir_all(s.dir.as_path()) {
+            Ok(_) => n_removed += 1,
+            Err(e) => return Err(format!("Couldn't remove snapshot {} {}", s.name, e)),
+        }
+    }
+    Ok(n_removed)
+}
+
+//Clears the store, leaving the backups & trash, then copies the snapshot in. Folders the snapshot has
+//stay put so anything watching them keeps working
+fn restore_snapshot_to(snapshot: &Snapshot, store: &Path) -> Result<(), String> {
+    let entries = match fs::read_dir(store) {
+        Ok(entries) => entries,
+        Err(e) => return Err(format!("Couldn't read {} {}", store.to_string_lossy(), e)),
+    };
+    for top in entries.flatten() {
+        if is_kept_out(top.file_name().as_os_str()) {
+            continue;
+        }
+        for entry in walkdir::WalkDir::new(top.path()).contents_first(true) {
+            let entry = entry.map_err(|e| format!("{}", e))?;
+            let rel = match entry.path().strip_prefix(store) {
+                Ok(rel) => rel,
+                Err(e) => return Err(format!("{}", e)),
+            };
+            let res = if !entry.file_type().is_dir() {
+                fs::remove_file(entry.path())
+            } else if !snapshot.dir.join(rel).is_dir() {
+                fs::remove_dir(entry.path())
+            } else {
+                Ok(())
+            };
+            if let Err(e) = res {
+                return Err(format!("Couldn't clear {} {}", rel.to_string_lossy(), e));
+            }
+        }
+    }
+    copy_store(snapshot.dir.as_path(), store)
+}
+
+pub fn create_snapshot(retention: usize) -> Result<Snapshot, String> {
+    let backups = get_owaagh_backups_dir()?;
+    let snapshot = create_snapshot_in(get_owaagh_appdata_dir()?.as_path(), backups.as_path())?;
+    prune_snapshots_in(backups.as_path(), retention)?;
+    Ok(snapshot)
+}
+
+pub fn list_snapshots() -> Result<Vec<Snapshot>, String> {
+    list_snapshots_in(get_owaagh_backups_dir()?.as_path())
+}
+
+//Snapshots what's there now first so a restore can be taken back, that one counts toward retention
+pub fn restore_snapshot(snapshot: &Snapshot, retention: usize) -> Result<Snapshot, String> {
+    let backups = get_owaagh_backups_dir()?;
+    let store = get_owaagh_appdata_dir()?;
+    //pruned after so the one being restored can't go first
+    let before = create_snapshot_in(store.as_path(), backups.as_path())?;
+    restore_snapshot_to(snapshot, store.as_path())?;
+    prune_snapshots_in(backups.as_path(), retention.max(2))?;
+    Ok(before)
+}
+
+//One setup back into the library with its metadata, next to the current one if the name is taken
+pub fn restore_snapshot_build(
+    snapshot: &Snapshot,
+    ca_game: &CaGame,
+    file_name: &str,
+) -> Result<PathBuf, String> {
+    let from = snapshot.get_army_setups_dir(ca_game).join(file_name);
+    let bytes = match fs::read(from.as_path()) {
+        Ok(bytes) => bytes,
+        Err(e) => return Err(format!("Couldn't read {} {}", from.to_string_lossy(), e)),
+    };
+    let build_id = get_army_build_id(bytes.as_slice());
+
+    let library = get_owaagh_army_setups_dir(ca_game)?;
+    let mut to = library.join(file_name);
+    let same_as_there = fs::read(to.as_path())
+        .map(|b| get_army_build_id(b.as_slice()) == build_id)
+        .unwrap_or(false);
+    if to.exists() && !same_as_there {
+        let path = Path::new(file_name);
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let ext = path.extension().unwrap_or_default().to_string_lossy();
+        to = library.join(format!("{} (restored {}).{}", stem, snapshot.name, ext));
+    }
+    if !same_as_there {
+        if let Err(e) = fs::write(to.as_path(), bytes.as_slice()) {
+            return Err(format!("Couldn't write {} {}", to.to_string_lossy(), e));
+        }
+    }
+
+    let index_file = snapshot
+        .dir
+        .join(get_ca_game_subfolder(ca_game))
+        .join(LIBRARY_INDEX_FILE);
+    if let Some(metadata) = load_library_index(index_file.as_path())?.remove(&build_id) {
+        //only fills in a build the current index doesn't know, edits since stay
+        let current = get_library_index_file(ca_game).and_then(|f| load_library_index(&f))?;
+        if !current.builds.contains_key(&build_id) {
+            update_library_index(ca_game, |index| {
+                index.builds.insert(build_id, metadata);
+            })?;
+        }
+    }
+    Ok(to)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshots_prune_and_restore() {
+        let dir = std::env::temp_dir().join(format!("owaagh_backup_{}", std::process::id()));
+        let _ = fs::remove_dir_all(dir.as_path());
+        let store = dir.join("WarbossWaaghit");
+        let setups = store.join("Warhammer2").join("army_setups");
+        fs::create_dir_all(setups.as_path()).unwrap();
+        fs::write(setups.join("GS vs HE.army_setup"), b"gobbos").unwrap();
+        fs::write(store.join("Warhammer2").join("library.json"), b"{}").unwrap();
+        let backups = store.join(OWAAGH_BACKUPS_SUBDIR);
+        fs::create_dir_all(backups.as_path()).unwrap();
+
+        let first = create_snapshot_in(store.as_path(), backups.as_path()).unwrap();
+        assert_eq!(
+            first.get_build_files(&CaGame::Warhammer2),
+            vec!["GS vs HE.army_setup".to_string()]
+        );
+        assert!(!first.dir.join(OWAAGH_BACKUPS_SUBDIR).exists());
+
+        //a bad copy wipes the library
+        fs::remove_file(setups.join("GS vs HE.army_setup")).unwrap();
+        fs::write(setups.join("junk.army_setup"), b"junk").unwrap();
+        let second = create_snapshot_in(store.as_path(), backups.as_path()).unwrap();
+        assert_ne!(first.name, second.name);
+        //half copied ones never get listed
+        fs::create_dir_all(backups.join(format!("{}.tmp", first.name))).unwrap();
+        fs::create_dir_all(backups.join(format!("{}_3.tmp", first.name))).unwrap();
+        fs::create_dir_all(backups.join(format!("{}_x", first.name))).unwrap();
+        let snapshots = list_snapshots_in(backups.as_path()).unwrap();
+        assert_eq!(snapshots.len(), 2);
+        assert_eq!(snapshots[0], second);
+
+        restore_snapshot_to(&first, store.as_path()).unwrap();
+        assert_eq!(
+            fs::read(setups.join("GS vs HE.army_setup")).unwrap(),
+            b"gobbos"
+        );
+        assert!(!setups.join("junk.army_setup").exists());
+        assert_eq!(list_snapshots_in(backups.as_path()).unwrap().len(), 2);
+
+        assert_eq!(prune_snapshots_in(backups.as_path(), 1), Ok(1));
+        let snapshots = list_snapshots_in(backups.as_path()).unwrap();
+        fs::remove_dir_all(dir.as_path()).unwrap();
+        assert_eq!(snapshots, vec![second]);
+    }
+}
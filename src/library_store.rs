@@ -0,0 +1,296 @@
+use crate::army_build::ArmyBuild;
+use crate::army_setups_folder::get_owaagh_appdata_dir;
+use crate::ca_game::{get_ca_game_subfolder, CaGame};
+use crate::factions::Wh2Factions;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+//Bump when BuildMetadata changes shape, older indexes get migrated on load
+pub const LIBRARY_SCHEMA_VERSION: u32 = 1;
+pub const LIBRARY_INDEX_FILE: &str = "library.json";
+
+//Everything the user can edit on a build, the setup file itself has none of it
+#[cfg_attr(
+    feature = "persistence",
+    derive(serde::Deserialize, serde::Serialize, Clone)
+)]
+pub struct BuildMetadata {
+    pub file_stem: String,
+    pub original_file: PathBuf,
+
+    pub faction: Wh2Factions,
+    pub vs_faction: Wh2Factions,
+    pub faction_str: String,
+    pub vs_faction_str: String,
+
+    pub funds: u32,
+    pub game_mod: String,
+    pub created_by: String,
+
+    pub win_count: u32,
+    pub loss_count: u32,
+    #[serde(default)]
+    pub draw_count: u32,
+
+    pub image_files: Vec<PathBuf>,
+    pub notes: String,
+}
+
+impl BuildMetadata {
+    pub fn new(build: &ArmyBuild) -> Self {
+        Self {
+            file_stem: build.file_stem.clone(),
+            original_file: build.original_file.clone(),
+            faction: build.faction.clone(),
+            vs_faction: build.vs_faction.clone(),
+            faction_str: build.faction_str.clone(),
+            vs_faction_str: build.vs_faction_str.clone(),
+            funds: build.funds,
+            game_mod: build.game_mod.clone(),
+            created_by: build.created_by.clone(),
+            win_count: build.win_count,
+            loss_count: build.loss_count,
+            draw_count: build.draw_count,
+            image_files: build.image_files.clone(),
+            notes: build.notes.clone(),
+        }
+    }
+}
+
+#[cfg_attr(
+    feature = "persistence",
+    derive(serde::Deserialize, serde::Serialize, Clone)
+)]
+pub struct LibraryIndex {
+    pub schema_version: u32,
+    pub builds: BTreeMap<String, BuildMetadata>, // by build_id
+}
+
+impl Default for LibraryIndex {
+    fn default() -> Self {
+        Self {
+            schema_version: LIBRARY_SCHEMA_VERSION,
+            builds: BTreeMap::new(),
+        }
+    }
+}
+
+impl LibraryIndex {
+    //Returns true if the index knew the build
+    pub fn merge_into(&self, build: &mut ArmyBuild) -> bool {
+        let m = match self.builds.get(&build.build_id) {
+            Some(m) => m,
+            None => return false,
+        };
+        build.faction = m.faction.clone();
+        build.vs_faction = m.vs_faction.clone();
+        build.faction_str = m.faction_str.clone();
+        build.vs_faction_str = m.vs_faction_str.clone();
+        build.funds = m.funds;
+        build.game_mod = m.game_mod.clone();
+        build.created_by = m.created_by.clone();
+        build.win_count = m.win_count;
+        build.loss_count = m.loss_count;
+        build.draw_count = m.draw_count;
+        build.image_files = m.image_files.clone();
+        build.notes = m.notes.clone();
+        if build.original_file == build.file {
+            build.original_file = m.original_file.clone();
+        }
+        true
+    }
+
+    pub fn update_from(&mut self, build: &ArmyBuild) {
+        if build.build_id.is_empty() {
+            return;
+        }
+        self.builds
+            .insert(build.build_id.clone(), BuildMetadata::new(build));
+    }
+
+    pub fn remove(&mut self, build_id: &str) -> Option<BuildMetadata> {
+        self.builds.remove(build_id)
+    }
+}
+
+//One index per game, sits next to the game's army_setups copies
+pub fn get_library_index_file(ca_game: &CaGame) -> Result<PathBuf, String> {
+    let p = get_owaagh_appdata_dir()?.join(get_ca_game_subfolder(ca_game));
+    Ok(p.join(LIBRARY_INDEX_FILE))
+}
+
+fn migrate_library_index(index: LibraryIndex) -> Result<LibraryIndex, String> {
+    if index.schema_version > LIBRARY_SCHEMA_VERSION {
+        return Err(format!(
+            "Library index schema {} is newer than this WarbossWaaghit knows ({})",
+            index.schema_version, LIBRARY_SCHEMA_VERSION
+        ));
+    }
+    //only the one schema so far
+    Ok(LibraryIndex {
+        schema_version: LIBRARY_SCHEMA_VERSION,
+        ..index
+    })
+}
+
+//Missing file is an empty library, a broken one is an error so it never gets overwritten
+pub fn load_library_index(file: &Path) -> Result<LibraryIndex, String> {
+    if !file.exists() {
+        return Ok(LibraryIndex::default());
+    }
+    let s = match fs::read_to_string(file) {
+        Ok(s) => s,
+        Err(e) => return Err(format!("Couldn't read {} {}", file.to_string_lossy(), e)),
+    };
+    match serde_json::from_str::<LibraryIndex>(s.as_str()) {
+        Ok(index) => migrate_library_index(index),
+        Err(e) => Err(format!("Couldn't parse {} {}", file.to_string_lossy(), e)),
+    }
+}
+
+pub fn save_library_index(file: &Path, index: &LibraryIndex) -> Result<(), String> {
+    let s = match serde_json::to_string_pretty(index) {
+        Ok(s) => s,
+        Err(e) => return Err(format!("Couldn't serialize library index {}", e)),
+    };
+    write_file_atomic(file, s.as_str())
+}
+
+//Written to a tmp file then renamed over so a crash never leaves half a file
+pub fn write_file_atomic(file: &Path, contents: &str) -> Result<(), String> {
+    if let Some(parent) = file.parent() {
+        if let Err(e) = fs::create_dir_all(parent) {
+            return Err(format!("{}", e));
+        }
+    }
+    let mut tmp_name = file.as_os_str().to_owned();
+    tmp_name.push(".tmp");
+    let tmp_file = PathBuf::from(tmp_name);
+    if let Err(e) = fs::write(tmp_file.as_path(), contents) {
+        return Err(format!(
+            "Couldn't write {} {}",
+            tmp_file.to_string_lossy(),
+            e
+        ));
+    }
+    match fs::rename(tmp_file.as_path(), file) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("Couldn't replace {} {}", file.to_string_lossy(), e)),
+    }
+}
+
+//Loads, applies the change & saves the game's index in one go
+pub fn update_library_index<F>(ca_game: &CaGame, update: F) -> Result<(), String>
+where
+    F: FnOnce(&mut LibraryIndex),
+{
+    let file = get_library_index_file(ca_game)?;
+    let mut index = load_library_index(file.as_path())?;
+    update(&mut index);
+    save_library_index(file.as_path(), &index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_build(build_id: &str) -> ArmyBuild {
+        ArmyBuild {
+            file: PathBuf::from("army_setups/GS vs HE.army_setup"),
+            original_file: PathBuf::from("army_setups/GS vs HE.army_setup"),
+            file_stem: "GS vs HE".to_string(),
+            build_id: build_id.to_string(),
+            ..ArmyBuild::default()
+        }
+    }
+
+    #[test]
+    fn index_saves_loads_and_merges() {
+        let dir = std::env::temp_dir().join(format!("owaagh_library_{}", std::process::id()));
+        let _ = fs::remove_dir_all(dir.as_path());
+        let file = dir.join("Warhammer2").join(LIBRARY_INDEX_FILE);
+        assert_eq!(
+            load_library_index(file.as_path()).unwrap().builds.len(),
+            0,
+            "missing file is an empty library"
+        );
+
+        let mut edited = get_build("abc");
+        edited.notes = "rush the left".to_string();
+        edited.created_by = "Felkon".to_string();
+        edited.game_mod = "SFO".to_string();
+        edited.funds = 9000;
+        edited.win_count = 3;
+        edited.draw_count = 1;
+        edited.original_file = PathBuf::from("Felkon/GS vs HE.army_setup");
+        let mut index = LibraryIndex::default();
+        index.update_from(&edited);
+        index.update_from(&get_build("")); // no id, nothing to key it by
+        save_library_index(file.as_path(), &index).unwrap();
+        let mut tmp_name = file.as_os_str().to_owned();
+        tmp_name.push(".tmp");
+        assert!(!PathBuf::from(tmp_name).exists());
+
+        let loaded = load_library_index(file.as_path()).unwrap();
+        assert_eq!(loaded.schema_version, LIBRARY_SCHEMA_VERSION);
+        assert_eq!(loaded.builds.keys().collect::<Vec<_>>(), vec!["abc"]);
+
+        let mut build = get_build("abc");
+        assert!(loaded.merge_into(&mut build));
+        assert_eq!(build.notes, "rush the left");
+        assert_eq!(build.created_by, "Felkon");
+        assert_eq!(build.game_mod, "SFO");
+        assert_eq!(
+            (build.funds, build.win_count, build.draw_count),
+            (9000, 3, 1)
+        );
+        assert_eq!(build.original_file, edited.original_file);
+        //a copy somewhere else keeps where it came from
+        let mut copy = get_build("abc");
+        copy.original_file = PathBuf::from("Downloads/GS vs HE.army_setup");
+        loaded.merge_into(&mut copy);
+        assert_eq!(
+            copy.original_file,
+            PathBuf::from("Downloads/GS vs HE.army_setup")
+        );
+        assert!(!loaded.merge_into(&mut get_build("def")));
+
+        fs::write(file.as_path(), b"{ not json").unwrap();
+        let broken = load_library_index(file.as_path());
+        fs::remove_dir_all(dir.as_path()).unwrap();
+        assert!(broken.err().unwrap().starts_with("Couldn't parse"));
+    }
+
+    #[test]
+    fn newer_schema_is_refused() {
+        let newer = LibraryIndex {
+            schema_version: LIBRARY_SCHEMA_VERSION + 1,
+            builds: BTreeMap::new(),
+        };
+        assert!(migrate_library_index(newer).is_err());
+        let older = LibraryIndex {
+            schema_version: 0,
+            builds: BTreeMap::new(),
+        };
+        assert_eq!(
+            migrate_library_index(older).unwrap().schema_version,
+            LIBRARY_SCHEMA_VERSION
+        );
+
+        let dir = std::env::temp_dir().join(format!("owaagh_schema_{}", std::process::id()));
+        let file = dir.join(LIBRARY_INDEX_FILE);
+        fs::create_dir_all(dir.as_path()).unwrap();
+        let s = format!(
+            "{{\"schema_version\": {}, \"builds\": {{}}}}",
+            LIBRARY_SCHEMA_VERSION + 1
+        );
+        fs::write(file.as_path(), s).unwrap();
+        let res = load_library_index(file.as_path());
+        fs::remove_dir_all(dir.as_path()).unwrap();
+        assert!(res
+            .err()
+            .unwrap()
+            .contains("newer than this WarbossWaaghit knows"));
+    }
+}
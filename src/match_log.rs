@@ -0,0 +1,200 @@
+use crate::army_build::ArmyBuild;
+use crate::army_setups_folder::get_owaagh_appdata_dir;
+use crate::ca_game::{get_ca_game_subfolder, CaGame};
+use crate::library_store::write_file_atomic;
+use crate::ymd_hms_dash_format;
+use chrono::{DateTime, Utc};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const MATCH_LOG_FILE: &str = "match_log.json";
+
+#[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MatchResult {
+    Win,
+    Loss,
+    Draw,
+}
+
+pub fn get_match_result_title(result: &MatchResult) -> &'static str {
+    match result {
+        MatchResult::Win => "Win",
+        MatchResult::Loss => "Loss",
+        MatchResult::Draw => "Draw",
+    }
+}
+
+#[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchRecord {
+    #[serde(with = "ymd_hms_dash_format")]
+    pub played_on: DateTime<Utc>,
+    pub build_id: String,
+    pub opponent_faction: String, // registry name, like faction_str
+    pub opponent_name: String,
+    pub map: String,
+    pub result: MatchResult,
+}
+
+#[cfg_attr(feature = "persistence", derive(serde::Serialize))]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MatchCounts {
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+}
+
+impl MatchCounts {
+    pub fn add(&mut self, result: &MatchResult) {
+        match result {
+            MatchResult::Win => self.wins += 1,
+            MatchResult::Loss => self.losses += 1,
+            MatchResult::Draw => self.draws += 1,
+        }
+    }
+
+    pub fn get_games_count(&self) -> u32 {
+        self.wins + self.losses + self.draws
+    }
+
+    //Same as ArmyBuild::get_win_percent, NaN when there's no games
+    pub fn get_win_percent(&self) -> f64 {
+        if self.get_games_count() == 0 {
+            return f64::NAN;
+        }
+        100.0 * self.wins as f64 / self.get_games_count() as f64
+    }
+
+    pub fn apply_to(&self, build: &mut ArmyBuild) {
+        build.win_count = self.wins;
+        build.loss_count = self.losses;
+        build.draw_count = self.draws;
+    }
+}
+
+//Every recorded game, the build win/loss/draw counters are worked out from it
+#[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, Default)]
+pub struct MatchLog {
+    pub records: Vec<MatchRecord>,
+}
+
+impl MatchLog {
+    pub fn get_build_counts(&self, build_id: &str) -> MatchCounts {
+        let mut counts = MatchCounts::default();
+        for r in self.records.iter().filter(|r| r.build_id == build_id) {
+            counts.add(&r.result);
+        }
+        counts
+    }
+
+    pub fn get_all_build_counts(&self) -> HashMap<String, MatchCounts> {
+        let mut by_id: HashMap<String, MatchCounts> = HashMap::new();
+        for r in self.records.iter() {
+            by_id.entry(r.build_id.clone()).or_default().add(&r.result);
+        }
+        by_id
+    }
+
+    //Newest first
+    pub fn get_build_records(&self, build_id: &str) -> Vec<&MatchRecord> {
+        let mut records: Vec<&MatchRecord> = self
+            .records
+            .iter()
+            .filter(|r| r.build_id == build_id)
+            .collect();
+        records.sort_by_key(|r| Reverse(r.played_on));
+        records
+    }
+}
+
+//One log per game, next to the library index
+pub fn get_match_log_file(ca_game: &CaGame) -> Result<PathBuf, String> {
+    let p = get_owaagh_appdata_dir()?.join(get_ca_game_subfolder(ca_game));
+    Ok(p.join(MATCH_LOG_FILE))
+}
+
+//Missing file is no games yet, a broken one is an error so it never gets overwritten
+pub fn load_match_log(file: &Path) -> Result<MatchLog, String> {
+    if !file.exists() {
+        return Ok(MatchLog::default());
+    }
+    let s = match fs::read_to_string(file) {
+        Ok(s) => s,
+        Err(e) => return Err(format!("Couldn't read {} {}", file.to_string_lossy(), e)),
+    };
+    match serde_json::from_str::<MatchLog>(s.as_str()) {
+        Ok(log) => Ok(log),
+        Err(e) => Err(format!("Couldn't parse {} {}", file.to_string_lossy(), e)),
+    }
+}
+
+pub fn save_match_log(file: &Path, log: &MatchLog) -> Result<(), String> {
+    let s = match serde_json::to_string_pretty(log) {
+        Ok(s) => s,
+        Err(e) => return Err(format!("Couldn't serialize match log {}", e)),
+    };
+    write_file_atomic(file, s.as_str())
+}
+
+//Appends to the game's log & returns the build's new counts
+pub fn record_match(ca_game: &CaGame, record: MatchRecord) -> Result<MatchCounts, String> {
+    let file = get_match_log_file(ca_game)?;
+    let mut log = load_match_log(file.as_path())?;
+    let build_id = record.build_id.clone();
+    log.records.push(record);
+    save_match_log(file.as_path(), &log)?;
+    Ok(log.get_build_counts(build_id.as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn record(build_id: &str, result: MatchResult, day: u32) -> MatchRecord {
+        MatchRecord {
+            played_on: Utc.with_ymd_and_hms(2021, 3, day, 12, 0, 0).unwrap(),
+            build_id: build_id.to_string(),
+            opponent_faction: "High Elves".to_string(),
+            opponent_name: String::new(),
+            map: String::new(),
+            result,
+        }
+    }
+
+    #[test]
+    fn counts_drive_win_percent() {
+        let log = MatchLog {
+            records: vec![
+                record("a", MatchResult::Win, 1),
+                record("a", MatchResult::Win, 2),
+                record("a", MatchResult::Loss, 3),
+                record("a", MatchResult::Draw, 4),
+                record("b", MatchResult::Loss, 5),
+            ],
+        };
+        let counts = log.get_build_counts("a");
+        assert_eq!(
+            counts,
+            MatchCounts {
+                wins: 2,
+                losses: 1,
+                draws: 1
+            }
+        );
+        assert_eq!(log.get_all_build_counts()["b"].losses, 1);
+
+        let mut build = ArmyBuild::default();
+        assert!(build.get_win_percent().is_nan());
+        counts.apply_to(&mut build);
+        assert_eq!(build.get_games_played_count(), 4);
+        assert_eq!(build.get_win_percent(), 50.0);
+
+        let newest = log.get_build_records("a");
+        assert_eq!(newest[0].result, MatchResult::Draw);
+    }
+}
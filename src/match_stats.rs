@@ -0,0 +1,204 @@
+use crate::army_build::{get_funds_level, ArmyBuild, FundsLevel};
+use crate::ca_game::CaGame;
+use crate::faction_registry::UNKNOWN_FACTION;
+use crate::match_log::{MatchCounts, MatchLog, MatchRecord};
+use std::collections::{BTreeMap, HashMap};
+
+const MONTH_FORMAT: &str = "%Y-%m";
+
+pub type CountsBreakdown = BTreeMap<String, MatchCounts>;
+
+#[cfg_attr(feature = "persistence", derive(serde::Serialize))]
+pub struct BuildStats {
+    pub build_id: String,
+    pub name: String,
+    pub faction: String,
+    pub counts: MatchCounts,
+    pub by_vs_faction: CountsBreakdown,
+    pub by_funds: CountsBreakdown,
+    pub by_month: CountsBreakdown, // "2021-03"
+}
+
+//Totals across every build of the faction
+#[cfg_attr(feature = "persistence", derive(serde::Serialize))]
+pub struct FactionStats {
+    pub faction: String,
+    pub counts: MatchCounts,
+    pub by_vs_faction: CountsBreakdown,
+}
+
+#[cfg_attr(feature = "persistence", derive(serde::Serialize))]
+pub struct MatchStats {
+    pub builds: Vec<BuildStats>,     // most played first
+    pub factions: Vec<FactionStats>, // most played first
+    pub unmatched_games: usize,      // logged for builds no longer in the library
+}
+
+fn get_or_unknown(s: &str) -> String {
+    if s.is_empty() {
+        UNKNOWN_FACTION.to_string()
+    } else {
+        s.to_string()
+    }
+}
+
+//Who the game was against, falls back to who the build was made for
+pub fn get_record_vs_faction(record: &MatchRecord, build: &ArmyBuild) -> String {
+    if record.opponent_faction.is_empty() {
+        get_or_unknown(build.vs_faction_str.as_str())
+    } else {
+        record.opponent_faction.clone()
+    }
+}
+
+pub fn get_funds_title(funds: u32, ca_game: &CaGame) -> String {
+    match get_funds_level(funds, ca_game) {
+        FundsLevel::Custom => format!("{}", funds),
+        level => format!("{:?}", level),
+    }
+}
+
+pub fn get_match_stats(log: &MatchLog, builds: &[ArmyBuild], ca_game: &CaGame) -> MatchStats {
+    let by_id: HashMap<&str, &ArmyBuild> = builds
+        .iter()
+        .filter(|b| !b.build_id.is_empty())
+        .map(|b| (b.build_id.as_str(), b))
+        .collect();
+
+    let mut build_stats: BTreeMap<String, BuildStats> = BTreeMap::new();
+    let mut faction_stats: BTreeMap<String, FactionStats> = BTreeMap::new();
+    let mut unmatched_games = 0;
+    for record in log.records.iter() {
+        let build = match by_id.get(record.build_id.as_str()) {
+            Some(b) => *b,
+            None => {
+                unmatched_games += 1;
+                continue;
+            }
+        };
+        let faction = get_or_unknown(build.faction_str.as_str());
+        let vs_faction = get_record_vs_faction(record, build);
+
+        let stats = build_stats
+            .entry(build.build_id.clone())
+            .or_insert_with(|| BuildStats {
+                build_id: build.build_id.clone(),
+                name: build.file_stem.clone(),
+                faction: faction.clone(),
+                counts: MatchCounts::default(),
+                by_vs_faction: BTreeMap::new(),
+                by_funds: BTreeMap::new(),
+                by_month: BTreeMap::new(),
+            });
+        stats.counts.add(&record.result);
+        stats
+            .by_vs_faction
+            .entry(vs_faction.clone())
+            .or_default()
+            .add(&record.result);
+        stats
+            .by_funds
+            .entry(get_funds_title(build.funds, ca_game))
+            .or_default()
+            .add(&record.result);
+        stats
+            .by_month
+            .entry(format!("{}", record.played_on.format(MONTH_FORMAT)))
+            .or_default()
+            .add(&record.result);
+
+        let totals = faction_stats
+            .entry(faction.clone())
+            .or_insert_with(|| FactionStats {
+                faction,
+                counts: MatchCounts::default(),
+                by_vs_faction: BTreeMap::new(),
+            });
+        totals.counts.add(&record.result);
+        totals
+            .by_vs_faction
+            .entry(vs_faction)
+            .or_default()
+            .add(&record.result);
+    }
+
+    let mut builds: Vec<BuildStats> = build_stats.into_values().collect();
+    builds.sort_by(|a, b| {
+        b.counts
+            .get_games_count()
+            .cmp(&a.counts.get_games_count())
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    let mut factions: Vec<FactionStats> = faction_stats.into_values().collect();
+    factions.sort_by(|a, b| {
+        b.counts
+            .get_games_count()
+            .cmp(&a.counts.get_games_count())
+            .then_with(|| a.faction.cmp(&b.faction))
+    });
+    MatchStats {
+        builds,
+        factions,
+        unmatched_games,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::match_log::MatchResult;
+    use chrono::{TimeZone, Utc};
+
+    fn build(build_id: &str, faction: &str, vs_faction: &str, funds: u32) -> ArmyBuild {
+        ArmyBuild {
+            file_stem: build_id.to_string(),
+            build_id: build_id.to_string(),
+            faction_str: faction.to_string(),
+            vs_faction_str: vs_faction.to_string(),
+            funds,
+            ..ArmyBuild::default()
+        }
+    }
+
+    fn record(build_id: &str, opponent: &str, month: u32, result: MatchResult) -> MatchRecord {
+        MatchRecord {
+            played_on: Utc.with_ymd_and_hms(2021, month, 1, 20, 0, 0).unwrap(),
+            build_id: build_id.to_string(),
+            opponent_faction: opponent.to_string(),
+            opponent_name: String::new(),
+            map: String::new(),
+            result,
+        }
+    }
+
+    #[test]
+    fn breaks_down_by_opponent_funds_and_month() {
+        let builds = vec![
+            build("gs1", "Greenskins", "High Elves", 12400),
+            build("gs2", "Greenskins", "Dwarfs", 9000),
+        ];
+        let log = MatchLog {
+            records: vec![
+                record("gs1", "High Elves", 3, MatchResult::Win),
+                record("gs1", "", 3, MatchResult::Loss),
+                record("gs1", "Dwarfs", 4, MatchResult::Win),
+                record("gs2", "Dwarfs", 4, MatchResult::Draw),
+                record("gone", "Dwarfs", 4, MatchResult::Win),
+            ],
+        };
+        let stats = get_match_stats(&log, &builds, &CaGame::Warhammer2);
+        assert_eq!(stats.unmatched_games, 1);
+
+        let gs1 = &stats.builds[0];
+        assert_eq!(gs1.build_id, "gs1");
+        assert_eq!(gs1.by_vs_faction["High Elves"].get_games_count(), 2);
+        assert_eq!(gs1.by_funds["Large"].wins, 2);
+        assert_eq!(gs1.by_month["2021-03"].losses, 1);
+        assert_eq!(stats.builds[1].by_funds["9000"].draws, 1);
+
+        assert_eq!(stats.factions.len(), 1);
+        let gs = &stats.factions[0];
+        assert_eq!(gs.counts.get_games_count(), 4);
+        assert_eq!(gs.by_vs_faction["Dwarfs"].get_win_percent(), 50.0);
+    }
+}
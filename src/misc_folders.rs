@@ -15,3 +15,26 @@ pub fn get_user_dir(subdir_str: &str, must_exist: bool) -> Result<PathBuf, Strin
     }
     Err(format!("dirs::home_dir() None"))
 }
+
+//Native folder picker, only have one on windows so elsewhere the discovered folders get listed instead
+#[cfg(windows)]
+pub fn pick_folder_dialog(default_folder: &str) -> Option<String> {
+    let params = wfd::DialogParams {
+        default_folder,
+        options: wfd::FOS_PICKFOLDERS,
+        ..Default::default()
+    };
+
+    match wfd::open_dialog(params) {
+        Ok(res) => Some(res.selected_file_path.to_string_lossy().to_string()),
+        Err(e) => {
+            println!("load folder dialog e {:?}", e);
+            None
+        }
+    }
+}
+
+#[cfg(not(windows))]
+pub fn pick_folder_dialog(_default_folder: &str) -> Option<String> {
+    None
+}
@@ -0,0 +1,182 @@
+use crate::faction_registry::get_faction_registry;
+
+const VS_TOKEN: &str = "vs";
+
+// Lowercase alphanumeric words, faction names folded to their abbreviation & v/versus to vs,
+// so "High Elves v Greenskins" & "HE vs GS" both come out [he, vs, gs]
+pub fn get_name_tokens(name: &str) -> Vec<String> {
+    let words: Vec<String> = name
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_ascii_lowercase())
+        .collect();
+
+    //every game's factions, the same fold on query & name is all that matters
+    let faction_names: Vec<(String, String)> = get_faction_registry()
+        .get_all_factions()
+        .flat_map(|f| {
+            let id = f.id.to_ascii_lowercase();
+            f.get_match_names()
+                .into_iter()
+                .map(move |n| (n.replace(' ', ""), id.clone()))
+        })
+        .collect();
+    let get_abbreviation = |word: &str| {
+        faction_names
+            .iter()
+            .find(|(name, _)| name == word)
+            .map(|(_, abbreviation)| abbreviation.clone())
+    };
+
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < words.len() {
+        if i + 1 < words.len() {
+            let joined = format!("{}{}", words[i], words[i + 1]);
+            if let Some(abbreviation) = get_abbreviation(joined.as_str()) {
+                tokens.push(abbreviation);
+                i += 2;
+                continue;
+            }
+        }
+        let word = words[i].as_str();
+        tokens.push(match word {
+            "v" | "versus" => VS_TOKEN.to_string(),
+            _ => get_abbreviation(word).unwrap_or_else(|| word.to_string()),
+        });
+        i += 1;
+    }
+    tokens
+}
+
+// Edits to turn a into b, swapping two neighbouring letters counts as one
+fn get_edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+// 1 for the same word down to 0 for no match, typos allowed once words get long enough
+fn get_token_score(query_token: &str, name_token: &str) -> f64 {
+    if query_token == name_token {
+        return 1.0;
+    }
+    if name_token.starts_with(query_token) {
+        return if query_token.len() >= 2 { 0.8 } else { 0.5 };
+    }
+    if query_token.len() >= 3 && name_token.contains(query_token) {
+        return 0.6;
+    }
+    let allowed_typos = match query_token.len() {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    };
+    let distance = get_edit_distance(query_token, name_token);
+    if distance <= allowed_typos {
+        0.5 - 0.1 * (distance - 1) as f64
+    } else {
+        0.0
+    }
+}
+
+// None if some query word matches nothing in the name, otherwise higher is more relevant.
+// Words found in the same order & the query showing up as typed both bump the score
+pub fn get_name_match_score(query: &str, name: &str) -> Option<f64> {
+    let query_tokens = get_name_tokens(query);
+    if query_tokens.is_empty() {
+        return Some(0.0);
+    }
+    let name_tokens = get_name_tokens(name);
+
+    let mut total = 0.0;
+    let mut in_order = true;
+    let mut last_position = None;
+    for q in query_tokens.iter() {
+        let (position, score) = name_tokens
+            .iter()
+            .enumerate()
+            .map(|(i, n)| (i, get_token_score(q.as_str(), n.as_str())))
+            .fold(
+                (0, 0.0),
+                |best, current| {
+                    if current.1 > best.1 {
+                        current
+                    } else {
+                        best
+                    }
+                },
+            );
+        if score <= 0.0 {
+            return None;
+        }
+        if let Some(last) = last_position {
+            in_order &= position > last;
+        }
+        last_position = Some(position);
+        total += score;
+    }
+
+    let mut score = total / query_tokens.len() as f64;
+    if in_order && query_tokens.len() > 1 {
+        score += 0.1;
+    }
+    if name
+        .to_ascii_lowercase()
+        .contains(query.trim().to_ascii_lowercase().as_str())
+    {
+        score += 0.2;
+    }
+    Some(score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn faction_names_fold_to_abbreviations() {
+        assert_eq!(
+            get_name_tokens("High Elves v Greenskins_12400"),
+            vec!["he", "vs", "gs", "12400"]
+        );
+        assert_eq!(get_name_tokens("GS vs HE"), vec!["gs", "vs", "he"]);
+    }
+
+    #[test]
+    fn abbreviation_order_and_typos_match() {
+        assert!(get_name_match_score("gs v he", "GS vs HE Night Goblins").is_some());
+        assert!(get_name_match_score("greenskins vs high elves", "GS vs HE").is_some());
+        assert!(get_name_match_score("nigth goblins", "GS vs HE Night Goblins").is_some());
+        assert!(get_name_match_score("gs vs skv", "GS vs HE Night Goblins").is_none());
+    }
+
+    #[test]
+    fn closer_matches_rank_higher() {
+        let exact = get_name_match_score("gs vs he", "GS vs HE").unwrap();
+        let swapped = get_name_match_score("gs vs he", "HE vs GS").unwrap();
+        let typo = get_name_match_score("goblins", "GS vs HE Gobilns").unwrap();
+        let prefix = get_name_match_score("gob", "GS vs HE Goblins").unwrap();
+        let whole = get_name_match_score("goblins", "GS vs HE Goblins").unwrap();
+        assert!(exact > swapped);
+        assert!(whole > prefix && prefix > typo);
+    }
+}
@@ -0,0 +1,214 @@
+use crate::army_setups_folder::get_owaagh_appdata_dir;
+use crate::ca_game::{get_ca_game_subfolder, CaGame};
+use crate::ca_game_discovery::get_ca_game_replays_candidates;
+use crate::library_store::write_file_atomic;
+use chrono::{DateTime, Utc};
+use std::cmp::Reverse;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const REPLAY_EXT: &str = "replay";
+const REPLAY_LINKS_FILE: &str = "replay_links.json";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayFile {
+    pub file: PathBuf,
+    pub file_name: String, // with extension, what links are keyed by
+    pub modified: DateTime<Utc>,
+    pub size: u64,
+}
+
+impl ReplayFile {
+    pub fn get_size_string(&self) -> String {
+        let kb = self.size as f64 / 1024.0;
+        if kb < 1024.0 {
+            format!("{:.0} KB", kb)
+        } else {
+            format!("{:.1} MB", kb / 1024.0)
+        }
+    }
+}
+
+fn is_replay_file(file: &Path) -> bool {
+    match file.extension() {
+        Some(ext) => ext.to_string_lossy().eq_ignore_ascii_case(REPLAY_EXT),
+        None => false,
+    }
+}
+
+pub fn scan_replays_folder(folder: &Path) -> Result<Vec<ReplayFile>, String> {
+    let entries = match fs::read_dir(folder) {
+        Ok(entries) => entries,
+        Err(e) => return Err(format!("Couldn't read {} {}", folder.to_string_lossy(), e)),
+    };
+    let mut replays = vec![];
+    for entry in entries.flatten() {
+        let file = entry.path();
+        if !file.is_file() || !is_replay_file(file.as_path()) {
+            continue;
+        }
+        let metadata = match entry.metadata() {
+            Ok(m) => m,
+            Err(_) => continue,
+        };
+        let modified: DateTime<Utc> = match metadata.modified() {
+            Ok(t) => t.into(),
+            Err(_) => Utc::now(),
+        };
+        replays.push(ReplayFile {
+            file_name: entry.file_name().to_string_lossy().to_string(),
+            file,
+            modified,
+            size: metadata.len(),
+        });
+    }
+    replays.sort_by_key(|r| Reverse(r.modified));
+    Ok(replays)
+}
+
+//Every replay in the game's discovered replays folders, newest first
+pub fn scan_ca_game_replays(ca_game: &CaGame) -> Vec<ReplayFile> {
+    let mut replays: Vec<ReplayFile> = vec![];
+    for candidate in get_ca_game_replays_candidates(ca_game) {
+        if !candidate.exists {
+            continue;
+        }
+        match scan_replays_folder(candidate.folder.as_path()) {
+            Ok(found) => replays.extend(found),
+            Err(e) => println!("scan_ca_game_replays {}", e),
+        }
+    }
+    replays.sort_by_key(|r| Reverse(r.modified));
+    replays
+}
+
+#[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayLink {
+    pub replay_file_name: String,
+    pub build_id: String,
+}
+
+//Which build each replay was played with, one build per replay
+#[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, Default)]
+pub struct ReplayLinks {
+    pub links: Vec<ReplayLink>,
+}
+
+impl ReplayLinks {
+    pub fn get_replay_build_id(&self, replay_file_name: &str) -> Option<&str> {
+        self.links
+            .iter()
+            .find(|l| l.replay_file_name == replay_file_name)
+            .map(|l| l.build_id.as_str())
+    }
+
+    pub fn link(&mut self, replay_file_name: &str, build_id: &str) {
+        self.unlink(replay_file_name);
+        self.links.push(ReplayLink {
+            replay_file_name: replay_file_name.to_string(),
+            build_id: build_id.to_string(),
+        });
+    }
+
+    pub fn unlink(&mut self, replay_file_name: &str) {
+        self.links
+            .retain(|l| l.replay_file_name != replay_file_name);
+    }
+}
+
+pub fn get_replay_links_file(ca_game: &CaGame) -> Result<PathBuf, String> {
+    let p = get_owaagh_appdata_dir()?.join(get_ca_game_subfolder(ca_game));
+    Ok(p.join(REPLAY_LINKS_FILE))
+}
+
+pub fn load_replay_links(file: &Path) -> Result<ReplayLinks, String> {
+    if !file.exists() {
+        return Ok(ReplayLinks::default());
+    }
+    let s = match fs::read_to_string(file) {
+        Ok(s) => s,
+        Err(e) => return Err(format!("Couldn't read {} {}", file.to_string_lossy(), e)),
+    };
+    match serde_json::from_str::<ReplayLinks>(s.as_str()) {
+        Ok(links) => Ok(links),
+        Err(e) => Err(format!("Couldn't parse {} {}", file.to_string_lossy(), e)),
+    }
+}
+
+pub fn save_replay_links(file: &Path, links: &ReplayLinks) -> Result<(), String> {
+    match serde_json::to_string_pretty(links) {
+        Ok(s) => write_file_atomic(file, s.as_str()),
+        Err(e) => Err(format!("Couldn't serialize replay links {}", e)),
+    }
+}
+
+//A game's replays & links, scanned once & refreshed on demand
+#[derive(Clone)]
+pub struct ReplayLibrary {
+    pub ca_game: CaGame,
+    pub replays: Vec<ReplayFile>,
+    pub links: ReplayLinks,
+}
+
+impl ReplayLibrary {
+    pub fn load(ca_game: &CaGame) -> Result<Self, String> {
+        let links = get_replay_links_file(ca_game).and_then(|f| load_replay_links(f.as_path()))?;
+        Ok(Self {
+            ca_game: ca_game.clone(),
+            replays: scan_ca_game_replays(ca_game),
+            links,
+        })
+    }
+
+    pub fn rescan(&mut self) {
+        self.replays = scan_ca_game_replays(&self.ca_game);
+    }
+
+    pub fn get_build_replays(&self, build_id: &str) -> Vec<&ReplayFile> {
+        self.replays
+            .iter()
+            .filter(|r| self.links.get_replay_build_id(r.file_name.as_str()) == Some(build_id))
+            .collect()
+    }
+
+    pub fn set_link(
+        &mut self,
+        replay_file_name: &str,
+        build_id: Option<&str>,
+    ) -> Result<(), String> {
+        match build_id {
+            Some(id) => self.links.link(replay_file_name, id),
+            None => self.links.unlink(replay_file_name),
+        }
+        let file = get_replay_links_file(&self.ca_game)?;
+        save_replay_links(file.as_path(), &self.links)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scans_only_replays_and_relinks() {
+        let folder = std::env::temp_dir().join(format!("owaagh_replays_{}", std::process::id()));
+        fs::create_dir_all(folder.as_path()).unwrap();
+        fs::write(folder.join("GS vs HE.replay"), [0u8; 2048]).unwrap();
+        fs::write(folder.join("notes.txt"), "not a replay").unwrap();
+        let replays = scan_replays_folder(folder.as_path()).unwrap();
+        fs::remove_dir_all(folder.as_path()).unwrap();
+        assert_eq!(replays.len(), 1);
+        assert_eq!(replays[0].file_name, "GS vs HE.replay");
+        assert_eq!(replays[0].get_size_string(), "2 KB");
+
+        let mut links = ReplayLinks::default();
+        links.link("GS vs HE.replay", "a");
+        links.link("GS vs HE.replay", "b");
+        assert_eq!(links.links.len(), 1);
+        assert_eq!(links.get_replay_build_id("GS vs HE.replay"), Some("b"));
+        links.unlink("GS vs HE.replay");
+        assert!(links.links.is_empty());
+    }
+}
@@ -0,0 +1,459 @@
+use crate::advanced_search_container::{parse_search_date_range, SearchFilter};
+use crate::ca_game::CaGame;
+use crate::faction_registry::{get_faction_registry, ALL_FACTIONS, UNKNOWN_FACTION};
+use std::fmt;
+
+// Small query grammar for the build search box, terms are space separated & all have to match
+//   gs v he goblins       words fuzzy match the build name, best matches first
+//   "night goblin"        quoted phrase has to be in the name as is
+//   faction:GS vs:HE      faction & opponent, abbreviation or full name
+//   funds:12400 funds>=9000 funds<15000
+//   author:felkon mod:sfo notes:"cav heavy"
+//   created>2021-01       after january, also created< created>= created<= & created:2021
+//   winrate>=50 games>3   win % & games played
+pub const SEARCH_QUERY_HELP: &str = "Words fuzzy match the name, \"quote\" exact phrases\n\
+faction:GS vs:HE  author:felkon  mod:sfo  notes:cav\n\
+funds:12400  funds>=9000  created>2021-01  winrate>=50  games>3";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SearchQueryError {
+    UnterminatedQuote { offset: usize },
+    UnknownKey(String),
+    EmptyValue(String),
+    UnsupportedOperator { key: String, op: String },
+    InvalidNumber { key: String, value: String },
+    UnknownFaction(String),
+    InvalidDate(String),
+}
+
+impl fmt::Display for SearchQueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SearchQueryError::UnterminatedQuote { offset } => {
+                write!(f, "Quote at {} never gets closed", offset)
+            }
+            SearchQueryError::UnknownKey(key) => write!(
+                f,
+                "Don't know \'{}\', try faction, vs, funds, author, mod, notes, created, winrate or games",
+                key
+            ),
+            SearchQueryError::EmptyValue(key) => write!(f, "\'{}\' needs a value", key),
+            SearchQueryError::UnsupportedOperator { key, op } => {
+                write!(f, "\'{}\' can't be used with \'{}\'", op, key)
+            }
+            SearchQueryError::InvalidNumber { key, value } => {
+                write!(f, "\'{}\' for {} ain't a number", value, key)
+            }
+            SearchQueryError::UnknownFaction(faction) => {
+                write!(f, "\'{}\' ain't a faction", faction)
+            }
+            SearchQueryError::InvalidDate(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for SearchQueryError {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum QueryOp {
+    Eq,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+fn get_query_op_string(op: QueryOp) -> &'static str {
+    match op {
+        QueryOp::Eq => ":",
+        QueryOp::Gt => ">",
+        QueryOp::Ge => ">=",
+        QueryOp::Lt => "<",
+        QueryOp::Le => "<=",
+    }
+}
+
+// A term with its quotes stripped, quoted_from is where the quoted part started in text
+struct QueryToken {
+    text: String,
+    quoted_from: Option<usize>,
+}
+
+fn tokenize_query(query: &str) -> Result<Vec<QueryToken>, SearchQueryError> {
+    let mut tokens = vec![];
+    let mut chars = query.char_indices().peekable();
+    while let Some(&(_, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        let mut token = QueryToken {
+            text: String::new(),
+            quoted_from: None,
+        };
+        while let Some(&(i, c)) = chars.peek() {
+            if c.is_whitespace() {
+                break;
+            }
+            chars.next();
+            if c != '"' {
+                token.text.push(c);
+                continue;
+            }
+            if token.quoted_from.is_none() {
+                token.quoted_from = Some(token.text.len());
+            }
+            let mut closed = false;
+            for (_, c) in chars.by_ref() {
+                if c == '"' {
+                    closed = true;
+                    break;
+                }
+                token.text.push(c);
+            }
+            if !closed {
+                return Err(SearchQueryError::UnterminatedQuote { offset: i });
+            }
+        }
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+// Splits key & operator off the unquoted start of the token
+fn split_query_term(token: &QueryToken) -> Option<(String, QueryOp, String)> {
+    let unquoted_len = token.quoted_from.unwrap_or(token.text.len());
+    let op_start = token.text[..unquoted_len].find([':', '>', '<'])?;
+    let rest = &token.text[op_start..];
+    let (op, op_len) = if rest.starts_with(">=") {
+        (QueryOp::Ge, 2)
+    } else if rest.starts_with("<=") {
+        (QueryOp::Le, 2)
+    } else if rest.starts_with('>') {
+        (QueryOp::Gt, 1)
+    } else if rest.starts_with('<') {
+        (QueryOp::Lt, 1)
+    } else {
+        (QueryOp::Eq, 1)
+    };
+    Some((
+        token.text[..op_start].to_ascii_lowercase(),
+        op,
+        token.text[op_start + op_len..].to_string(),
+    ))
+}
+
+fn parse_query_number<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, SearchQueryError> {
+    value
+        .replace(',', "")
+        .parse::<T>()
+        .map_err(|_| SearchQueryError::InvalidNumber {
+            key: key.to_string(),
+            value: value.to_string(),
+        })
+}
+
+fn compile_query_term(
+    ca_game: &CaGame,
+    key: &str,
+    op: QueryOp,
+    value: String,
+) -> Result<Vec<SearchFilter>, SearchQueryError> {
+    if value.trim().is_empty() {
+        return Err(SearchQueryError::EmptyValue(key.to_string()));
+    }
+    let unsupported = || SearchQueryError::UnsupportedOperator {
+        key: key.to_string(),
+        op: get_query_op_string(op).to_string(),
+    };
+    let text_only = |filter: SearchFilter| match op {
+        QueryOp::Eq => Ok(vec![filter]),
+        _ => Err(unsupported()),
+    };
+    match key {
+        "faction" | "f" | "vs" => {
+            let faction = if value.eq_ignore_ascii_case(UNKNOWN_FACTION) {
+                UNKNOWN_FACTION.to_string()
+            } else if value.eq_ignore_ascii_case(ALL_FACTIONS) {
+                return Ok(vec![]);
+            } else {
+                get_faction_registry()
+                    .find_faction(ca_game, value.as_str())
+                    .map(|f| f.name.clone())
+                    .ok_or_else(|| SearchQueryError::UnknownFaction(value.clone()))?
+            };
+            if key == "vs" {
+                text_only(SearchFilter::VsFaction(faction))
+            } else {
+                text_only(SearchFilter::Faction(faction))
+            }
+        }
+        "author" | "by" => text_only(SearchFilter::CreatedBy(value)),
+        "mod" => text_only(SearchFilter::GameMod(value)),
+        "notes" => text_only(SearchFilter::Notes(value)),
+        "name" => text_only(SearchFilter::Name(value)),
+        "funds" => {
+            let funds: u32 = parse_query_number(key, value.as_str())?;
+            let (min, max) = match op {
+                QueryOp::Eq => (funds, funds),
+                QueryOp::Gt => (funds.saturating_add(1), u32::MAX),
+                QueryOp::Ge => (funds, u32::MAX),
+                QueryOp::Lt => (0, funds.checked_sub(1).ok_or_else(unsupported)?),
+                QueryOp::Le => (0, funds),
+            };
+            Ok(vec![SearchFilter::FundsRange { min, max }])
+        }
+        "created" => {
+            let (start, end) =
+                parse_search_date_range(value.as_str()).map_err(SearchQueryError::InvalidDate)?;
+            Ok(match op {
+                QueryOp::Eq => vec![
+                    SearchFilter::CreatedAfter(start),
+                    SearchFilter::CreatedBefore(end),
+                ],
+                QueryOp::Gt => vec![SearchFilter::CreatedAfter(end)],
+                QueryOp::Ge => vec![SearchFilter::CreatedAfter(start)],
+                QueryOp::Lt => vec![SearchFilter::CreatedBefore(start)],
+                QueryOp::Le => vec![SearchFilter::CreatedBefore(end)],
+            })
+        }
+        //at least, the only one MinWinPercent can say
+        "winrate" => match op {
+            QueryOp::Ge => Ok(vec![SearchFilter::MinWinPercent(parse_query_number(
+                key,
+                value.trim_end_matches('%'),
+            )?)]),
+            _ => Err(unsupported()),
+        },
+        "games" | "played" => {
+            let games: u32 = parse_query_number(key, value.as_str())?;
+            match op {
+                QueryOp::Ge | QueryOp::Eq => Ok(vec![SearchFilter::MinGamesPlayed(games)]),
+                QueryOp::Gt => Ok(vec![SearchFilter::MinGamesPlayed(
+                    games
+                        .checked_add(1)
+                        .ok_or_else(|| SearchQueryError::InvalidNumber {
+                            key: key.to_string(),
+                            value: value.clone(),
+                        })?,
+                )]),
+                _ => Err(unsupported()),
+            }
+        }
+        _ => Err(SearchQueryError::UnknownKey(key.to_string())),
+    }
+}
+
+// Compiles the search box text to the filters update_display_builds runs, empty query is no filters
+// Bare words go together into one ranked name search, quoted phrases have to be in the name as is
+pub fn parse_search_query(
+    query: &str,
+    ca_game: &CaGame,
+) -> Result<Vec<SearchFilter>, SearchQueryError> {
+    let mut filters = vec![];
+    let mut name_words = vec![];
+    let mut name_position = 0;
+    for token in tokenize_query(query)? {
+        match split_query_term(&token) {
+            Some((key, op, value)) => {
+                filters.extend(compile_query_term(ca_game, key.as_str(), op, value)?)
+            }
+            None if token.quoted_from.is_some() => filters.push(SearchFilter::Phrase(token.text)),
+            None => {
+                if name_words.is_empty() {
+                    name_position = filters.len();
+                }
+                name_words.push(token.text);
+            }
+        }
+    }
+    if !name_words.is_empty() {
+        filters.insert(name_position, SearchFilter::Name(name_words.join(" ")));
+    }
+    Ok(filters)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::advanced_search_container::parse_search_date;
+
+    fn parse(query: &str) -> Result<Vec<SearchFilter>, SearchQueryError> {
+        parse_search_query(query, &CaGame::Warhammer2)
+    }
+
+    fn date(s: &str) -> chrono::DateTime<chrono::Utc> {
+        parse_search_date(s).unwrap()
+    }
+
+    #[test]
+    fn plain_words_match_name() {
+        assert_eq!(
+            parse("night  goblin").unwrap(),
+            vec![SearchFilter::Name("night goblin".to_string())]
+        );
+        assert_eq!(parse("   ").unwrap(), vec![]);
+    }
+
+    #[test]
+    fn full_example_query() {
+        let filters =
+            parse("faction:GS vs:HE funds:12400 author:felkon created>2021-01 \"night goblin\"")
+                .unwrap();
+        assert_eq!(
+            filters,
+            vec![
+                SearchFilter::Faction("Greenskins".to_string()),
+                SearchFilter::VsFaction("High Elves".to_string()),
+                SearchFilter::FundsRange {
+                    min: 12400,
+                    max: 12400
+                },
+                SearchFilter::CreatedBy("felkon".to_string()),
+                SearchFilter::CreatedAfter(date("2021-02")),
+                SearchFilter::Phrase("night goblin".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn bare_words_join_around_terms() {
+        assert_eq!(
+            parse("gs vs:HE night \"big\" goblin").unwrap(),
+            vec![
+                SearchFilter::Name("gs night goblin".to_string()),
+                SearchFilter::VsFaction("High Elves".to_string()),
+                SearchFilter::Phrase("big".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn faction_names_and_case() {
+        assert_eq!(
+            parse("FACTION:\"high elves\" vs:skaven").unwrap(),
+            vec![
+                SearchFilter::Faction("High Elves".to_string()),
+                SearchFilter::VsFaction("Skaven".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn quoted_values_keep_operators() {
+        assert_eq!(
+            parse("notes:\"vs:HE > cav\" \"a:b\"").unwrap(),
+            vec![
+                SearchFilter::Notes("vs:HE > cav".to_string()),
+                SearchFilter::Phrase("a:b".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn funds_comparisons() {
+        assert_eq!(
+            parse("funds>=9,000 funds<15000").unwrap(),
+            vec![
+                SearchFilter::FundsRange {
+                    min: 9000,
+                    max: u32::MAX
+                },
+                SearchFilter::FundsRange { min: 0, max: 14999 }
+            ]
+        );
+    }
+
+    #[test]
+    fn created_ranges() {
+        assert_eq!(
+            parse("created:2020-12").unwrap(),
+            vec![
+                SearchFilter::CreatedAfter(date("2020-12")),
+                SearchFilter::CreatedBefore(date("2021"))
+            ]
+        );
+        assert_eq!(
+            parse("created<=2021-03-31").unwrap(),
+            vec![SearchFilter::CreatedBefore(date("2021-04-01"))]
+        );
+    }
+
+    #[test]
+    fn win_rate_and_games() {
+        assert_eq!(
+            parse("winrate>=55% games>3").unwrap(),
+            vec![
+                SearchFilter::MinWinPercent(55.0),
+                SearchFilter::MinGamesPlayed(4)
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed_queries() {
+        assert_eq!(
+            parse("gs \"night goblin"),
+            Err(SearchQueryError::UnterminatedQuote { offset: 3 })
+        );
+        assert_eq!(
+            parse("colour:red"),
+            Err(SearchQueryError::UnknownKey("colour".to_string()))
+        );
+        assert_eq!(
+            parse("vs:"),
+            Err(SearchQueryError::EmptyValue("vs".to_string()))
+        );
+        assert_eq!(
+            parse("vs:Orks"),
+            Err(SearchQueryError::UnknownFaction("Orks".to_string()))
+        );
+        assert_eq!(
+            parse("funds:lots"),
+            Err(SearchQueryError::InvalidNumber {
+                key: "funds".to_string(),
+                value: "lots".to_string()
+            })
+        );
+        assert_eq!(
+            parse("author>felkon"),
+            Err(SearchQueryError::UnsupportedOperator {
+                key: "author".to_string(),
+                op: ">".to_string()
+            })
+        );
+        for q in &["winrate>50", "winrate:50", "winrate<50"] {
+            assert!(
+                matches!(parse(q), Err(SearchQueryError::UnsupportedOperator { .. })),
+                "{}",
+                q
+            );
+        }
+        assert_eq!(
+            parse("wins>=50"),
+            Err(SearchQueryError::UnknownKey("wins".to_string()))
+        );
+        assert!(matches!(
+            parse("created>2021-13"),
+            Err(SearchQueryError::InvalidDate(_))
+        ));
+        assert_eq!(
+            parse("games>4294967295"),
+            Err(SearchQueryError::InvalidNumber {
+                key: "games".to_string(),
+                value: "4294967295".to_string()
+            })
+        );
+        for q in &[
+            "created:4294967295",
+            "created:2021-4294967295",
+            "created:2147483647",
+        ] {
+            assert!(
+                matches!(parse(q), Err(SearchQueryError::InvalidDate(_))),
+                "{}",
+                q
+            );
+        }
+    }
+}
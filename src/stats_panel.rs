@@ -0,0 +1,178 @@
+use crate::army_build::ArmyBuild;
+use crate::army_setups_manager::ArmySetupsManager;
+use crate::ca_game::{get_ca_game_title, CaGame};
+use crate::match_log::{get_match_log_file, load_match_log, MatchCounts};
+use crate::match_stats::{get_match_stats, CountsBreakdown, MatchStats};
+use eframe::egui;
+use eframe::egui::{Color32, ScrollArea, Ui};
+
+#[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Default)]
+pub struct StatsPanel {
+    #[serde(skip)]
+    stats: Option<(CaGame, MatchStats)>, // worked out for this game, None to redo
+    #[serde(skip)]
+    error: String,
+    #[serde(default)]
+    selected_build_id: String,
+}
+
+fn format_win_percent(counts: &MatchCounts) -> String {
+    let p = counts.get_win_percent();
+    if p.is_nan() {
+        "".to_string()
+    } else {
+        format!("{:.1}", p)
+    }
+}
+
+fn counts_row_ui(ui: &mut Ui, title: &str, counts: &MatchCounts) {
+    ui.label(title);
+    ui.label(format!("{}", counts.get_games_count()));
+    ui.label(format!("{}", counts.wins));
+    ui.label(format!("{}", counts.losses));
+    ui.label(format!("{}", counts.draws));
+    ui.label(format_win_percent(counts));
+    ui.end_row();
+}
+
+fn counts_grid_ui(ui: &mut Ui, id: &str, title: &str, breakdown: &CountsBreakdown) {
+    egui::Grid::new(id).striped(true).show(ui, |ui| {
+        for header in [title, "Games", "W", "L", "D", "% W"].iter() {
+            ui.label(*header);
+        }
+        ui.end_row();
+        for (key, counts) in breakdown.iter() {
+            counts_row_ui(ui, key.as_str(), counts);
+        }
+    });
+}
+
+impl StatsPanel {
+    fn refresh(&mut self, ca_game: &CaGame, army_setups_manager: &ArmySetupsManager) {
+        let builds: Vec<ArmyBuild> = match army_setups_manager.army_builds.get(ca_game) {
+            Some(army_set) => army_set.iter().cloned().collect(),
+            None => vec![],
+        };
+        let log = match get_match_log_file(ca_game).and_then(|f| load_match_log(f.as_path())) {
+            Ok(log) => log,
+            Err(e) => {
+                self.error = e;
+                Default::default()
+            }
+        };
+        self.stats = Some((ca_game.clone(), get_match_stats(&log, &builds, ca_game)));
+    }
+
+    pub fn central_panel_ui(
+        &mut self,
+        ui: &mut Ui,
+        ca_game: &CaGame,
+        army_setups_manager: &ArmySetupsManager,
+    ) {
+        let is_stale = match self.stats.as_ref() {
+            Some((stats_game, _)) => stats_game != ca_game,
+            None => true,
+        };
+        ui.horizontal(|ui| {
+            ui.heading(format!("{} Results", get_ca_game_title(ca_game)));
+            if ui.button("Refresh").clicked() || is_stale {
+                self.error = "".to_owned();
+                self.refresh(ca_game, army_setups_manager);
+            }
+        });
+        if !self.error.is_empty() {
+            ui.colored_label(Color32::RED, self.error.as_str());
+        }
+
+        let selected_build_id = &mut self.selected_build_id;
+        let stats = match self.stats.as_ref() {
+            Some((_, stats)) => stats,
+            None => return,
+        };
+        if stats.builds.is_empty() {
+            ui.label(
+                "No games recorded yet, record them under a build's Games section in Build Boss",
+            );
+            return;
+        }
+        if stats.unmatched_games > 0 {
+            ui.label(format!(
+                "{} games are for builds that aren't loaded",
+                stats.unmatched_games
+            ));
+        }
+
+        ScrollArea::auto_sized().show(ui, |ui| {
+            egui::CollapsingHeader::new("Factions")
+                .default_open(true)
+                .show(ui, |ui| {
+                    for f in stats.factions.iter() {
+                        egui::CollapsingHeader::new(format!(
+                            "{}  {} games  {}% W",
+                            f.faction,
+                            f.counts.get_games_count(),
+                            format_win_percent(&f.counts)
+                        ))
+                        .id_source(format!("faction_stats_{}", f.faction))
+                        .default_open(false)
+                        .show(ui, |ui| {
+                            counts_grid_ui(
+                                ui,
+                                format!("faction_vs_{}", f.faction).as_str(),
+                                "Vs",
+                                &f.by_vs_faction,
+                            );
+                        });
+                    }
+                });
+
+            egui::CollapsingHeader::new("Builds")
+                .default_open(true)
+                .show(ui, |ui| {
+                    egui::Grid::new("build_stats_grid")
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for header in ["Build", "Games", "W", "L", "D", "% W"].iter() {
+                                ui.label(*header);
+                            }
+                            ui.end_row();
+                            for b in stats.builds.iter() {
+                                if ui
+                                    .selectable_label(
+                                        b.build_id == *selected_build_id,
+                                        format!("{} ({})", b.name, b.faction),
+                                    )
+                                    .clicked()
+                                {
+                                    *selected_build_id = b.build_id.clone();
+                                }
+                                ui.label(format!("{}", b.counts.get_games_count()));
+                                ui.label(format!("{}", b.counts.wins));
+                                ui.label(format!("{}", b.counts.losses));
+                                ui.label(format!("{}", b.counts.draws));
+                                ui.label(format_win_percent(&b.counts));
+                                ui.end_row();
+                            }
+                        });
+                });
+
+            if let Some(b) = stats
+                .builds
+                .iter()
+                .find(|b| b.build_id == *selected_build_id)
+            {
+                egui::CollapsingHeader::new(format!("{} Matchups", b.name))
+                    .id_source("selected_build_stats")
+                    .default_open(true)
+                    .show(ui, |ui| {
+                        counts_grid_ui(ui, "build_vs_stats", "Vs", &b.by_vs_faction);
+                        ui.separator();
+                        counts_grid_ui(ui, "build_funds_stats", "Funds", &b.by_funds);
+                        ui.separator();
+                        counts_grid_ui(ui, "build_month_stats", "Month", &b.by_month);
+                    });
+            }
+        });
+    }
+}
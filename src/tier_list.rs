@@ -0,0 +1,385 @@
+use crate::army_build::ArmyBuild;
+use crate::army_setups_folder::get_owaagh_appdata_dir;
+use crate::ca_game::{get_ca_game_subfolder, CaGame};
+use crate::faction_registry::get_faction_registry;
+use crate::library_store::write_file_atomic;
+use enum_iterator::IntoEnumIterator;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const TIER_LISTS_FILE: &str = "tier_lists.json";
+
+#[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, Copy, PartialEq, IntoEnumIterator)]
+pub enum Tier {
+    S,
+    A,
+    B,
+    C,
+    D,
+    F,
+}
+
+//"S", "s tier", "Tier A" all count, anything else isn't a tier
+pub fn get_tier_from_str(s: &str) -> Option<Tier> {
+    let s = s.trim().to_ascii_lowercase();
+    let s = s
+        .trim_start_matches("tier")
+        .trim_end_matches("tier")
+        .trim()
+        .to_string();
+    Tier::into_enum_iter().find(|t| format!("{:?}", t).eq_ignore_ascii_case(s.as_str()))
+}
+
+#[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TierEntryKind {
+    Faction,
+    Build,
+}
+
+#[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct TierEntry {
+    pub kind: TierEntryKind,
+    pub key: String,   // registry faction name or build_id
+    pub label: String, // shown, the build's name when it was added
+    #[serde(default)]
+    pub note: String,
+}
+
+impl TierEntry {
+    pub fn new_faction(faction_name: &str) -> Self {
+        Self {
+            kind: TierEntryKind::Faction,
+            key: faction_name.to_string(),
+            label: faction_name.to_string(),
+            note: String::new(),
+        }
+    }
+
+    pub fn new_build(build: &ArmyBuild) -> Self {
+        Self {
+            kind: TierEntryKind::Build,
+            key: build.get_identity(),
+            label: build.file_stem.clone(),
+            note: String::new(),
+        }
+    }
+}
+
+#[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct TierRow {
+    pub tier: Tier,
+    pub entries: Vec<TierEntry>,
+}
+
+#[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct TierList {
+    pub name: String,
+    pub ca_game: CaGame,
+    pub patch: String,      // free text, "1.12" or "Twisted & Twilight"
+    pub rows: Vec<TierRow>, // one per tier, S first
+}
+
+impl TierList {
+    pub fn new(name: &str, ca_game: &CaGame, patch: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ca_game: ca_game.clone(),
+            patch: patch.to_string(),
+            rows: Tier::into_enum_iter()
+                .map(|tier| TierRow {
+                    tier,
+                    entries: vec![],
+                })
+                .collect(),
+        }
+    }
+
+    pub fn get_title(&self) -> String {
+        if self.patch.is_empty() {
+            self.name.clone()
+        } else {
+            format!("{} ({})", self.name, self.patch)
+        }
+    }
+
+    pub fn contains(&self, kind: &TierEntryKind, key: &str) -> bool {
+        self.rows
+            .iter()
+            .flat_map(|r| r.entries.iter())
+            .any(|e| e.kind == *kind && e.key == key)
+    }
+
+    //Each faction or build goes in once, returns false when it's already placed
+    pub fn add_entry(&mut self, tier: Tier, entry: TierEntry) -> bool {
+        if self.contains(&entry.kind, entry.key.as_str()) {
+            return false;
+        }
+        match self.rows.iter_mut().find(|r| r.tier == tier) {
+            Some(row) => row.entries.push(entry),
+            None => self.rows.push(TierRow {
+                tier,
+                entries: vec![entry],
+            }),
+        }
+        true
+    }
+
+    pub fn remove_entry(&mut self, row: usize, index: usize) -> Option<TierEntry> {
+        let entries = &mut self.rows.get_mut(row)?.entries;
+        if index < entries.len() {
+            Some(entries.remove(index))
+        } else {
+            None
+        }
+    }
+
+    //to_index is where it lands counting the entry being moved as gone, clamped to the row end
+    pub fn move_entry(&mut self, from: (usize, usize), to: (usize, usize)) -> bool {
+        if to.0 >= self.rows.len() {
+            return false;
+        }
+        let entry = match self.remove_entry(from.0, from.1) {
+            Some(e) => e,
+            None => return false,
+        };
+        let entries = &mut self.rows[to.0].entries;
+        let index = to.1.min(entries.len());
+        entries.insert(index, entry);
+        true
+    }
+}
+
+//Splits csv text into rows of cells, handles quoted cells with commas, "" & line breaks
+fn parse_csv_rows(csv: &str) -> Vec<Vec<String>> {
+    let mut rows = vec![];
+    let mut row = vec![];
+    let mut cell = String::new();
+    let mut in_quotes = false;
+    let mut chars = csv.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                cell.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => row.push(std::mem::take(&mut cell)),
+            '\r' if !in_quotes => {}
+            '\n' if !in_quotes => {
+                row.push(std::mem::take(&mut cell));
+                rows.push(std::mem::take(&mut row));
+            }
+            c => cell.push(c),
+        }
+    }
+    if !cell.is_empty() || !row.is_empty() {
+        row.push(cell);
+        rows.push(row);
+    }
+    rows
+}
+
+fn get_csv_entry(cell: &str, ca_game: &CaGame, builds: &[ArmyBuild]) -> Option<TierEntry> {
+    if let Some(f) = get_faction_registry().find_faction(ca_game, cell) {
+        return Some(TierEntry::new_faction(f.name.as_str()));
+    }
+    builds
+        .iter()
+        .find(|b| b.file_stem.eq_ignore_ascii_case(cell))
+        .map(TierEntry::new_build)
+}
+
+// Reads the layouts community sheets get exported in
+//   row per tier      S,Greenskins,Dark Elves
+//   column per tier   a header of S,A,B... with entries under each
+//   pairs             Greenskins,S   or   S,Greenskins with a header row
+// Cells that aren't a known faction or build name come back as skipped
+pub fn parse_tier_list_csv(
+    csv: &str,
+    list: &mut TierList,
+    builds: &[ArmyBuild],
+) -> Result<Vec<String>, String> {
+    let ca_game = list.ca_game.clone();
+    let mut skipped = vec![];
+    let mut column_tiers: Vec<Option<Tier>> = vec![];
+    let mut n_added = 0;
+
+    for row in parse_csv_rows(csv) {
+        let cells: Vec<&str> = row.iter().map(|c| c.trim()).collect();
+        let filled: Vec<&str> = cells.iter().copied().filter(|c| !c.is_empty()).collect();
+        if filled.is_empty() {
+            continue;
+        }
+
+        let mut placed = vec![];
+        if filled.len() > 1 && filled.iter().all(|c| get_tier_from_str(c).is_some()) {
+            column_tiers = cells.iter().map(|c| get_tier_from_str(c)).collect();
+            continue;
+        } else if let Some(tier) = get_tier_from_str(filled[0]) {
+            placed.extend(filled[1..].iter().map(|c| (tier, *c)));
+        } else if let Some(tier) = filled[1..].iter().find_map(|c| get_tier_from_str(c)) {
+            placed.push((tier, filled[0]));
+        } else if !column_tiers.is_empty() {
+            for (i, c) in cells.iter().enumerate() {
+                if let Some(Some(tier)) = column_tiers.get(i) {
+                    if !c.is_empty() {
+                        placed.push((*tier, *c));
+                    }
+                }
+            }
+        } else {
+            //header or notes row
+            continue;
+        }
+
+        for (tier, cell) in placed {
+            match get_csv_entry(cell, &ca_game, builds) {
+                Some(entry) => {
+                    if list.add_entry(tier, entry) {
+                        n_added += 1;
+                    }
+                }
+                None => skipped.push(cell.to_string()),
+            }
+        }
+    }
+
+    if n_added == 0 {
+        return Err("Couldn't find any tiers with factions or builds in dat csv".to_string());
+    }
+    Ok(skipped)
+}
+
+pub fn import_tier_list_csv(
+    file: &Path,
+    list: &mut TierList,
+    builds: &[ArmyBuild],
+) -> Result<Vec<String>, String> {
+    match fs::read_to_string(file) {
+        Ok(csv) => parse_tier_list_csv(csv.as_str(), list, builds),
+        Err(e) => Err(format!("Couldn't read {} {}", file.to_string_lossy(), e)),
+    }
+}
+
+pub fn import_tier_list_json(file: &Path) -> Result<TierList, String> {
+    let s = match fs::read_to_string(file) {
+        Ok(s) => s,
+        Err(e) => return Err(format!("Couldn't read {} {}", file.to_string_lossy(), e)),
+    };
+    match serde_json::from_str::<TierList>(s.as_str()) {
+        Ok(list) => Ok(list),
+        Err(e) => Err(format!("Couldn't parse {} {}", file.to_string_lossy(), e)),
+    }
+}
+
+//Writes "<name> <patch>.json" into the folder to send to others
+pub fn export_tier_list_json(list: &TierList, folder: &Path) -> Result<PathBuf, String> {
+    let s = match serde_json::to_string_pretty(list) {
+        Ok(s) => s,
+        Err(e) => return Err(format!("Couldn't serialize tier list {}", e)),
+    };
+    let stem: String = format!("{} {}", list.name, list.patch)
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == ' ' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let file = folder.join(format!("{}.json", stem));
+    write_file_atomic(file.as_path(), s.as_str())?;
+    Ok(file)
+}
+
+//One file per game holding all its lists
+pub fn get_tier_lists_file(ca_game: &CaGame) -> Result<PathBuf, String> {
+    let p = get_owaagh_appdata_dir()?.join(get_ca_game_subfolder(ca_game));
+    Ok(p.join(TIER_LISTS_FILE))
+}
+
+pub fn load_tier_lists(file: &Path) -> Result<Vec<TierList>, String> {
+    if !file.exists() {
+        return Ok(vec![]);
+    }
+    let s = match fs::read_to_string(file) {
+        Ok(s) => s,
+        Err(e) => return Err(format!("Couldn't read {} {}", file.to_string_lossy(), e)),
+    };
+    match serde_json::from_str::<Vec<TierList>>(s.as_str()) {
+        Ok(lists) => Ok(lists),
+        Err(e) => Err(format!("Couldn't parse {} {}", file.to_string_lossy(), e)),
+    }
+}
+
+pub fn save_tier_lists(file: &Path, lists: &[TierList]) -> Result<(), String> {
+    match serde_json::to_string_pretty(lists) {
+        Ok(s) => write_file_atomic(file, s.as_str()),
+        Err(e) => Err(format!("Couldn't serialize tier lists {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_labels(list: &TierList, tier: Tier) -> Vec<&str> {
+        list.rows
+            .iter()
+            .find(|r| r.tier == tier)
+            .unwrap()
+            .entries
+            .iter()
+            .map(|e| e.label.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn reads_row_column_and_pair_layouts() {
+        let mut list = TierList::new("Ladder", &CaGame::Warhammer2, "1.12");
+        let skipped = parse_tier_list_csv(
+            "Tier,Factions\r\nS,Greenskins,\"Dark Elves\"\nA tier,HE,Mystery Faction\n",
+            &mut list,
+            &[],
+        )
+        .unwrap();
+        assert_eq!(get_labels(&list, Tier::S), vec!["Greenskins", "Dark Elves"]);
+        assert_eq!(get_labels(&list, Tier::A), vec!["High Elves"]);
+        assert_eq!(skipped, vec!["Mystery Faction"]);
+
+        let mut list = TierList::new("Sheet", &CaGame::Warhammer2, "");
+        parse_tier_list_csv("S,A,B\nVC,Dwarfs,\nSkaven,,Norsca\n", &mut list, &[]).unwrap();
+        assert_eq!(get_labels(&list, Tier::S), vec!["Vampire Counts", "Skaven"]);
+        assert_eq!(get_labels(&list, Tier::B), vec!["Norsca"]);
+
+        let mut list = TierList::new("Pairs", &CaGame::Warhammer2, "");
+        parse_tier_list_csv("Faction,Tier\nLizardmen,B\nLizardmen,S\n", &mut list, &[]).unwrap();
+        assert_eq!(get_labels(&list, Tier::B), vec!["Lizardmen"]);
+        assert!(get_labels(&list, Tier::S).is_empty());
+
+        assert!(parse_tier_list_csv("nothing,here\n", &mut list, &[]).is_err());
+    }
+
+    #[test]
+    fn moves_entries_between_and_within_tiers() {
+        let mut list = TierList::new("Moves", &CaGame::Warhammer2, "");
+        for f in ["Greenskins", "Skaven", "Norsca"].iter() {
+            list.add_entry(Tier::S, TierEntry::new_faction(f));
+        }
+        assert!(list.move_entry((0, 0), (0, 2)));
+        assert_eq!(
+            get_labels(&list, Tier::S),
+            vec!["Skaven", "Norsca", "Greenskins"]
+        );
+        assert!(list.move_entry((0, 1), (5, 9)));
+        assert_eq!(get_labels(&list, Tier::F), vec!["Norsca"]);
+        assert!(!list.move_entry((0, 7), (1, 0)));
+    }
+}
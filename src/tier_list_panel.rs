@@ -0,0 +1,387 @@
+use crate::army_build::ArmyBuild;
+use crate::army_setups_manager::ArmySetupsManager;
+use crate::ca_game::CaGame;
+use crate::faction_registry::{get_faction_registry, ALL_FACTIONS};
+use crate::factions::faction_dropdown_button;
+use crate::misc_folders::get_user_dir;
+use crate::tier_list::{
+    export_tier_list_json, get_tier_lists_file, import_tier_list_csv, import_tier_list_json,
+    load_tier_lists, save_tier_lists, Tier, TierEntry, TierEntryKind, TierList,
+};
+use eframe::egui;
+use eframe::egui::{Color32, Pos2, Rect, Sense, Ui};
+use enum_iterator::IntoEnumIterator;
+use std::path::PathBuf;
+
+fn get_tier_color(tier: &Tier) -> Color32 {
+    match tier {
+        Tier::S => Color32::from_rgb(255, 127, 127),
+        Tier::A => Color32::from_rgb(255, 191, 127),
+        Tier::B => Color32::from_rgb(255, 223, 127),
+        Tier::C => Color32::from_rgb(255, 255, 127),
+        Tier::D => Color32::from_rgb(191, 255, 127),
+        Tier::F => Color32::from_rgb(127, 255, 127),
+    }
+}
+
+#[cfg_attr(feature = "persistence", derive(serde::Deserialize, serde::Serialize))]
+#[serde(default)]
+pub struct TierListPanel {
+    #[serde(skip)]
+    lists: Option<(CaGame, Vec<TierList>)>, // loaded for this game, None to reload
+    selected_list: usize,
+    #[serde(skip)]
+    dragging: Option<(usize, usize)>, // row & index of the entry being dragged
+    #[serde(skip)]
+    message: String,
+
+    new_name: String,
+    new_patch: String,
+    add_tier: Tier,
+    add_faction_name: String,
+    add_build_id: String,
+    import_file: String,
+    export_folder: String,
+}
+
+impl Default for TierListPanel {
+    fn default() -> Self {
+        let export_folder = match get_user_dir("Downloads", false) {
+            Ok(p) => p.to_string_lossy().to_string(),
+            Err(_) => String::new(),
+        };
+        Self {
+            lists: None,
+            selected_list: 0,
+            dragging: None,
+            message: "".to_owned(),
+            new_name: "Ladder".to_owned(),
+            new_patch: "".to_owned(),
+            add_tier: Tier::S,
+            add_faction_name: ALL_FACTIONS.to_string(),
+            add_build_id: "".to_owned(),
+            import_file: "".to_owned(),
+            export_folder,
+        }
+    }
+}
+
+impl TierListPanel {
+    fn save(&mut self) {
+        if let Some((ca_game, lists)) = self.lists.as_ref() {
+            if let Err(e) =
+                get_tier_lists_file(ca_game).and_then(|f| save_tier_lists(f.as_path(), lists))
+            {
+                self.message = e;
+            }
+        }
+    }
+
+    fn load(&mut self, ca_game: &CaGame) {
+        let lists = match get_tier_lists_file(ca_game).and_then(|f| load_tier_lists(f.as_path())) {
+            Ok(lists) => lists,
+            Err(e) => {
+                self.message = e;
+                vec![]
+            }
+        };
+        self.lists = Some((ca_game.clone(), lists));
+        self.dragging = None;
+    }
+
+    fn list_select_ui(&mut self, ui: &mut Ui, ca_game: &CaGame) -> bool {
+        let mut changed = false;
+        let TierListPanel {
+            lists,
+            selected_list,
+            new_name,
+            new_patch,
+            ..
+        } = self;
+        let lists = match lists.as_mut() {
+            Some((_, lists)) => lists,
+            None => return false,
+        };
+        let selected_title = match lists.get(*selected_list) {
+            Some(l) => l.get_title(),
+            None => "".to_string(),
+        };
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_label("Tier List")
+                .selected_text(selected_title)
+                .show_ui(ui, |ui| {
+                    for (i, l) in lists.iter().enumerate() {
+                        ui.selectable_value(selected_list, i, l.get_title());
+                    }
+                });
+            if !lists.is_empty() && ui.button("Delete").clicked() {
+                lists.remove(*selected_list);
+                *selected_list = 0;
+                changed = true;
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("Name");
+            ui.text_edit_singleline(new_name);
+            ui.label("Patch");
+            ui.text_edit_singleline(new_patch);
+            if ui.button("New").clicked() && !new_name.trim().is_empty() {
+                lists.push(TierList::new(new_name.trim(), ca_game, new_patch.trim()));
+                *selected_list = lists.len() - 1;
+                changed = true;
+            }
+        });
+        changed
+    }
+
+    //Tier rows with draggable entries, drop lands before whichever entry is under the pointer
+    fn tiers_ui(ui: &mut Ui, list: &mut TierList, dragging: &mut Option<(usize, usize)>) -> bool {
+        let mut row_rects: Vec<Rect> = vec![];
+        let mut entry_rects: Vec<Vec<Rect>> = vec![];
+        let mut remove = None;
+        for (r, row) in list.rows.iter().enumerate() {
+            let mut rects = vec![];
+            let row_response = ui.group(|ui| {
+                ui.horizontal_wrapped(|ui| {
+                    ui.colored_label(get_tier_color(&row.tier), format!("{:?}", row.tier));
+                    ui.separator();
+                    for (i, entry) in row.entries.iter().enumerate() {
+                        let label = match entry.kind {
+                            TierEntryKind::Faction => entry.label.clone(),
+                            TierEntryKind::Build => format!("🗐 {}", entry.label),
+                        };
+                        let response = ui
+                            .selectable_label(*dragging == Some((r, i)), label)
+                            .interact(Sense::drag())
+                            .on_hover_text("Drag to move, right click to remove");
+                        if response.drag_started() {
+                            *dragging = Some((r, i));
+                        }
+                        if response.secondary_clicked() {
+                            remove = Some((r, i));
+                        }
+                        rects.push(response.rect);
+                    }
+                });
+            });
+            row_rects.push(row_response.response.rect);
+            entry_rects.push(rects);
+        }
+
+        if let Some((r, i)) = remove {
+            list.remove_entry(r, i);
+            return true;
+        }
+
+        let input = ui.input();
+        if !input.pointer.any_released() {
+            return false;
+        }
+        let (from, pos) = match (dragging.take(), input.pointer.hover_pos()) {
+            (Some(from), Some(pos)) => (from, pos),
+            _ => return false,
+        };
+        let to_row = match row_rects.iter().position(|rect| rect.contains(pos)) {
+            Some(r) => r,
+            None => return false,
+        };
+        let is_before = |rect: &Rect, pos: Pos2| {
+            pos.y < rect.top() || (pos.y <= rect.bottom() && pos.x < rect.center().x)
+        };
+        let mut to_index = entry_rects[to_row]
+            .iter()
+            .position(|rect| is_before(rect, pos))
+            .unwrap_or(entry_rects[to_row].len());
+        if to_row == from.0 && to_index > from.1 {
+            to_index -= 1;
+        }
+        if (to_row, to_index) == from {
+            return false;
+        }
+        list.move_entry(from, (to_row, to_index))
+    }
+
+    fn add_entry_ui(&mut self, ui: &mut Ui, ca_game: &CaGame, builds: &[ArmyBuild]) -> bool {
+        let mut changed = false;
+        let TierListPanel {
+            lists,
+            selected_list,
+            add_tier,
+            add_faction_name,
+            add_build_id,
+            ..
+        } = self;
+        let list = match lists
+            .as_mut()
+            .and_then(|(_, lists)| lists.get_mut(*selected_list))
+        {
+            Some(l) => l,
+            None => return false,
+        };
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_label("Tier")
+                .selected_text(format!("{:?}", add_tier))
+                .show_ui(ui, |ui| {
+                    for t in Tier::into_enum_iter() {
+                        ui.selectable_value(add_tier, t, format!("{:?}", t));
+                    }
+                });
+        });
+
+        if !get_faction_registry().get_game_factions(ca_game).is_empty() {
+            ui.horizontal(|ui| {
+                faction_dropdown_button(ui, ca_game, add_faction_name, "Faction", false);
+                let is_faction = get_faction_registry()
+                    .find_faction(ca_game, add_faction_name.as_str())
+                    .is_some();
+                if ui.button("Add Faction").clicked() && is_faction {
+                    changed = list
+                        .add_entry(*add_tier, TierEntry::new_faction(add_faction_name.as_str()));
+                }
+            });
+        }
+
+        let selected_build = builds.iter().find(|b| b.get_identity() == *add_build_id);
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_label("Build")
+                .selected_text(selected_build.map(|b| b.file_stem.as_str()).unwrap_or(""))
+                .show_ui(ui, |ui| {
+                    for b in builds.iter() {
+                        ui.selectable_value(add_build_id, b.get_identity(), b.file_stem.as_str());
+                    }
+                });
+            if let Some(b) = selected_build {
+                if ui.button("Add Build").clicked() {
+                    changed = list.add_entry(*add_tier, TierEntry::new_build(b));
+                }
+            }
+        });
+        changed
+    }
+
+    fn import_export_ui(&mut self, ui: &mut Ui, builds: &[ArmyBuild]) -> bool {
+        let mut changed = false;
+        let TierListPanel {
+            lists,
+            selected_list,
+            message,
+            import_file,
+            export_folder,
+            ..
+        } = self;
+        ui.horizontal(|ui| {
+            ui.label("File");
+            ui.text_edit_singleline(import_file)
+                .on_hover_text("A .csv saved from a tier list sheet, or a shared .json tier list");
+        });
+        let file = PathBuf::from(import_file.trim());
+        ui.horizontal(|ui| {
+            if ui.button("Import CSV Into List").clicked() {
+                let list = lists
+                    .as_mut()
+                    .and_then(|(_, lists)| lists.get_mut(*selected_list));
+                *message = match list {
+                    Some(list) => match import_tier_list_csv(file.as_path(), list, builds) {
+                        Ok(skipped) => {
+                            changed = true;
+                            if skipped.is_empty() {
+                                "Imported".to_string()
+                            } else {
+                                format!("Imported, didn't know {}", skipped.join(", "))
+                            }
+                        }
+                        Err(e) => e,
+                    },
+                    None => "Make a tier list to import into first".to_string(),
+                };
+            }
+            if ui.button("Import JSON").clicked() {
+                match (import_tier_list_json(file.as_path()), lists.as_mut()) {
+                    (Ok(imported), Some((ca_game, lists))) => {
+                        if imported.ca_game == *ca_game {
+                            lists.push(imported);
+                            *selected_list = lists.len() - 1;
+                            *message = "Imported".to_string();
+                            changed = true;
+                        } else {
+                            *message = "Dat tier list is for a different game".to_string();
+                        }
+                    }
+                    (Err(e), _) => *message = e,
+                    _ => {}
+                }
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Export Folder");
+            ui.text_edit_singleline(export_folder);
+            if ui.button("Export JSON").clicked() {
+                let list = lists
+                    .as_ref()
+                    .and_then(|(_, lists)| lists.get(*selected_list));
+                if let Some(list) = list {
+                    *message = match export_tier_list_json(
+                        list,
+                        PathBuf::from(export_folder.as_str()).as_path(),
+                    ) {
+                        Ok(f) => format!("Exported {}", f.to_string_lossy()),
+                        Err(e) => e,
+                    };
+                }
+            }
+        });
+        changed
+    }
+
+    pub fn central_panel_ui(
+        &mut self,
+        ui: &mut Ui,
+        ca_game: &CaGame,
+        army_setups_manager: &ArmySetupsManager,
+    ) {
+        let is_stale = match self.lists.as_ref() {
+            Some((lists_game, _)) => lists_game != ca_game,
+            None => true,
+        };
+        if is_stale {
+            self.load(ca_game);
+            self.selected_list = 0;
+        }
+        let mut builds: Vec<ArmyBuild> = match army_setups_manager.army_builds.get(ca_game) {
+            Some(army_set) => army_set.iter().cloned().collect(),
+            None => vec![],
+        };
+        builds.sort();
+
+        let mut changed = self.list_select_ui(ui, ca_game);
+
+        if let Some((_, lists)) = self.lists.as_mut() {
+            if let Some(list) = lists.get_mut(self.selected_list) {
+                ui.horizontal(|ui| {
+                    ui.label("Patch");
+                    changed |= ui.text_edit_singleline(&mut list.patch).lost_focus();
+                });
+                changed |= TierListPanel::tiers_ui(ui, list, &mut self.dragging);
+            }
+        }
+
+        egui::CollapsingHeader::new("Add")
+            .default_open(true)
+            .show(ui, |ui| {
+                changed |= self.add_entry_ui(ui, ca_game, builds.as_slice());
+            });
+        egui::CollapsingHeader::new("Import & Export")
+            .default_open(false)
+            .show(ui, |ui| {
+                changed |= self.import_export_ui(ui, builds.as_slice());
+            });
+        if !self.message.is_empty() {
+            ui.label(self.message.as_str());
+        }
+
+        if changed {
+            self.save();
+        }
+    }
+}